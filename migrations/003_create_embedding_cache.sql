@@ -0,0 +1,10 @@
+-- Persistent tier of the embedding cache.
+-- Keys are SHA-256 hashes of (provider name, model, normalised text); vectors
+-- are stored as little-endian f32 blobs. created_at is a Unix timestamp used
+-- for TTL checks.
+
+CREATE TABLE IF NOT EXISTS embedding_cache (
+    key        TEXT    NOT NULL PRIMARY KEY,
+    embedding  BLOB    NOT NULL,
+    created_at INTEGER NOT NULL
+);
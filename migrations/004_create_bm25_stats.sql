@@ -0,0 +1,15 @@
+-- Corpus statistics of the built-in BM25 sparse encoder.
+-- bm25_terms holds the number of stored documents containing each term (keyed
+-- by the term's 32-bit hash); bm25_corpus is a single row with the document
+-- count and the total document length in tokens.
+
+CREATE TABLE IF NOT EXISTS bm25_terms (
+    term     INTEGER NOT NULL PRIMARY KEY,
+    doc_freq INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS bm25_corpus (
+    id           INTEGER NOT NULL PRIMARY KEY CHECK (id = 1),
+    doc_count    INTEGER NOT NULL,
+    total_length INTEGER NOT NULL
+);
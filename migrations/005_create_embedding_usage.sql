@@ -0,0 +1,16 @@
+-- Embedding token usage and cost, aggregated per hour, provider, API key and
+-- session. bucket_start is the Unix timestamp of the start of the hour.
+-- api_key_id is a truncated SHA-256 hash of the caller's X-Api-Key header;
+-- api_key_id and session_id are '' for unattributed calls.
+
+CREATE TABLE IF NOT EXISTS embedding_usage (
+    bucket_start     INTEGER NOT NULL,
+    provider         TEXT    NOT NULL,
+    api_key_id       TEXT    NOT NULL,
+    session_id       TEXT    NOT NULL,
+    calls            INTEGER NOT NULL,
+    tokens           INTEGER NOT NULL,
+    estimated_tokens INTEGER NOT NULL,
+    cost             REAL    NOT NULL,
+    PRIMARY KEY (bucket_start, provider, api_key_id, session_id)
+);
@@ -0,0 +1,17 @@
+-- Entries of the in-process memory store behind the /memory endpoints, kept
+-- when [memory] persistent is set and loaded back into memory on startup.
+-- metadata is a JSON object; parent_id repeats the metadata key of chunked
+-- entries so all chunks of a memory can be deleted together; embedding is a
+-- little-endian f32 blob. created_at is a Unix timestamp.
+
+CREATE TABLE IF NOT EXISTS memory_entries (
+    id         TEXT    NOT NULL PRIMARY KEY,
+    text       TEXT    NOT NULL,
+    metadata   TEXT    NOT NULL,
+    session    TEXT,
+    parent_id  TEXT,
+    embedding  BLOB    NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_memory_entries_parent_id ON memory_entries (parent_id);
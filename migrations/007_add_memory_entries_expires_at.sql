@@ -0,0 +1,7 @@
+-- Expiry of /memory entries stored with ttl_seconds or expires_at, as a Unix
+-- timestamp; NULL keeps the entry indefinitely. The sweeper deletes rows
+-- whose expiry has passed.
+
+ALTER TABLE memory_entries ADD COLUMN expires_at INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_memory_entries_expires_at ON memory_entries (expires_at);
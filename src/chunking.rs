@@ -0,0 +1,315 @@
+//! Splitting long texts into overlapping chunks before embedding.
+//!
+//! Embedding models have a maximum input length; longer texts are either
+//! silently truncated or rejected.  When a `[chunking]` section is configured,
+//! the store endpoints split long input with a [`Chunker`] and store one point
+//! per chunk.  Every chunk carries the reserved metadata keys
+//! [`PARENT_ID_KEY`], [`CHUNK_INDEX_KEY`] and [`CHUNK_COUNT_KEY`], which lets
+//! search collapse chunk hits back to their parent memory.
+//!
+//! Strategies (all sizes are in characters except `tokens`):
+//! - `characters` – fixed-size windows
+//! - `sentences`  – whole sentences packed greedily up to `max_size`
+//! - `paragraphs` – blank-line separated paragraphs packed greedily
+//! - `tokens`     – whitespace-separated words packed up to an approximate
+//!   token budget (one token ≈ four characters)
+//!
+//! Consecutive chunks share up to `overlap` units of trailing context.
+//! Segments that are larger than `max_size` on their own are split into
+//! character windows.
+
+use crate::config::{ChunkStrategy, ChunkingConfig};
+
+/// Payload/metadata key holding the ID shared by all chunks of one memory.
+pub const PARENT_ID_KEY: &str = "parent_id";
+/// Payload/metadata key holding the zero-based position of a chunk.
+pub const CHUNK_INDEX_KEY: &str = "chunk_index";
+/// Payload/metadata key holding the total number of chunks of the parent.
+pub const CHUNK_COUNT_KEY: &str = "chunk_count";
+
+/// Metadata keys reserved for chunk bookkeeping.
+pub const RESERVED_CHUNK_KEYS: [&str; 3] = [PARENT_ID_KEY, CHUNK_INDEX_KEY, CHUNK_COUNT_KEY];
+
+/// Approximate number of characters per token for the `tokens` strategy.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    strategy: ChunkStrategy,
+    max_size: usize,
+    overlap: usize,
+}
+
+/// A piece of the input plus its size in the strategy's unit.
+struct Segment<'a> {
+    text: &'a str,
+    size: usize,
+}
+
+impl Chunker {
+    pub fn new(cfg: &ChunkingConfig) -> Self {
+        Self {
+            strategy: cfg.strategy,
+            max_size: cfg.max_size.max(1),
+            overlap: cfg.overlap.min(cfg.max_size.saturating_sub(1)),
+        }
+    }
+
+    /// Split `text` into chunks.  Text that already fits is returned as a
+    /// single chunk, unchanged.
+    pub fn split(&self, text: &str) -> Vec<String> {
+        if self.measure(text) <= self.max_size {
+            return vec![text.to_string()];
+        }
+
+        match self.strategy {
+            ChunkStrategy::Characters => char_windows(text, self.max_size, self.overlap),
+            ChunkStrategy::Sentences => self.pack(split_sentences(text), " "),
+            ChunkStrategy::Paragraphs => self.pack(split_paragraphs(text), "\n\n"),
+            ChunkStrategy::Tokens => self.pack(text.split_whitespace().collect(), " "),
+        }
+    }
+
+    /// Size of `text` in this strategy's unit.
+    fn measure(&self, text: &str) -> usize {
+        match self.strategy {
+            ChunkStrategy::Tokens => approx_token_count(text),
+            _ => text.chars().count(),
+        }
+    }
+
+    /// Greedily pack `pieces` into chunks of at most `max_size` units, joined
+    /// by `separator`, carrying up to `overlap` units of trailing pieces into
+    /// the next chunk.
+    fn pack(&self, pieces: Vec<&str>, separator: &str) -> Vec<String> {
+        let separator_size = self.measure(separator);
+        let mut segments = Vec::new();
+        for piece in pieces {
+            let size = self.measure(piece);
+            if size <= self.max_size {
+                segments.push(Segment { text: piece, size });
+            } else {
+                // An oversized piece is emitted on its own as character windows.
+                segments.push(Segment {
+                    text: piece,
+                    size: usize::MAX,
+                });
+            }
+        }
+
+        let mut chunks = Vec::new();
+        let mut current: Vec<&Segment> = Vec::new();
+        let mut current_size = 0;
+
+        for segment in &segments {
+            if segment.size == usize::MAX {
+                flush(&mut chunks, &current, separator);
+                current.clear();
+                current_size = 0;
+                let window_chars = match self.strategy {
+                    ChunkStrategy::Tokens => self.max_size * CHARS_PER_TOKEN,
+                    _ => self.max_size,
+                };
+                let overlap_chars = match self.strategy {
+                    ChunkStrategy::Tokens => self.overlap * CHARS_PER_TOKEN,
+                    _ => self.overlap,
+                };
+                chunks.extend(char_windows(segment.text, window_chars, overlap_chars));
+                continue;
+            }
+
+            let added = if current.is_empty() {
+                segment.size
+            } else {
+                separator_size + segment.size
+            };
+            if !current.is_empty() && current_size + added > self.max_size {
+                flush(&mut chunks, &current, separator);
+
+                // Keep trailing segments that fit in the overlap budget.
+                let mut kept = Vec::new();
+                let mut kept_size = 0;
+                for seg in current.iter().rev() {
+                    let extra = if kept.is_empty() {
+                        seg.size
+                    } else {
+                        separator_size + seg.size
+                    };
+                    if kept_size + extra > self.overlap
+                        || kept_size + extra + separator_size + segment.size > self.max_size
+                    {
+                        break;
+                    }
+                    kept_size += extra;
+                    kept.push(*seg);
+                }
+                kept.reverse();
+                current = kept;
+                current_size = kept_size;
+            }
+
+            current_size += if current.is_empty() {
+                segment.size
+            } else {
+                separator_size + segment.size
+            };
+            current.push(segment);
+        }
+        flush(&mut chunks, &current, separator);
+        chunks
+    }
+}
+
+fn flush(chunks: &mut Vec<String>, segments: &[&Segment], separator: &str) {
+    if !segments.is_empty() {
+        let joined: Vec<&str> = segments.iter().map(|s| s.text).collect();
+        chunks.push(joined.join(separator));
+    }
+}
+
+fn approx_tokens(word: &str) -> usize {
+    word.chars().count().div_ceil(CHARS_PER_TOKEN).max(1)
+}
+
+/// Approximate token count of `text`, using the same estimate as the
+/// `tokens` strategy.
+pub fn approx_token_count(text: &str) -> usize {
+    text.split_whitespace().map(approx_tokens).sum()
+}
+
+/// Fixed-size character windows advancing by `size - overlap`.
+fn char_windows(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let step = size.saturating_sub(overlap).max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + size).min(chars.len());
+        chunks.push(chars[start..end].iter().collect());
+        if end == chars.len() {
+            break;
+        }
+        start += step;
+    }
+    chunks
+}
+
+/// Split on sentence-ending punctuation followed by whitespace.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') {
+            if let Some(&(next, n)) = chars.peek() {
+                if n.is_whitespace() {
+                    sentences.push(text[start..next].trim());
+                    start = next;
+                }
+            }
+        } else if i + c.len_utf8() == text.len() {
+            break;
+        }
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+/// Split on blank lines.
+fn split_paragraphs(text: &str) -> Vec<&str> {
+    let mut paragraphs = Vec::new();
+    let mut current_start: Option<usize> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            if let Some(start) = current_start.take() {
+                paragraphs.push(text[start..offset].trim());
+            }
+        } else if current_start.is_none() {
+            current_start = Some(offset);
+        }
+        offset += line.len();
+    }
+    if let Some(start) = current_start {
+        paragraphs.push(text[start..].trim());
+    }
+    paragraphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunker(strategy: ChunkStrategy, max_size: usize, overlap: usize) -> Chunker {
+        Chunker::new(&ChunkingConfig {
+            strategy,
+            max_size,
+            overlap,
+        })
+    }
+
+    #[test]
+    fn short_text_is_a_single_unchanged_chunk() {
+        let c = chunker(ChunkStrategy::Sentences, 100, 10);
+        assert_eq!(
+            c.split("  Short text.  "),
+            vec!["  Short text.  ".to_string()]
+        );
+    }
+
+    #[test]
+    fn character_windows_overlap() {
+        let c = chunker(ChunkStrategy::Characters, 4, 1);
+        assert_eq!(c.split("abcdefghij"), vec!["abcd", "defg", "ghij"]);
+    }
+
+    #[test]
+    fn character_windows_respect_multibyte_boundaries() {
+        let c = chunker(ChunkStrategy::Characters, 2, 0);
+        assert_eq!(c.split("äöüß"), vec!["äö", "üß"]);
+    }
+
+    #[test]
+    fn sentences_are_packed_without_splitting() {
+        let c = chunker(ChunkStrategy::Sentences, 30, 0);
+        let chunks = c.split("First sentence here. Second one! Third sentence? Fourth.");
+        assert_eq!(
+            chunks,
+            vec![
+                "First sentence here.",
+                "Second one! Third sentence?",
+                "Fourth."
+            ]
+        );
+    }
+
+    #[test]
+    fn sentences_carry_overlap_into_next_chunk() {
+        let c = chunker(ChunkStrategy::Sentences, 20, 8);
+        let chunks = c.split("Aaaa aaaa. Bbb. Cccc cccc. Dd.");
+        assert_eq!(chunks, vec!["Aaaa aaaa. Bbb.", "Bbb. Cccc cccc. Dd."]);
+    }
+
+    #[test]
+    fn paragraphs_split_on_blank_lines() {
+        let c = chunker(ChunkStrategy::Paragraphs, 15, 0);
+        let chunks = c.split("First para.\n\nSecond para.\n  \nThird.");
+        assert_eq!(chunks, vec!["First para.", "Second para.", "Third."]);
+    }
+
+    #[test]
+    fn oversized_segment_falls_back_to_character_windows() {
+        let c = chunker(ChunkStrategy::Sentences, 10, 0);
+        let chunks = c.split("Tiny. Averyveryverylongsentence.");
+        assert_eq!(chunks, vec!["Tiny.", "Averyveryv", "erylongsen", "tence."]);
+    }
+
+    #[test]
+    fn tokens_strategy_uses_approximate_token_budget() {
+        // Each 4-letter word is one token; budget of 3 tokens per chunk.
+        let c = chunker(ChunkStrategy::Tokens, 3, 1);
+        let chunks = c.split("aaaa bbbb cccc dddd eeee");
+        assert_eq!(chunks, vec!["aaaa bbbb cccc", "cccc dddd eeee"]);
+    }
+}
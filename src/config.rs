@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fmt, fs};
 
 use serde::Deserialize;
 use tracing::warn;
@@ -9,6 +9,259 @@ pub struct Config {
     pub embedding: EmbeddingConfig,
     pub qdrant: Option<QdrantConfig>,
     pub database: Option<DatabaseConfig>,
+    /// Optional splitting of long texts before embedding.
+    /// Texts are embedded whole when the `[chunking]` section is absent.
+    pub chunking: Option<ChunkingConfig>,
+    /// Settings of the in-process store behind the `/memory` endpoints.
+    /// Entries are kept in memory only when the `[memory]` section is absent.
+    pub memory: Option<MemoryConfig>,
+}
+
+/// A secret from the config, such as an API key.
+///
+/// Its `Debug` output is a placeholder, so secrets never reach the log through
+/// `{:?}` of a config struct.  Use [`Secret::expose`] where the value is sent.
+#[derive(Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"[redacted]\"")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Resolve the three ways of supplying an API key into `api_key`.
+///
+/// At most one of `api_key`, `api_key_env` and `api_key_file` may be set.  A
+/// referenced variable or file that is missing or empty is an error; trailing
+/// whitespace (such as the newline most secret files end with) is removed.
+fn resolve_api_key(
+    owner: &str,
+    api_key: &mut Option<Secret>,
+    env: Option<&str>,
+    file: Option<&str>,
+) -> Result<(), String> {
+    let sources = [api_key.is_some(), env.is_some(), file.is_some()];
+    if sources.iter().filter(|&&set| set).count() > 1 {
+        return Err(format!(
+            "{owner}: set only one of 'api_key', 'api_key_env' and 'api_key_file'"
+        ));
+    }
+
+    let (value, source) = if let Some(var) = env {
+        let value = std::env::var(var).map_err(|_| {
+            format!("{owner}: environment variable '{var}' named by 'api_key_env' is not set")
+        })?;
+        (value, format!("environment variable '{var}'"))
+    } else if let Some(path) = file {
+        let value = fs::read_to_string(path)
+            .map_err(|e| format!("{owner}: cannot read 'api_key_file' '{path}': {e}"))?;
+        (value.trim_end().to_string(), format!("file '{path}'"))
+    } else {
+        return Ok(());
+    };
+
+    if value.is_empty() {
+        return Err(format!("{owner}: the API key in {source} is empty"));
+    }
+    *api_key = Some(Secret::from(value));
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChunkingConfig {
+    /// How text is split: `"characters"`, `"sentences"` (default),
+    /// `"paragraphs"` or `"tokens"`.
+    #[serde(default)]
+    pub strategy: ChunkStrategy,
+    /// Maximum size of one chunk, in characters (approximate tokens for the
+    /// `tokens` strategy). Defaults to 2 000.
+    #[serde(default = "default_chunk_max_size")]
+    pub max_size: usize,
+    /// Trailing context repeated at the start of the next chunk, in the same
+    /// unit as `max_size`. Must be smaller than `max_size`. Defaults to 200.
+    #[serde(default = "default_chunk_overlap")]
+    pub overlap: usize,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    Characters,
+    #[default]
+    Sentences,
+    Paragraphs,
+    Tokens,
+}
+
+fn default_chunk_max_size() -> usize {
+    2_000
+}
+
+fn default_chunk_overlap() -> usize {
+    200
+}
+
+impl ChunkingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_size == 0 {
+            return Err("chunking.max_size must be greater than zero".to_string());
+        }
+        if self.overlap >= self.max_size {
+            return Err(format!(
+                "chunking.overlap ({}) must be smaller than chunking.max_size ({})",
+                self.overlap, self.max_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MemoryConfig {
+    /// Also keep entries in the SQLite database configured under
+    /// `[database]`, so they survive restarts. Defaults to `false`.
+    #[serde(default)]
+    pub persistent: bool,
+    /// Approximate nearest-neighbour index used by `GET /memory/search`.
+    #[serde(default)]
+    pub index: HnswConfig,
+    /// Limits on the store's size and which entries are evicted to keep it
+    /// within them.
+    #[serde(default)]
+    pub capacity: CapacityConfig,
+}
+
+/// HNSW index settings, under `[memory.index]`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct HnswConfig {
+    /// Links per node (twice as many on the bottom layer). Higher improves
+    /// recall at the cost of memory and insert time. Defaults to 16.
+    pub m: usize,
+    /// Candidates considered when linking a new entry. Defaults to 200.
+    pub ef_construction: usize,
+    /// Candidates considered per search; raised to the requested `limit`
+    /// when that is larger. Defaults to 64.
+    pub ef_search: usize,
+    /// Searches over fewer entries of the query's dimensionality scan every
+    /// entry exactly instead. Defaults to 1 000.
+    pub min_entries: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            min_entries: 1_000,
+        }
+    }
+}
+
+impl HnswConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.m < 2 {
+            return Err("memory.index.m must be at least 2".to_string());
+        }
+        if self.ef_construction == 0 || self.ef_search == 0 {
+            return Err(
+                "memory.index.ef_construction and ef_search must be greater than zero".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Capacity limits of the `/memory` store, under `[memory.capacity]`.
+/// The store is unbounded when neither limit is set.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CapacityConfig {
+    /// Maximum number of entries (each chunk counts separately).
+    pub max_entries: Option<usize>,
+    /// Maximum approximate size of the entries in bytes: text, metadata,
+    /// session and embedding.
+    pub max_bytes: Option<usize>,
+    /// Which entries are evicted first when a store exceeds a limit.
+    /// Defaults to `lru`.
+    pub policy: EvictionPolicy,
+    /// Apply the limits to each session tag separately (entries without a
+    /// session share one allowance), evicting only from the session being
+    /// written to. Defaults to `false`.
+    pub per_session: bool,
+    /// Metadata key read by the `importance` policy. Defaults to
+    /// `"importance"`.
+    pub importance_key: String,
+}
+
+impl Default for CapacityConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: None,
+            max_bytes: None,
+            policy: EvictionPolicy::default(),
+            per_session: false,
+            importance_key: "importance".to_string(),
+        }
+    }
+}
+
+impl CapacityConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_entries == Some(0) || self.max_bytes == Some(0) {
+            return Err(
+                "memory.capacity.max_entries and max_bytes must be greater than zero".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionPolicy {
+    /// Least recently returned by a search (or stored, if never returned).
+    #[default]
+    Lru,
+    /// Least often returned by a search; ties go to the least recent.
+    Lfu,
+    /// Stored longest ago.
+    Oldest,
+    /// Lowest numeric value of the `importance_key` metadata; entries
+    /// without a numeric value go first, then the oldest among equals.
+    Importance,
+}
+
+impl EvictionPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EvictionPolicy::Lru => "lru",
+            EvictionPolicy::Lfu => "lfu",
+            EvictionPolicy::Oldest => "oldest",
+            EvictionPolicy::Importance => "importance",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -22,21 +275,108 @@ pub struct DatabaseConfig {
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// How often the config file is checked for changes to reload the
+    /// embedding providers, in milliseconds. `0` disables the check (SIGHUP
+    /// and `POST /admin/reload` still work). Defaults to 2 000.
+    #[serde(default = "default_config_poll_interval_ms")]
+    pub config_poll_interval_ms: u64,
+    /// How often expired memories are deleted from the `/memory` store and
+    /// Qdrant, in seconds. Expired entries are excluded from searches
+    /// regardless. `0` disables the sweep. Defaults to 60.
+    #[serde(default = "default_expiry_sweep_interval_secs")]
+    pub expiry_sweep_interval_secs: u64,
+}
+
+fn default_config_poll_interval_ms() -> u64 {
+    2000
+}
+
+fn default_expiry_sweep_interval_secs() -> u64 {
+    60
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct EmbeddingConfig {
     pub default_provider: String,
     pub providers: HashMap<String, ProviderConfig>,
+    /// Optional embedding cache shared by all providers.
+    /// Caching is disabled when the `[embedding.cache]` section is absent.
+    pub cache: Option<CacheConfig>,
+    /// Retry and circuit-breaker defaults applied to every provider.
+    /// Individual providers can override them with their own `resilience` table.
+    #[serde(default)]
+    pub resilience: ResilienceConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ResilienceConfig {
+    /// Retries after the initial attempt for transient failures (network
+    /// errors, HTTP 429 and 5xx). `0` disables retries. Defaults to 3.
+    pub max_retries: u32,
+    /// Base delay before the first retry; doubled on each further attempt
+    /// and jittered. Defaults to 200 ms.
+    pub initial_backoff_ms: u64,
+    /// Upper bound on a single backoff delay. An upstream `Retry-After`
+    /// longer than this is returned to the caller instead of waited out.
+    /// Defaults to 5 000 ms.
+    pub max_backoff_ms: u64,
+    /// Consecutive failed requests that open the circuit breaker. Defaults to 5.
+    pub failure_threshold: u32,
+    /// Seconds the breaker stays open before admitting a probe request.
+    /// Defaults to 30.
+    pub open_duration_secs: u64,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 5_000,
+            failure_threshold: 5,
+            open_duration_secs: 30,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
+pub struct CacheConfig {
+    /// Maximum number of embeddings held in the in-process LRU tier.
+    /// Defaults to 10 000 when not specified.
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+    /// Seconds after which a cached embedding is treated as stale and
+    /// re-fetched from the provider. Entries never expire when absent.
+    pub ttl_seconds: Option<u64>,
+    /// Also persist cached embeddings in the SQLite database configured under
+    /// `[database]`, so they survive restarts. Defaults to `false`.
+    #[serde(default)]
+    pub persistent: bool,
+}
+
+fn default_cache_max_entries() -> usize {
+    10_000
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct ProviderConfig {
     #[serde(rename = "type")]
     pub provider_type: String,
+    /// Upstream URL; not used by the in-process `hashing` provider.
+    #[serde(default)]
     pub base_url: String,
+    /// Model name; optional for the in-process `hashing` provider.
+    #[serde(default)]
     pub model: String,
-    pub api_key: Option<String>,
+    /// API key sent upstream. Prefer `api_key_env` or `api_key_file` to
+    /// keep the key out of the config file; only one of the three may be set.
+    pub api_key: Option<Secret>,
+    /// Name of an environment variable holding the API key, resolved at load.
+    pub api_key_env: Option<String>,
+    /// Path of a file holding the API key (e.g. a Docker or Kubernetes
+    /// secret), resolved at load.
+    pub api_key_file: Option<String>,
     /// Auth header scheme used when `api_key` is set.
     /// `"bearer"` (default) → `Authorization: Bearer <key>`
     /// `"api-key"`          → `api-key: <key>` (Azure OpenAI style)
@@ -44,6 +384,80 @@ pub struct ProviderConfig {
     /// Path appended to `base_url` to reach the embeddings endpoint.
     /// Defaults to `"/v1/embeddings"` when absent.
     pub embeddings_path: Option<String>,
+    /// Maximum number of texts sent upstream in a single batch request.
+    /// Defaults to the provider's documented limit and is clamped to it.
+    pub max_batch_size: Option<usize>,
+    /// Per-provider override of `[embedding.resilience]`.
+    pub resilience: Option<ResilienceConfig>,
+    /// Declared output dimensionality of the model.
+    /// Required on every provider that takes part in a fallback chain, and
+    /// sets the vector size of the `hashing` provider (default 384).
+    pub dimensions: Option<usize>,
+    /// Providers tried, in order, when this one fails or times out.
+    /// All must declare the same `dimensions` as this provider; their own
+    /// `fallback` lists are not followed.
+    #[serde(default)]
+    pub fallback: Vec<String>,
+    /// Maximum time to wait for each provider in this provider's fallback
+    /// chain before moving on to the next one. No limit when absent.
+    pub fallback_timeout_ms: Option<u64>,
+    /// Upstream requests allowed per minute (each retry counts). Unlimited
+    /// when absent.
+    pub requests_per_minute: Option<u32>,
+    /// Approximate input tokens (≈ 4 characters each) allowed per minute.
+    /// Unlimited when absent.
+    pub tokens_per_minute: Option<u32>,
+    /// Maximum number of upstream requests in flight at once. Unlimited
+    /// when absent.
+    pub max_concurrency: Option<usize>,
+    /// Longest a call may queue for the limits above before it is rejected
+    /// with HTTP 429. Defaults to 10 000 ms.
+    pub max_queue_wait_ms: Option<u64>,
+    /// Price of one million input tokens, in whatever currency the provider
+    /// bills, used to cost each call in `GET /api/usage`. Calls are recorded
+    /// at zero cost when absent.
+    pub cost_per_million_tokens: Option<f64>,
+    /// Text prepended to search queries, e.g. `"search_query: "` for
+    /// nomic-embed-text. Not applied by the `hashing` provider.
+    pub query_prefix: Option<String>,
+    /// Text prepended to stored documents, e.g. `"search_document: "` for
+    /// nomic-embed-text. Not applied by the `hashing` provider.
+    pub document_prefix: Option<String>,
+    /// Size of the returned vectors for models trained for truncation
+    /// (Matryoshka). Sent upstream by the `openai` provider and applied by
+    /// truncating the model output otherwise. Not used by `hashing`, which
+    /// takes its size from `dimensions`.
+    pub output_dimensions: Option<usize>,
+    /// L2-normalise returned vectors (after truncation). Defaults to `false`;
+    /// `tei` sends it to the server, which normalises when it is unset.
+    #[serde(default)]
+    pub normalize: Option<bool>,
+    /// `voyage`, `cohere` and `tei`: whether over-long inputs are truncated
+    /// (`true`) or rejected (`false`) upstream. The upstream default applies
+    /// when absent.
+    pub truncation: Option<bool>,
+    /// `voyage` and `cohere`: numeric type of the returned components, one of
+    /// `"float"`, `"int8"` or `"uint8"`. Defaults to `float`.
+    pub output_dtype: Option<String>,
+    /// `command` only: executable started once and kept running to serve
+    /// embedding requests over stdin/stdout.
+    pub command: Option<String>,
+    /// `command` only: arguments passed to `command`.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Timeouts, connection pooling, proxy, headers and trusted CAs for the
+    /// upstream HTTP client. `command` only uses `request_timeout_ms`.
+    #[serde(flatten)]
+    pub http: HttpClientConfig,
+}
+
+impl ProviderConfig {
+    /// Whether any of the rate-limiting settings is configured.
+    pub fn has_rate_limits(&self) -> bool {
+        self.requests_per_minute.is_some()
+            || self.tokens_per_minute.is_some()
+            || self.max_concurrency.is_some()
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -57,7 +471,11 @@ pub struct QdrantConfig {
     pub collection: String,
     /// Optional API key for Qdrant Cloud or secured instances.
     /// Can be overridden by the `QDRANT_API_KEY` environment variable.
-    pub api_key: Option<String>,
+    pub api_key: Option<Secret>,
+    /// Name of an environment variable holding the API key, resolved at load.
+    pub api_key_env: Option<String>,
+    /// Path of a file holding the API key, resolved at load.
+    pub api_key_file: Option<String>,
     /// Dimensionality of the stored vectors. Must match the embedding model output.
     /// Defaults to 768 when not specified.
     #[serde(default = "default_dimensions")]
@@ -67,6 +485,13 @@ pub struct QdrantConfig {
     /// Must be chosen to match the embedding model's geometry.
     #[serde(default = "default_distance")]
     pub distance: String,
+    /// Timeouts, connection pooling, proxy, headers and trusted CAs for the
+    /// Qdrant HTTP client.
+    #[serde(flatten)]
+    pub http: HttpClientConfig,
+    /// Sparse lexical vectors stored next to each dense embedding, enabling
+    /// the `sparse` and `hybrid` search modes.
+    pub sparse: Option<SparseConfig>,
 }
 
 fn default_dimensions() -> u32 {
@@ -83,16 +508,261 @@ impl Default for QdrantConfig {
             url: "http://localhost:6333".to_string(),
             collection: "agent_memory".to_string(),
             api_key: None,
+            api_key_env: None,
+            api_key_file: None,
             dimensions: default_dimensions(),
             distance: default_distance(),
+            http: HttpClientConfig::default(),
+            sparse: None,
         }
     }
 }
 
+/// `[qdrant.sparse]`: how sparse vectors are produced.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SparseConfig {
+    /// `"bm25"` (built in) or `"splade"` (a text-embeddings-inference server
+    /// hosting a SPLADE model).
+    #[serde(rename = "type")]
+    pub encoder_type: String,
+    /// Name of the sparse vector in the Qdrant collection. Defaults to
+    /// `"text-sparse"`.
+    #[serde(default = "default_sparse_vector_name")]
+    pub vector_name: String,
+    /// `bm25` only: term-frequency saturation. Defaults to 1.2.
+    #[serde(default = "default_bm25_k1")]
+    pub k1: f32,
+    /// `bm25` only: document-length normalisation, between 0 and 1.
+    /// Defaults to 0.75.
+    #[serde(default = "default_bm25_b")]
+    pub b: f32,
+    /// `splade` only: URL of the server.
+    #[serde(default)]
+    pub base_url: String,
+    /// `splade` only: bearer token, for deployments behind an authenticating
+    /// proxy.
+    pub api_key: Option<Secret>,
+    /// Name of an environment variable holding the API key, resolved at load.
+    pub api_key_env: Option<String>,
+    /// Path of a file holding the API key, resolved at load.
+    pub api_key_file: Option<String>,
+    /// `splade` only: HTTP client settings.
+    #[serde(flatten)]
+    pub http: HttpClientConfig,
+}
+
+fn default_sparse_vector_name() -> String {
+    "text-sparse".to_string()
+}
+
+fn default_bm25_k1() -> f32 {
+    1.2
+}
+
+fn default_bm25_b() -> f32 {
+    0.75
+}
+
+impl SparseConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.encoder_type.as_str() {
+            "bm25" => {
+                if !self.k1.is_finite() || self.k1 < 0.0 {
+                    return Err("qdrant.sparse.k1 must not be negative".to_string());
+                }
+                if !(0.0..=1.0).contains(&self.b) {
+                    return Err("qdrant.sparse.b must be between 0 and 1".to_string());
+                }
+            }
+            "splade" => {
+                if self.base_url.is_empty() {
+                    return Err("qdrant.sparse of type 'splade' requires 'base_url'".to_string());
+                }
+            }
+            other => {
+                return Err(format!(
+                    "Unknown qdrant.sparse type '{other}': expected \"bm25\" or \"splade\""
+                ))
+            }
+        }
+        if self.vector_name.is_empty() {
+            return Err("qdrant.sparse.vector_name must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// HTTP client settings, accepted directly in each provider table and in
+/// `[qdrant]`.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct HttpClientConfig {
+    /// Time allowed to establish a connection. Defaults to 10 000 ms.
+    pub connect_timeout_ms: Option<u64>,
+    /// Time allowed for a whole request, from connecting to reading the
+    /// response. Defaults to 30 000 ms. Exceeding it fails with HTTP 504.
+    pub request_timeout_ms: Option<u64>,
+    /// Maximum idle connections kept open per host. Unbounded when absent.
+    pub pool_max_idle: Option<usize>,
+    /// Proxy for all requests, e.g. `http://proxy.internal:3128`. When
+    /// absent the `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` environment
+    /// variables apply.
+    pub proxy: Option<String>,
+    /// Extra headers sent with every request. Values are redacted in logs.
+    #[serde(default)]
+    pub headers: HashMap<String, Secret>,
+    /// PEM files of CA certificates trusted in addition to the system roots,
+    /// e.g. for a self-hosted endpoint behind an internal CA.
+    #[serde(default)]
+    pub ca_certs: Vec<String>,
+}
+
+/// Voyage AI API endpoint: the default `base_url` of `voyage` providers, also
+/// used when migrating legacy `claude` providers.
+pub const VOYAGE_BASE_URL: &str = "https://api.voyageai.com";
+
+impl EmbeddingConfig {
+    /// Rewrite providers of the retired `claude` type as `voyage`.
+    ///
+    /// The `claude` type posted Voyage models to an Anthropic URL that does
+    /// not serve embeddings.  Such providers now use the `voyage` type, and
+    /// an Anthropic `base_url` is replaced with the Voyage endpoint.  A
+    /// warning is logged for each migrated provider so the config gets
+    /// updated; the API key must be a Voyage AI key.
+    pub fn migrate_legacy_providers(&mut self) {
+        for (name, provider) in &mut self.providers {
+            if provider.provider_type != "claude" {
+                continue;
+            }
+            provider.provider_type = "voyage".to_string();
+            if provider.base_url.is_empty() || provider.base_url.contains("api.anthropic.com") {
+                provider.base_url = VOYAGE_BASE_URL.to_string();
+            }
+            warn!(
+                provider = %name,
+                base_url = %provider.base_url,
+                "Provider type 'claude' is deprecated and is treated as 'voyage'; \
+                 set type = \"voyage\" and use a Voyage AI API key"
+            );
+        }
+    }
+
+    /// Check cross-provider constraints that serde cannot express.
+    ///
+    /// Fallback chains may only link providers that exist and declare the same
+    /// `dimensions`, otherwise vectors from different members would be
+    /// incomparable.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, provider) in &self.providers {
+            if provider
+                .cost_per_million_tokens
+                .is_some_and(|cost| !cost.is_finite() || cost < 0.0)
+            {
+                return Err(format!(
+                    "Provider '{name}' has a negative or invalid 'cost_per_million_tokens'"
+                ));
+            }
+            if let Some(output) = provider.output_dimensions {
+                if output == 0 {
+                    return Err(format!("Provider '{name}' has 'output_dimensions' of zero"));
+                }
+                if provider.dimensions.is_some_and(|d| d != output) {
+                    return Err(format!(
+                        "Provider '{name}' declares 'dimensions' different from 'output_dimensions'"
+                    ));
+                }
+            }
+        }
+
+        for (name, provider) in &self.providers {
+            if provider.fallback.is_empty() {
+                continue;
+            }
+            let Some(dimensions) = provider.dimensions else {
+                return Err(format!(
+                    "Provider '{name}' declares a fallback chain but no 'dimensions'"
+                ));
+            };
+            for fallback_name in &provider.fallback {
+                if fallback_name == name {
+                    return Err(format!("Provider '{name}' lists itself as a fallback"));
+                }
+                let fallback = self.providers.get(fallback_name).ok_or_else(|| {
+                    format!("Provider '{name}' falls back to unknown provider '{fallback_name}'")
+                })?;
+                match fallback.dimensions {
+                    Some(d) if d == dimensions => {}
+                    Some(d) => {
+                        return Err(format!(
+                            "Provider '{name}' ({dimensions} dimensions) cannot fall back to \
+                             '{fallback_name}' ({d} dimensions)"
+                        ))
+                    }
+                    None => {
+                        return Err(format!(
+                        "Fallback provider '{fallback_name}' of '{name}' must declare 'dimensions'"
+                    ))
+                    }
+                }
+                if fallback.model != provider.model {
+                    warn!(
+                        provider = %name,
+                        fallback = %fallback_name,
+                        "Fallback provider serves a different model; vectors may not be comparable"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Config {
+    /// Replace every `api_key_env` / `api_key_file` reference with the key it
+    /// points to.
+    fn resolve_secrets(&mut self) -> Result<(), String> {
+        for (name, provider) in &mut self.embedding.providers {
+            resolve_api_key(
+                &format!("Provider '{name}'"),
+                &mut provider.api_key,
+                provider.api_key_env.as_deref(),
+                provider.api_key_file.as_deref(),
+            )?;
+        }
+        if let Some(qdrant) = &mut self.qdrant {
+            resolve_api_key(
+                "[qdrant]",
+                &mut qdrant.api_key,
+                qdrant.api_key_env.as_deref(),
+                qdrant.api_key_file.as_deref(),
+            )?;
+            if let Some(sparse) = &mut qdrant.sparse {
+                resolve_api_key(
+                    "[qdrant.sparse]",
+                    &mut sparse.api_key,
+                    sparse.api_key_env.as_deref(),
+                    sparse.api_key_file.as_deref(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let contents = fs::read_to_string(path)?;
         let mut config: Config = toml::from_str(&contents)?;
+        config.embedding.migrate_legacy_providers();
+        config.resolve_secrets()?;
+        config.embedding.validate()?;
+        if let Some(chunking) = &config.chunking {
+            chunking.validate()?;
+        }
+        if let Some(sparse) = config.qdrant.as_ref().and_then(|q| q.sparse.as_ref()) {
+            sparse.validate()?;
+        }
+        if let Some(memory) = &config.memory {
+            memory.index.validate()?;
+            memory.capacity.validate()?;
+        }
 
         // QDRANT_URL is the sole trigger for enabling Qdrant when no [qdrant]
         // section is present in the config file.  The other two variables only
@@ -112,7 +782,7 @@ impl Config {
                 }
             }
             if let Ok(api_key) = std::env::var("QDRANT_API_KEY") {
-                qdrant.api_key = Some(api_key);
+                qdrant.api_key = Some(Secret::from(api_key));
             }
         } else if std::env::var("QDRANT_COLLECTION").is_ok()
             || std::env::var("QDRANT_API_KEY").is_ok()
@@ -138,3 +808,341 @@ impl Config {
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_embedding(toml_str: &str) -> EmbeddingConfig {
+        toml::from_str(toml_str).expect("embedding config should parse")
+    }
+
+    #[test]
+    fn validate_accepts_chain_with_matching_dimensions() {
+        let cfg = parse_embedding(
+            r#"
+            default_provider = "primary"
+            [providers.primary]
+            type = "ollama"
+            base_url = "http://a:11434"
+            model = "nomic-embed-text"
+            dimensions = 768
+            fallback = ["secondary"]
+            [providers.secondary]
+            type = "ollama"
+            base_url = "http://b:11434"
+            model = "nomic-embed-text"
+            dimensions = 768
+            "#,
+        );
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_chain_with_different_dimensions() {
+        let cfg = parse_embedding(
+            r#"
+            default_provider = "primary"
+            [providers.primary]
+            type = "ollama"
+            base_url = "http://a:11434"
+            model = "nomic-embed-text"
+            dimensions = 768
+            fallback = ["secondary"]
+            [providers.secondary]
+            type = "openai"
+            base_url = "https://api.openai.com"
+            model = "text-embedding-3-small"
+            dimensions = 1536
+            "#,
+        );
+        let err = cfg.validate().unwrap_err();
+        assert!(err.contains("768") && err.contains("1536"), "{err}");
+    }
+
+    #[test]
+    fn validate_rejects_chain_without_declared_dimensions() {
+        let cfg = parse_embedding(
+            r#"
+            default_provider = "primary"
+            [providers.primary]
+            type = "ollama"
+            base_url = "http://a:11434"
+            model = "nomic-embed-text"
+            fallback = ["secondary"]
+            [providers.secondary]
+            type = "ollama"
+            base_url = "http://b:11434"
+            model = "nomic-embed-text"
+            dimensions = 768
+            "#,
+        );
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_fallback() {
+        let cfg = parse_embedding(
+            r#"
+            default_provider = "primary"
+            [providers.primary]
+            type = "ollama"
+            base_url = "http://a:11434"
+            model = "nomic-embed-text"
+            dimensions = 768
+            fallback = ["missing"]
+            "#,
+        );
+        assert!(cfg.validate().unwrap_err().contains("missing"));
+    }
+
+    #[test]
+    fn validate_rejects_conflicting_output_dimensions() {
+        let cfg = parse_embedding(
+            r#"
+            default_provider = "openai"
+            [providers.openai]
+            type = "openai"
+            base_url = "https://api.openai.com"
+            model = "text-embedding-3-large"
+            dimensions = 3072
+            output_dimensions = 256
+            "#,
+        );
+        assert!(cfg.validate().unwrap_err().contains("output_dimensions"));
+    }
+
+    #[test]
+    fn validate_rejects_negative_cost() {
+        let cfg = parse_embedding(
+            r#"
+            default_provider = "openai"
+            [providers.openai]
+            type = "openai"
+            base_url = "https://api.openai.com"
+            model = "text-embedding-3-small"
+            cost_per_million_tokens = -0.02
+            "#,
+        );
+        assert!(cfg
+            .validate()
+            .unwrap_err()
+            .contains("cost_per_million_tokens"));
+    }
+
+    #[test]
+    fn legacy_claude_provider_is_migrated_to_voyage() {
+        let mut cfg = parse_embedding(
+            r#"
+            default_provider = "claude"
+            [providers.claude]
+            type = "claude"
+            base_url = "https://api.anthropic.com"
+            api_key = "key"
+            model = "voyage-3"
+            [providers.proxy]
+            type = "claude"
+            base_url = "http://voyage-proxy:8080"
+            model = "voyage-3"
+            "#,
+        );
+        cfg.migrate_legacy_providers();
+
+        let claude = &cfg.providers["claude"];
+        assert_eq!(claude.provider_type, "voyage");
+        assert_eq!(claude.base_url, VOYAGE_BASE_URL);
+        assert_eq!(claude.model, "voyage-3");
+        let proxy = &cfg.providers["proxy"];
+        assert_eq!(proxy.provider_type, "voyage");
+        assert_eq!(proxy.base_url, "http://voyage-proxy:8080");
+    }
+
+    fn parse_config(toml_str: &str) -> Config {
+        let base = r#"
+            [server]
+            host = "127.0.0.1"
+            port = 8080
+        "#;
+        toml::from_str(&format!("{base}\n{toml_str}")).expect("config should parse")
+    }
+
+    #[test]
+    fn api_keys_are_resolved_from_env_and_file() {
+        let secret_file = std::env::temp_dir().join(format!("qdrant-key-{}", uuid::Uuid::new_v4()));
+        fs::write(&secret_file, "file-secret\n").unwrap();
+        std::env::set_var("CONFIG_TEST_OPENAI_API_KEY", "env-secret");
+
+        let mut cfg = parse_config(&format!(
+            r#"
+            [embedding]
+            default_provider = "openai"
+            [embedding.providers.openai]
+            type = "openai"
+            base_url = "https://api.openai.com"
+            model = "text-embedding-3-small"
+            api_key_env = "CONFIG_TEST_OPENAI_API_KEY"
+            [qdrant]
+            url = "http://localhost:6333"
+            collection = "memories"
+            api_key_file = "{}"
+            "#,
+            secret_file.display()
+        ));
+        cfg.resolve_secrets().unwrap();
+        fs::remove_file(&secret_file).unwrap();
+
+        let openai = &cfg.embedding.providers["openai"];
+        assert_eq!(
+            openai.api_key.as_ref().map(Secret::expose),
+            Some("env-secret")
+        );
+        let qdrant = cfg.qdrant.as_ref().unwrap();
+        assert_eq!(
+            qdrant.api_key.as_ref().map(Secret::expose),
+            Some("file-secret")
+        );
+
+        // Neither key may appear in Debug output.
+        let debug = format!("{cfg:?}");
+        assert!(
+            !debug.contains("env-secret") && !debug.contains("file-secret"),
+            "{debug}"
+        );
+        assert!(debug.contains("[redacted]"));
+    }
+
+    #[test]
+    fn missing_or_conflicting_api_key_sources_are_rejected() {
+        let mut cfg = parse_config(
+            r#"
+            [embedding]
+            default_provider = "openai"
+            [embedding.providers.openai]
+            type = "openai"
+            model = "text-embedding-3-small"
+            api_key_env = "CONFIG_TEST_UNSET_API_KEY"
+            "#,
+        );
+        let err = cfg.resolve_secrets().unwrap_err();
+        assert!(err.contains("CONFIG_TEST_UNSET_API_KEY"), "{err}");
+
+        let mut cfg = parse_config(
+            r#"
+            [embedding]
+            default_provider = "openai"
+            [embedding.providers.openai]
+            type = "openai"
+            model = "text-embedding-3-small"
+            api_key_file = "/nonexistent/openai-key"
+            "#,
+        );
+        let err = cfg.resolve_secrets().unwrap_err();
+        assert!(err.contains("/nonexistent/openai-key"), "{err}");
+
+        let mut cfg = parse_config(
+            r#"
+            [embedding]
+            default_provider = "openai"
+            [embedding.providers.openai]
+            type = "openai"
+            model = "text-embedding-3-small"
+            api_key = "literal"
+            api_key_env = "CONFIG_TEST_OPENAI_API_KEY"
+            "#,
+        );
+        assert!(cfg.resolve_secrets().is_err());
+    }
+
+    #[test]
+    fn chunking_defaults_and_validation() {
+        let cfg: ChunkingConfig = toml::from_str("").unwrap();
+        assert_eq!(cfg.strategy, ChunkStrategy::Sentences);
+        assert!(cfg.validate().is_ok());
+
+        let cfg: ChunkingConfig =
+            toml::from_str("strategy = \"tokens\"\nmax_size = 100\noverlap = 100").unwrap();
+        assert_eq!(cfg.strategy, ChunkStrategy::Tokens);
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn http_client_settings_are_read_from_provider_and_qdrant_tables() {
+        let config = parse_config(
+            r#"
+            [embedding]
+            default_provider = "openai"
+
+            [embedding.providers.openai]
+            type = "openai"
+            base_url = "https://api.openai.com"
+            connect_timeout_ms = 2000
+            request_timeout_ms = 15000
+            pool_max_idle = 4
+            proxy = "http://proxy.internal:3128"
+            ca_certs = ["/etc/ssl/internal-ca.pem"]
+
+            [embedding.providers.openai.headers]
+            OpenAI-Organization = "org-123"
+
+            [qdrant]
+            url = "https://qdrant.internal:6333"
+            collection = "memories"
+            request_timeout_ms = 5000
+            "#,
+        );
+
+        let http = &config.embedding.providers["openai"].http;
+        assert_eq!(http.connect_timeout_ms, Some(2000));
+        assert_eq!(http.request_timeout_ms, Some(15000));
+        assert_eq!(http.pool_max_idle, Some(4));
+        assert_eq!(http.proxy.as_deref(), Some("http://proxy.internal:3128"));
+        assert_eq!(http.headers["OpenAI-Organization"].expose(), "org-123");
+        assert_eq!(http.ca_certs, vec!["/etc/ssl/internal-ca.pem"]);
+        assert!(!format!("{http:?}").contains("org-123"));
+
+        let qdrant = config.qdrant.unwrap();
+        assert_eq!(qdrant.http.request_timeout_ms, Some(5000));
+        assert_eq!(qdrant.http.connect_timeout_ms, None);
+    }
+
+    #[test]
+    fn sparse_settings_are_validated() {
+        let parse = |toml_str: &str| -> SparseConfig { toml::from_str(toml_str).unwrap() };
+
+        let bm25 = parse(r#"type = "bm25""#);
+        assert_eq!((bm25.k1, bm25.b), (1.2, 0.75));
+        assert_eq!(bm25.vector_name, "text-sparse");
+        assert!(bm25.validate().is_ok());
+
+        assert!(parse("type = \"bm25\"\nb = 1.5").validate().is_err());
+        assert!(parse(r#"type = "splade""#).validate().is_err());
+        assert!(
+            parse("type = \"splade\"\nbase_url = \"http://splade:8080\"")
+                .validate()
+                .is_ok()
+        );
+        assert!(parse(r#"type = "tfidf""#).validate().is_err());
+    }
+
+    #[test]
+    fn memory_capacity_defaults_and_validation() {
+        let memory: MemoryConfig = toml::from_str("persistent = true").unwrap();
+        let capacity = &memory.capacity;
+        assert_eq!((capacity.max_entries, capacity.max_bytes), (None, None));
+        assert_eq!(capacity.policy, EvictionPolicy::Lru);
+        assert_eq!(capacity.importance_key, "importance");
+        assert!(capacity.validate().is_ok());
+
+        let memory: MemoryConfig = toml::from_str(
+            "[capacity]\nmax_entries = 100\npolicy = \"importance\"\nper_session = true",
+        )
+        .unwrap();
+        assert_eq!(memory.capacity.max_entries, Some(100));
+        assert_eq!(memory.capacity.policy, EvictionPolicy::Importance);
+        assert!(memory.capacity.per_session);
+
+        let capacity: CapacityConfig = toml::from_str("max_bytes = 0").unwrap();
+        assert!(capacity.validate().is_err());
+        assert!(toml::from_str::<CapacityConfig>(r#"policy = "random""#).is_err());
+    }
+}
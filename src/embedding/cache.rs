@@ -0,0 +1,602 @@
+//! Content-addressed embedding cache.
+//!
+//! [`CachedProvider`] wraps any [`EmbeddingProvider`] and serves repeated
+//! requests for the same text from a shared [`EmbeddingCache`] instead of
+//! calling the upstream provider again.
+//!
+//! Entries are keyed by a SHA-256 hash of the provider name, the model and the
+//! whitespace-normalised input text, so two providers serving different models
+//! never share vectors.
+//!
+//! The cache has two tiers:
+//! - an in-process LRU bounded by `max_entries`
+//! - an optional persistent tier in the SQLite `embedding_cache` table
+//!
+//! A hit in the persistent tier is promoted into the LRU tier.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+
+use crate::{config::CacheConfig, error::EmbeddingError};
+
+use super::{DynEmbeddingProvider, Embedding, EmbeddingProvider, InputKind};
+
+// ---------------------------------------------------------------------------
+// EmbeddingCache
+// ---------------------------------------------------------------------------
+
+/// Bounds on how often expired rows are deleted from the persistent tier; the
+/// TTL itself is used in between.
+const MIN_PURGE_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Point-in-time cache counters reported by `/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of embeddings currently held in the in-process tier.
+    pub entries: usize,
+    /// Whether the SQLite-backed tier is active.
+    pub persistent: bool,
+}
+
+/// Two-tier embedding cache shared by every [`CachedProvider`].
+pub struct EmbeddingCache {
+    lru: Mutex<LruTier>,
+    pool: Option<SqlitePool>,
+    ttl: Option<Duration>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EmbeddingCache {
+    /// Build a cache from config.
+    ///
+    /// `pool` backs the persistent tier; it is only used when
+    /// `cfg.persistent` is set.
+    pub fn new(cfg: &CacheConfig, pool: Option<SqlitePool>) -> Self {
+        Self::with_settings(
+            cfg.max_entries,
+            cfg.ttl_seconds.map(Duration::from_secs),
+            pool.filter(|_| cfg.persistent),
+        )
+    }
+
+    fn with_settings(max_entries: usize, ttl: Option<Duration>, pool: Option<SqlitePool>) -> Self {
+        Self {
+            lru: Mutex::new(LruTier::new(max_entries)),
+            pool,
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Current hit/miss counters and LRU size.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.lru.lock().unwrap_or_else(|e| e.into_inner()).len(),
+            persistent: self.pool.is_some(),
+        }
+    }
+
+    /// Look up `key` in the LRU tier, then the persistent tier.
+    async fn get(&self, key: &str) -> Option<Embedding> {
+        let cached = self
+            .lru
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key, self.ttl);
+        let found = match cached {
+            Some(embedding) => Some(embedding),
+            None => self.get_persistent(key).await,
+        };
+
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Store `embedding` under `key` in both tiers.
+    async fn put(&self, key: String, embedding: Embedding) {
+        if let Some(pool) = &self.pool {
+            let result = sqlx::query(
+                "INSERT OR REPLACE INTO embedding_cache (key, embedding, created_at) VALUES (?, ?, ?)",
+            )
+            .bind(&key)
+            .bind(encode_embedding(&embedding))
+            .bind(Utc::now().timestamp())
+            .execute(pool)
+            .await;
+            if let Err(e) = result {
+                warn!(error = %e, "Failed to write embedding to persistent cache");
+            }
+        }
+
+        self.lru
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, embedding);
+    }
+
+    /// Delete every persistent entry older than the TTL, returning how many
+    /// were removed.  Does nothing without a TTL or a persistent tier.
+    pub async fn purge_expired(&self) -> Result<u64, sqlx::Error> {
+        let (Some(pool), Some(ttl)) = (&self.pool, self.ttl) else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now().timestamp().saturating_sub(ttl.as_secs() as i64);
+        let result = sqlx::query("DELETE FROM embedding_cache WHERE created_at <= ?")
+            .bind(cutoff)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Periodically delete expired persistent entries, so the table does not
+    /// keep rows that are never read again.  Does nothing without a TTL or a
+    /// persistent tier.
+    pub fn spawn_purge(self: &Arc<Self>) {
+        let Some(ttl) = self.ttl.filter(|_| self.pool.is_some()) else {
+            return;
+        };
+        let cache = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker =
+                tokio::time::interval(ttl.clamp(MIN_PURGE_INTERVAL, MAX_PURGE_INTERVAL));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match cache.purge_expired().await {
+                    Ok(0) => {}
+                    Ok(removed) => {
+                        info!(removed, "Removed expired embeddings from persistent cache")
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to remove expired embeddings from persistent cache")
+                    }
+                }
+            }
+        });
+    }
+
+    async fn get_persistent(&self, key: &str) -> Option<Embedding> {
+        let pool = self.pool.as_ref()?;
+        let (blob, created_at): (Vec<u8>, i64) =
+            sqlx::query_as("SELECT embedding, created_at FROM embedding_cache WHERE key = ?")
+                .bind(key)
+                .fetch_optional(pool)
+                .await
+                .map_err(|e| warn!(error = %e, "Failed to read persistent embedding cache"))
+                .ok()??;
+
+        if let Some(ttl) = self.ttl {
+            let age = Utc::now().timestamp().saturating_sub(created_at);
+            if age >= ttl.as_secs() as i64 {
+                let result =
+                    sqlx::query("DELETE FROM embedding_cache WHERE key = ? AND created_at = ?")
+                        .bind(key)
+                        .bind(created_at)
+                        .execute(pool)
+                        .await;
+                if let Err(e) = result {
+                    warn!(error = %e, "Failed to delete expired embedding from persistent cache");
+                }
+                return None;
+            }
+        }
+
+        let embedding = decode_embedding(&blob)?;
+        self.lru
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string(), embedding.clone());
+        Some(embedding)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CachedProvider
+// ---------------------------------------------------------------------------
+
+/// An [`EmbeddingProvider`] that consults an [`EmbeddingCache`] before
+/// delegating to the wrapped provider.
+pub struct CachedProvider {
+    inner: DynEmbeddingProvider,
+    cache: Arc<EmbeddingCache>,
+    provider: String,
+    model: String,
+}
+
+impl CachedProvider {
+    pub fn new(
+        inner: DynEmbeddingProvider,
+        cache: Arc<EmbeddingCache>,
+        provider: &str,
+        model: &str,
+    ) -> Self {
+        Self {
+            inner,
+            cache,
+            provider: provider.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    fn key(&self, text: &str, kind: InputKind) -> String {
+        cache_key(&self.provider, &self.model, kind, text)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for CachedProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        let key = self.key(text, kind);
+        if let Some(embedding) = self.cache.get(&key).await {
+            return Ok(embedding);
+        }
+
+        let embedding = self.inner.embed_as(text, kind).await?;
+        self.cache.put(key, embedding.clone()).await;
+        Ok(embedding)
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let keys: Vec<String> = texts.iter().map(|t| self.key(t, kind)).collect();
+
+        let mut results: Vec<Option<Embedding>> = Vec::with_capacity(texts.len());
+        for key in &keys {
+            results.push(self.cache.get(key).await);
+        }
+
+        let missing: Vec<usize> = (0..texts.len()).filter(|&i| results[i].is_none()).collect();
+        if !missing.is_empty() {
+            let miss_texts: Vec<String> = missing.iter().map(|&i| texts[i].clone()).collect();
+            let fetched = self.inner.embed_batch_as(&miss_texts, kind).await?;
+            if fetched.len() != missing.len() {
+                return Err(EmbeddingError::InvalidResponse(format!(
+                    "Expected {} embeddings, provider returned {}",
+                    missing.len(),
+                    fetched.len()
+                )));
+            }
+            for (i, embedding) in missing.into_iter().zip(fetched) {
+                self.cache.put(keys[i].clone(), embedding.clone()).await;
+                results[i] = Some(embedding);
+            }
+        }
+
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Private helpers
+// ---------------------------------------------------------------------------
+
+/// Hash `(provider, model, input kind, normalised text)` into a hex cache key.
+///
+/// Normalisation trims the text and collapses runs of whitespace, so inputs
+/// that differ only in spacing share an entry.  The input kind is only hashed
+/// when given, so keys for unhinted text match those written before input
+/// kinds existed.
+fn cache_key(provider: &str, model: &str, kind: InputKind, text: &str) -> String {
+    let normalised = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut hasher = Sha256::new();
+    let parts = [
+        Some(provider),
+        Some(model),
+        kind.as_str(),
+        Some(normalised.as_str()),
+    ];
+    for part in parts.into_iter().flatten() {
+        hasher.update(part.as_bytes());
+        // NUL separator prevents ("ab", "c") and ("a", "bc") from colliding.
+        hasher.update([0u8]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+pub(crate) fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub(crate) fn decode_embedding(blob: &[u8]) -> Option<Embedding> {
+    if !blob.len().is_multiple_of(4) {
+        return None;
+    }
+    Some(
+        blob.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+/// Fixed-capacity least-recently-used map.
+///
+/// Recency is tracked with a monotonically increasing tick per access; the
+/// `order` index maps ticks back to keys so the oldest entry can be evicted in
+/// O(log n).
+struct LruTier {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, LruEntry>,
+    order: BTreeMap<u64, String>,
+}
+
+struct LruEntry {
+    embedding: Embedding,
+    inserted_at: Instant,
+    tick: u64,
+}
+
+impl LruTier {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&mut self, key: &str, ttl: Option<Duration>) -> Option<Embedding> {
+        let entry = self.entries.get(key)?;
+        if ttl.is_some_and(|ttl| entry.inserted_at.elapsed() >= ttl) {
+            let tick = entry.tick;
+            self.order.remove(&tick);
+            self.entries.remove(key);
+            return None;
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        entry.tick = tick;
+        self.order.insert(tick, key.to_string());
+        Some(entry.embedding.clone())
+    }
+
+    fn insert(&mut self, key: String, embedding: Embedding) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some(old) = self.entries.remove(&key) {
+            self.order.remove(&old.tick);
+        }
+        while self.entries.len() >= self.capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => {
+                    self.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            LruEntry {
+                embedding,
+                inserted_at: Instant::now(),
+                tick: self.tick,
+            },
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::session_store::SessionStore;
+
+    struct CountingProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn embed_as(
+            &self,
+            text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![text.len() as f32, 1.0])
+        }
+    }
+
+    fn make_provider(cache: Arc<EmbeddingCache>) -> (Arc<CountingProvider>, CachedProvider) {
+        let inner = Arc::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let cached = CachedProvider::new(inner.clone(), cache, "ollama", "nomic-embed-text");
+        (inner, cached)
+    }
+
+    #[tokio::test]
+    async fn repeated_text_is_served_from_cache() {
+        let cache = Arc::new(EmbeddingCache::with_settings(10, None, None));
+        let (inner, provider) = make_provider(cache.clone());
+
+        let first = provider.embed("hello world").await.unwrap();
+        let second = provider.embed("  hello   world ").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn keys_differ_by_provider_model_and_input_kind() {
+        let none = InputKind::Unspecified;
+        assert_ne!(
+            cache_key("a", "m", none, "text"),
+            cache_key("b", "m", none, "text")
+        );
+        assert_ne!(
+            cache_key("a", "m1", none, "text"),
+            cache_key("a", "m2", none, "text")
+        );
+        assert_ne!(
+            cache_key("ab", "c", none, "text"),
+            cache_key("a", "bc", none, "text")
+        );
+        assert_ne!(
+            cache_key("a", "m", InputKind::Query, "text"),
+            cache_key("a", "m", InputKind::Document, "text")
+        );
+    }
+
+    #[tokio::test]
+    async fn embed_batch_only_fetches_misses() {
+        let cache = Arc::new(EmbeddingCache::with_settings(10, None, None));
+        let (inner, provider) = make_provider(cache);
+
+        provider.embed("bb").await.unwrap();
+        let texts = vec!["a".to_string(), "bb".to_string(), "ccc".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            embeddings,
+            vec![vec![1.0, 1.0], vec![2.0, 1.0], vec![3.0, 1.0]]
+        );
+        // One call for the initial "bb", then one each for the two misses.
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn lru_evicts_least_recently_used_entry() {
+        let cache = Arc::new(EmbeddingCache::with_settings(2, None, None));
+        let (inner, provider) = make_provider(cache);
+
+        provider.embed("a").await.unwrap();
+        provider.embed("b").await.unwrap();
+        provider.embed("a").await.unwrap(); // refresh "a"
+        provider.embed("c").await.unwrap(); // evicts "b"
+        provider.embed("a").await.unwrap(); // still cached
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+        provider.embed("b").await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_refetched() {
+        let cache = Arc::new(EmbeddingCache::with_settings(
+            10,
+            Some(Duration::ZERO),
+            None,
+        ));
+        let (inner, provider) = make_provider(cache);
+
+        provider.embed("hello").await.unwrap();
+        provider.embed("hello").await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_persistent_entries_are_deleted() {
+        let store = SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        let pool = store.pool().clone();
+        let rows = |pool: SqlitePool| async move {
+            let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM embedding_cache")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            count
+        };
+
+        let cache = Arc::new(EmbeddingCache::with_settings(
+            10,
+            Some(Duration::ZERO),
+            Some(pool.clone()),
+        ));
+        let (_, provider) = make_provider(cache.clone());
+        provider.embed("read back").await.unwrap();
+        provider.embed("swept").await.unwrap();
+        assert_eq!(rows(pool.clone()).await, 2);
+
+        // Reading an expired entry deletes its row.
+        let (key,): (String,) = sqlx::query_as("SELECT key FROM embedding_cache LIMIT 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let fresh = Arc::new(EmbeddingCache::with_settings(
+            10,
+            Some(Duration::ZERO),
+            Some(pool.clone()),
+        ));
+        assert!(fresh.get(&key).await.is_none());
+        assert_eq!(rows(pool.clone()).await, 1);
+
+        // The purge removes the rest.
+        assert_eq!(cache.purge_expired().await.unwrap(), 1);
+        assert_eq!(rows(pool).await, 0);
+    }
+
+    #[tokio::test]
+    async fn persistent_tier_survives_a_fresh_lru() {
+        let store = SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        let pool = store.pool().clone();
+
+        let first = Arc::new(EmbeddingCache::with_settings(10, None, Some(pool.clone())));
+        let (_, provider) = make_provider(first);
+        let stored = provider.embed("remember me").await.unwrap();
+
+        // A new cache instance shares only the database, not the LRU tier.
+        let second = Arc::new(EmbeddingCache::with_settings(10, None, Some(pool)));
+        let (inner, provider) = make_provider(second.clone());
+        let loaded = provider.embed("remember me").await.unwrap();
+
+        assert_eq!(stored, loaded);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 0);
+        assert_eq!(second.stats().hits, 1);
+    }
+}
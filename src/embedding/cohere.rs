@@ -0,0 +1,359 @@
+//! Cohere embedding provider (v2 API).
+//!
+//! API reference: <https://docs.cohere.com/reference/embed>
+//!
+//! Request  → POST {base_url}/v2/embed   (`base_url` defaults to
+//!             `https://api.cohere.com`)
+//! Headers  → `Authorization: Bearer <api_key>`
+//! Body     → `{"model": "…", "texts": ["…", …], "input_type": "search_query"|"search_document",
+//!             "embedding_types": ["float"], "truncate": "END"|"NONE", "output_dimension": N}`
+//! Response → `{"embeddings": {"float": [[…], …]}}`
+//!
+//! Cohere requires an `input_type`; the query hint maps to `search_query` and
+//! both the document hint and the absence of a hint map to `search_document`.
+//! `embedding_types` requests the configured `output_dtype` (default `float`)
+//! and the vectors are read from the matching key.  `truncation = true` is
+//! sent as `"END"`, `false` as `"NONE"`.
+//!
+//! Embeddings are returned in input order.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ProviderConfig, error::EmbeddingError, usage::report_tokens};
+
+use super::{
+    batch_size_from_config, error_from_response, http_client_from_config, output_dtype_from_config,
+    parse_response, Embedding, EmbeddingProvider, InputKind, InputPrefixes, OutputShape,
+};
+
+/// Cohere API endpoint, used when `base_url` is not configured.
+const DEFAULT_BASE_URL: &str = "https://api.cohere.com";
+
+/// Maximum number of texts per request accepted by the Cohere embed endpoint.
+const MAX_BATCH_SIZE: usize = 96;
+
+pub struct CohereProvider {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: String,
+    truncate: Option<&'static str>,
+    embedding_type: String,
+    max_batch_size: usize,
+    prefixes: InputPrefixes,
+    shape: OutputShape,
+}
+
+impl CohereProvider {
+    pub fn new(cfg: &ProviderConfig) -> Result<Self, EmbeddingError> {
+        Ok(Self {
+            client: http_client_from_config(cfg)?,
+            base_url: match cfg.base_url.trim_end_matches('/') {
+                "" => DEFAULT_BASE_URL.to_string(),
+                base_url => base_url.to_string(),
+            },
+            model: cfg.model.clone(),
+            api_key: cfg
+                .api_key
+                .as_ref()
+                .map(|k| k.expose().to_string())
+                .unwrap_or_default(),
+            truncate: cfg.truncation.map(|t| if t { "END" } else { "NONE" }),
+            embedding_type: output_dtype_from_config(cfg)?.unwrap_or_else(|| "float".to_string()),
+            max_batch_size: batch_size_from_config(cfg.max_batch_size, MAX_BATCH_SIZE),
+            prefixes: InputPrefixes::from_config(cfg),
+            shape: OutputShape::from_config(cfg),
+        })
+    }
+
+    async fn request_embeddings(
+        &self,
+        input: &[&str],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        if self.api_key.is_empty() {
+            return Err(EmbeddingError::AuthenticationError);
+        }
+
+        let url = format!("{}/v2/embed", self.base_url);
+        let prefixed = self.prefixes.apply(input, kind);
+        let texts: Vec<&str> = prefixed.iter().map(AsRef::as_ref).collect();
+        let body = CohereRequest {
+            model: &self.model,
+            texts: &texts,
+            input_type: match kind {
+                InputKind::Query => "search_query",
+                InputKind::Document | InputKind::Unspecified => "search_document",
+            },
+            embedding_types: [&self.embedding_type],
+            truncate: self.truncate,
+            output_dimension: self.shape.dimensions(),
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(EmbeddingError::AuthenticationError);
+        }
+        if !status.is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let mut parsed: CohereResponse = parse_response(response, "Cohere").await?;
+
+        if let Some(tokens) = parsed
+            .meta
+            .and_then(|meta| meta.billed_units)
+            .and_then(|units| units.input_tokens)
+        {
+            report_tokens(tokens);
+        }
+        let embeddings = parsed
+            .embeddings
+            .remove(&self.embedding_type)
+            .ok_or_else(|| {
+                EmbeddingError::InvalidResponse(format!(
+                    "Cohere response has no '{}' embeddings",
+                    self.embedding_type
+                ))
+            })?;
+        self.shape.apply(embeddings)
+    }
+}
+
+#[derive(Serialize)]
+struct CohereRequest<'a> {
+    model: &'a str,
+    texts: &'a [&'a str],
+    input_type: &'static str,
+    embedding_types: [&'a str; 1],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncate: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dimension: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct CohereResponse {
+    /// Vectors keyed by embedding type, e.g. `"float"`.
+    embeddings: HashMap<String, Vec<Vec<f32>>>,
+    #[serde(default)]
+    meta: Option<CohereMeta>,
+}
+
+#[derive(Deserialize)]
+struct CohereMeta {
+    #[serde(default)]
+    billed_units: Option<CohereBilledUnits>,
+}
+
+#[derive(Deserialize)]
+struct CohereBilledUnits {
+    #[serde(default)]
+    input_tokens: Option<u64>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for CohereProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.request_embeddings(&[text], kind)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| EmbeddingError::InvalidResponse("Empty embeddings array".to_string()))
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let input: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.request_embeddings(&input, kind).await?;
+        if embeddings.len() != texts.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, Cohere returned {}",
+                texts.len(),
+                embeddings.len()
+            )));
+        }
+        Ok(embeddings)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::config::ProviderConfig;
+
+    fn make_config(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            provider_type: "cohere".to_string(),
+            base_url: base_url.to_string(),
+            model: "embed-english-v3.0".to_string(),
+            api_key: Some("test-cohere-key".into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn embed_batch_sends_texts_and_reads_float_embeddings() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v2/embed"))
+            .and(header("authorization", "Bearer test-cohere-key"))
+            .and(body_json(serde_json::json!({
+                "model": "embed-english-v3.0",
+                "texts": ["first", "second"],
+                "input_type": "search_document",
+                "embedding_types": ["float"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "abc",
+                "embeddings": {"float": [[1.0_f32, 0.0_f32], [0.0_f32, 1.0_f32]]},
+                "texts": ["first", "second"],
+                "meta": {"api_version": {"version": "2"}}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = CohereProvider::new(&make_config(&server.uri())).unwrap();
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Document)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+
+    #[tokio::test]
+    async fn query_kind_and_configured_options_are_sent() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v2/embed"))
+            .and(body_json(serde_json::json!({
+                "model": "embed-v4.0",
+                "texts": ["where is the config?"],
+                "input_type": "search_query",
+                "embedding_types": ["int8"],
+                "truncate": "NONE",
+                "output_dimension": 2
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "embeddings": {"int8": [[-3, 9]]}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = CohereProvider::new(&ProviderConfig {
+            model: "embed-v4.0".to_string(),
+            truncation: Some(false),
+            output_dimensions: Some(2),
+            output_dtype: Some("int8".to_string()),
+            ..make_config(&server.uri())
+        })
+        .unwrap();
+        let embedding = provider
+            .embed_as("where is the config?", InputKind::Query)
+            .await
+            .unwrap();
+        assert_eq!(embedding, vec![-3.0, 9.0]);
+    }
+
+    #[tokio::test]
+    async fn embed_returns_auth_error_on_401() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v2/embed"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("invalid api token"))
+            .mount(&server)
+            .await;
+
+        let provider = CohereProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello").await;
+        assert!(matches!(result, Err(EmbeddingError::AuthenticationError)));
+    }
+
+    #[tokio::test]
+    async fn embed_returns_auth_error_when_api_key_missing() {
+        let provider = CohereProvider::new(&ProviderConfig {
+            api_key: None,
+            ..make_config("http://localhost")
+        })
+        .unwrap();
+        let result = provider.embed("hello").await;
+        assert!(matches!(result, Err(EmbeddingError::AuthenticationError)));
+    }
+
+    #[tokio::test]
+    async fn missing_embedding_type_is_an_invalid_response() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v2/embed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "embeddings": {"int8": [[1, 2]]}
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = CohereProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello").await;
+        assert!(matches!(result, Err(EmbeddingError::InvalidResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_is_reported_with_retry_after() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v2/embed"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "7")
+                    .set_body_string("too many requests"),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = CohereProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::RateLimited {
+                retry_after_secs: Some(7),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn base_url_defaults_to_the_cohere_api() {
+        let provider = CohereProvider::new(&make_config("")).unwrap();
+        assert_eq!(provider.base_url, DEFAULT_BASE_URL);
+    }
+}
@@ -0,0 +1,507 @@
+//! External-process embedding provider.
+//!
+//! Spawns `command` (with `args`) once and keeps it running, exchanging
+//! newline-delimited JSON over its stdin and stdout:
+//!
+//! Request  → `{"id": 1, "texts": ["…", …], "input_type": "query"|"document"}`
+//! Response → `{"id": 1, "vectors": [[…], …]}`
+//!          | `{"id": 1, "error": "…"}`
+//!
+//! Every request carries a fresh `id` and the child may answer in any order,
+//! so several requests can be in flight at once.  `input_type` is sent only
+//! when the caller passes an [`InputKind`] hint.  Stdout lines that are not
+//! valid responses are logged and ignored; stderr is forwarded to the log.
+//!
+//! If the child exits, its in-flight requests fail with a transient error and
+//! the next request starts a new child.  A request that gets no answer within
+//! `request_timeout_ms` (default 30 000 ms) fails with a timeout and the child
+//! is treated as hung: it is killed, its other in-flight requests fail like on
+//! an exit, and the next request starts a new child.  An `error` response is
+//! reported as HTTP 422.
+
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::oneshot,
+};
+use tracing::{info, warn};
+
+use crate::{config::ProviderConfig, error::EmbeddingError, http_client::DEFAULT_REQUEST_TIMEOUT};
+
+use super::{
+    batch_size_from_config, Embedding, EmbeddingProvider, InputKind, InputPrefixes, OutputShape,
+};
+
+/// Upper bound on texts per request; the protocol has no limit of its own.
+const MAX_BATCH_SIZE: usize = 256;
+
+type PendingMap = Mutex<HashMap<u64, oneshot::Sender<Result<Vec<Embedding>, EmbeddingError>>>>;
+
+fn process_exited() -> EmbeddingError {
+    EmbeddingError::ProviderError {
+        status: 503,
+        message: "embedding process exited".to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Running child
+// ---------------------------------------------------------------------------
+
+/// One spawned child process and the requests waiting on it.
+struct Running {
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: Arc<PendingMap>,
+    exited: Arc<AtomicBool>,
+    /// Held so the child is killed when this generation is dropped.
+    _process: Child,
+}
+
+impl Running {
+    fn spawn(provider: &str, program: &str, args: &[String]) -> Result<Self, EmbeddingError> {
+        let mut process = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| EmbeddingError::ProviderError {
+                status: 503,
+                message: format!("failed to start '{program}': {e}"),
+            })?;
+
+        let stdin = process.stdin.take().expect("child stdin is piped");
+        let stdout = process.stdout.take().expect("child stdout is piped");
+        let stderr = process.stderr.take().expect("child stderr is piped");
+
+        let pending: Arc<PendingMap> = Arc::default();
+        let exited = Arc::new(AtomicBool::new(false));
+
+        tokio::spawn(read_responses(
+            provider.to_string(),
+            stdout,
+            pending.clone(),
+            exited.clone(),
+        ));
+        let name = provider.to_string();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                warn!(provider = %name, "{line}");
+            }
+        });
+
+        Ok(Self {
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending,
+            exited,
+            _process: process,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        !self.exited.load(Ordering::SeqCst)
+    }
+
+    /// Mark the child dead and fail every request still waiting on it.
+    fn fail_pending(pending: &PendingMap, exited: &AtomicBool) {
+        exited.store(true, Ordering::SeqCst);
+        let waiters: Vec<_> = pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .collect();
+        for (_, tx) in waiters {
+            let _ = tx.send(Err(process_exited()));
+        }
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+    }
+
+    /// Send one request and wait for its response.
+    async fn request(
+        &self,
+        request: &CommandRequest<'_>,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let mut line = serde_json::to_string(request)
+            .map_err(|e| EmbeddingError::BadRequest(format!("Failed to encode request: {e}")))?;
+        line.push('\n');
+
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request.id, tx);
+        // The reader marks the child dead before draining `pending`, so a
+        // request registered after the drain sees the flag here.
+        if !self.is_alive() {
+            self.forget(request.id);
+            return Err(process_exited());
+        }
+
+        let written = {
+            let mut stdin = self.stdin.lock().await;
+            match stdin.write_all(line.as_bytes()).await {
+                Ok(()) => stdin.flush().await,
+                Err(e) => Err(e),
+            }
+        };
+        if written.is_err() {
+            Self::fail_pending(&self.pending, &self.exited);
+            return Err(process_exited());
+        }
+
+        rx.await.unwrap_or_else(|_| Err(process_exited()))
+    }
+}
+
+/// Read responses from the child's stdout and hand each to its waiter.
+async fn read_responses(
+    provider: String,
+    stdout: ChildStdout,
+    pending: Arc<PendingMap>,
+    exited: Arc<AtomicBool>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response: CommandResponse = match serde_json::from_str(&line) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(provider = %provider, error = %e, "Ignoring unparseable output from embedding process");
+                continue;
+            }
+        };
+        let waiter = pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&response.id);
+        let Some(tx) = waiter else {
+            // The request already timed out.
+            continue;
+        };
+        let result = match (response.vectors, response.error) {
+            (_, Some(message)) => Err(EmbeddingError::ProviderError {
+                status: 422,
+                message,
+            }),
+            (Some(vectors), None) => Ok(vectors),
+            (None, None) => Err(EmbeddingError::InvalidResponse(
+                "Response has neither 'vectors' nor 'error'".to_string(),
+            )),
+        };
+        let _ = tx.send(result);
+    }
+    warn!(provider = %provider, "Embedding process exited");
+    Running::fail_pending(&pending, &exited);
+}
+
+#[derive(Serialize)]
+struct CommandRequest<'a> {
+    id: u64,
+    texts: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input_type: Option<&'static str>,
+}
+
+#[derive(Deserialize)]
+struct CommandResponse {
+    id: u64,
+    #[serde(default)]
+    vectors: Option<Vec<Vec<f32>>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// CommandProvider
+// ---------------------------------------------------------------------------
+
+pub struct CommandProvider {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    next_id: AtomicU64,
+    child: tokio::sync::Mutex<Option<Arc<Running>>>,
+    max_batch_size: usize,
+    prefixes: InputPrefixes,
+    shape: OutputShape,
+}
+
+impl CommandProvider {
+    pub fn new(name: &str, cfg: &ProviderConfig) -> Result<Self, EmbeddingError> {
+        let program = cfg
+            .command
+            .clone()
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| {
+                EmbeddingError::ConfigError(format!(
+                    "Provider '{name}' of type 'command' requires 'command'"
+                ))
+            })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            program,
+            args: cfg.args.clone(),
+            timeout: cfg
+                .http
+                .request_timeout_ms
+                .map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_millis),
+            next_id: AtomicU64::new(1),
+            child: tokio::sync::Mutex::new(None),
+            max_batch_size: batch_size_from_config(cfg.max_batch_size, MAX_BATCH_SIZE),
+            prefixes: InputPrefixes::from_config(cfg),
+            shape: OutputShape::from_config(cfg),
+        })
+    }
+
+    /// The running child, started (or restarted) when there is none.
+    async fn running(&self) -> Result<Arc<Running>, EmbeddingError> {
+        let mut child = self.child.lock().await;
+        if let Some(running) = child.as_ref().filter(|r| r.is_alive()) {
+            return Ok(running.clone());
+        }
+        if child.is_some() {
+            warn!(provider = %self.name, "Restarting embedding process");
+        }
+        let running = Arc::new(Running::spawn(&self.name, &self.program, &self.args)?);
+        info!(provider = %self.name, command = %self.program, "Started embedding process");
+        *child = Some(running.clone());
+        Ok(running)
+    }
+
+    /// Fail the requests waiting on a hung child and drop it from the slot,
+    /// so it is killed once the last in-flight request lets go of it and the
+    /// next request starts a new one.
+    async fn retire(&self, running: &Arc<Running>) {
+        Running::fail_pending(&running.pending, &running.exited);
+        let mut child = self.child.lock().await;
+        if child
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, running))
+        {
+            *child = None;
+        }
+    }
+
+    async fn request_embeddings(
+        &self,
+        input: &[&str],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let running = self.running().await?;
+        let prefixed = self.prefixes.apply(input, kind);
+        let texts: Vec<&str> = prefixed.iter().map(AsRef::as_ref).collect();
+        let request = CommandRequest {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            texts: &texts,
+            input_type: kind.as_str(),
+        };
+
+        let vectors = match tokio::time::timeout(self.timeout, running.request(&request)).await {
+            Ok(result) => result?,
+            Err(_) => {
+                warn!(provider = %self.name, "Embedding process did not respond in time; killing it");
+                self.retire(&running).await;
+                return Err(EmbeddingError::Timeout(format!(
+                    "embedding process for provider '{}' did not respond within {} ms",
+                    self.name,
+                    self.timeout.as_millis()
+                )));
+            }
+        };
+        if vectors.len() != input.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, embedding process returned {}",
+                input.len(),
+                vectors.len()
+            )));
+        }
+        self.shape.apply(vectors)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for CommandProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.request_embeddings(&[text], kind)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| EmbeddingError::InvalidResponse("Empty vectors array".to_string()))
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let input: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.request_embeddings(&input, kind).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HttpClientConfig;
+
+    /// Shell snippet that sets `$id` from the request line in `$line`.
+    const READ_ID: &str = r#"id=$(printf '%s' "$line" | sed 's/^{"id":\([0-9]*\).*/\1/')"#;
+
+    fn shell_provider(script: &str, timeout_ms: u64) -> CommandProvider {
+        CommandProvider::new(
+            "script",
+            &ProviderConfig {
+                provider_type: "command".to_string(),
+                command: Some("sh".to_string()),
+                args: vec!["-c".to_string(), script.to_string()],
+                http: HttpClientConfig {
+                    request_timeout_ms: Some(timeout_ms),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn command_is_required() {
+        let result = CommandProvider::new(
+            "script",
+            &ProviderConfig {
+                provider_type: "command".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(EmbeddingError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn batch_round_trips_over_stdio() {
+        let script = format!(
+            r#"while IFS= read -r line; do {READ_ID}; echo "{{\"id\":$id,\"vectors\":[[1.0,0.0],[0.0,1.0]]}}"; done"#
+        );
+        let provider = shell_provider(&script, 5_000);
+
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Document)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+        // The same child serves later requests.
+        let again = provider
+            .embed_batch_as(&texts, InputKind::Document)
+            .await
+            .unwrap();
+        assert_eq!(again.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_are_matched_by_id() {
+        // Requests containing "slow" are answered after a delay, in the
+        // background, so the fast request's response arrives first.
+        let script = format!(
+            r#"while IFS= read -r line; do {READ_ID}; case "$line" in
+                 *slow*) (sleep 0.3; echo "{{\"id\":$id,\"vectors\":[[9.0]]}}") & ;;
+                 *) echo "{{\"id\":$id,\"vectors\":[[1.0]]}}" ;;
+               esac; done"#
+        );
+        let provider = Arc::new(shell_provider(&script, 5_000));
+
+        let slow = tokio::spawn({
+            let provider = provider.clone();
+            async move { provider.embed("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let fast = provider.embed("fast").await.unwrap();
+
+        assert_eq!(fast, vec![1.0]);
+        assert!(!slow.is_finished());
+        assert_eq!(slow.await.unwrap().unwrap(), vec![9.0]);
+    }
+
+    #[tokio::test]
+    async fn crashed_child_is_restarted_on_next_request() {
+        let marker =
+            std::env::temp_dir().join(format!("command-provider-{}", uuid::Uuid::new_v4()));
+        let marker = marker.display();
+        // The first child exits as soon as it receives a request.
+        let script = format!(
+            r#"if [ -e '{marker}' ]; then
+                 while IFS= read -r line; do {READ_ID}; echo "{{\"id\":$id,\"vectors\":[[2.0]]}}"; done
+               else
+                 touch '{marker}'; read -r line; exit 3
+               fi"#
+        );
+        let provider = shell_provider(&script, 5_000);
+
+        let err = provider.embed("first").await.unwrap_err();
+        assert!(err.is_transient(), "{err:?}");
+        assert_eq!(provider.embed("second").await.unwrap(), vec![2.0]);
+    }
+
+    #[tokio::test]
+    async fn hung_request_times_out() {
+        let marker =
+            std::env::temp_dir().join(format!("command-provider-{}", uuid::Uuid::new_v4()));
+        let marker = marker.display();
+        // The first child swallows its input without ever answering.
+        let script = format!(
+            r#"if [ -e '{marker}' ]; then
+                 while IFS= read -r line; do {READ_ID}; echo "{{\"id\":$id,\"vectors\":[[3.0]]}}"; done
+               else
+                 touch '{marker}'; cat > /dev/null
+               fi"#
+        );
+        let provider = shell_provider(&script, 200);
+
+        let err = provider.embed("anything").await.unwrap_err();
+        assert!(matches!(err, EmbeddingError::Timeout(_)), "{err:?}");
+        // The hung child is replaced, so the next request is answered.
+        assert_eq!(provider.embed("again").await.unwrap(), vec![3.0]);
+    }
+
+    #[tokio::test]
+    async fn error_response_is_reported() {
+        let script = format!(
+            r#"while IFS= read -r line; do {READ_ID}; echo "not json"; echo "{{\"id\":$id,\"error\":\"model not loaded\"}}"; done"#
+        );
+        let provider = shell_provider(&script, 5_000);
+        let err = provider.embed("anything").await.unwrap_err();
+        assert!(
+            matches!(&err, EmbeddingError::ProviderError { status: 422, message } if message == "model not loaded"),
+            "{err:?}"
+        );
+    }
+}
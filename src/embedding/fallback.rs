@@ -0,0 +1,268 @@
+//! Fallback provider chains.
+//!
+//! A provider configured with `fallback = ["secondary", …]` is registered as a
+//! [`FallbackProvider`] that tries itself first and then each fallback in
+//! order, returning the first successful result.  Config validation guarantees
+//! that every member declares the same dimensionality.
+//!
+//! Each member is the fully wrapped provider (retries, circuit breaker, cache),
+//! so a member whose breaker is open is skipped immediately.
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::error::EmbeddingError;
+
+use super::{DynEmbeddingProvider, Embedding, EmbeddingProvider, InputKind};
+
+/// The boxed future type returned by `#[async_trait]` methods.
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub struct FallbackProvider {
+    chain: Vec<(String, DynEmbeddingProvider)>,
+    timeout: Option<Duration>,
+}
+
+impl FallbackProvider {
+    /// Build a chain from `(name, provider)` pairs in priority order.
+    ///
+    /// `timeout` bounds each member's attempt; a member that exceeds it is
+    /// treated as failed and the next one is tried.
+    pub fn new(chain: Vec<(String, DynEmbeddingProvider)>, timeout: Option<Duration>) -> Self {
+        Self { chain, timeout }
+    }
+
+    /// Run `op` against each member in turn, returning the first success
+    /// together with the name of the member that produced it.
+    async fn try_chain<'a, T, F>(&'a self, op: F) -> Result<(T, String), EmbeddingError>
+    where
+        F: Fn(&'a DynEmbeddingProvider) -> BoxFuture<'a, Result<T, EmbeddingError>> + Send + Sync,
+        T: Send,
+    {
+        let mut last_error = None;
+        for (name, provider) in &self.chain {
+            let attempt = op(provider);
+            let result = match self.timeout {
+                Some(limit) => tokio::time::timeout(limit, attempt)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(EmbeddingError::Timeout(format!(
+                            "provider '{name}' did not respond within {} ms",
+                            limit.as_millis()
+                        )))
+                    }),
+                None => attempt.await,
+            };
+            match result {
+                Ok(value) => return Ok((value, name.clone())),
+                Err(e) => {
+                    warn!(provider = %name, error = %e, "Provider failed, trying next in fallback chain");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            EmbeddingError::ConfigError("Fallback chain has no providers".to_string())
+        }))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for FallbackProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.embed_with_source(text, kind)
+            .await
+            .map(|(embedding, _)| embedding)
+    }
+
+    async fn embed_with_source(
+        &self,
+        text: &str,
+        kind: InputKind,
+    ) -> Result<(Embedding, Option<String>), EmbeddingError> {
+        let (embedding, name) = self.try_chain(|p| p.embed_as(text, kind)).await?;
+        Ok((embedding, Some(name)))
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.embed_batch_with_source(texts, kind)
+            .await
+            .map(|(embeddings, _)| embeddings)
+    }
+
+    async fn embed_batch_with_source(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<(Vec<Embedding>, Option<String>), EmbeddingError> {
+        let (embeddings, name) = self.try_chain(|p| p.embed_batch_as(texts, kind)).await?;
+        Ok((embeddings, Some(name)))
+    }
+
+    /// The smallest batch size in the chain, so a batch sized for the primary
+    /// can always be retried on any fallback.
+    fn max_batch_size(&self) -> usize {
+        self.chain
+            .iter()
+            .map(|(_, p)| p.max_batch_size())
+            .min()
+            .unwrap_or(super::DEFAULT_MAX_BATCH_SIZE)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    struct StaticProvider {
+        result: Result<Embedding, u16>,
+        delay: Duration,
+        calls: AtomicUsize,
+    }
+
+    impl StaticProvider {
+        fn ok(value: f32) -> Arc<Self> {
+            Self::build(Ok(vec![value]), Duration::ZERO)
+        }
+
+        fn failing(status: u16) -> Arc<Self> {
+            Self::build(Err(status), Duration::ZERO)
+        }
+
+        fn build(result: Result<Embedding, u16>, delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                result,
+                delay,
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for StaticProvider {
+        async fn embed_as(
+            &self,
+            _text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.result
+                .clone()
+                .map_err(|status| EmbeddingError::ProviderError {
+                    status,
+                    message: "down".to_string(),
+                })
+        }
+    }
+
+    #[tokio::test]
+    async fn primary_success_skips_fallbacks() {
+        let primary = StaticProvider::ok(1.0);
+        let secondary = StaticProvider::ok(2.0);
+        let chain = FallbackProvider::new(
+            vec![
+                (
+                    "primary".to_string(),
+                    primary.clone() as DynEmbeddingProvider,
+                ),
+                (
+                    "secondary".to_string(),
+                    secondary.clone() as DynEmbeddingProvider,
+                ),
+            ],
+            None,
+        );
+
+        let (embedding, source) = chain
+            .embed_with_source("hi", InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embedding, vec![1.0]);
+        assert_eq!(source.as_deref(), Some("primary"));
+        assert_eq!(secondary.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn falls_back_on_error_and_reports_source() {
+        let chain = FallbackProvider::new(
+            vec![
+                (
+                    "primary".to_string(),
+                    StaticProvider::failing(503) as DynEmbeddingProvider,
+                ),
+                (
+                    "secondary".to_string(),
+                    StaticProvider::ok(2.0) as DynEmbeddingProvider,
+                ),
+            ],
+            None,
+        );
+
+        let (embedding, source) = chain
+            .embed_with_source("hi", InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embedding, vec![2.0]);
+        assert_eq!(source.as_deref(), Some("secondary"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_on_timeout() {
+        let slow = StaticProvider::build(Ok(vec![1.0]), Duration::from_millis(200));
+        let chain = FallbackProvider::new(
+            vec![
+                ("primary".to_string(), slow as DynEmbeddingProvider),
+                (
+                    "secondary".to_string(),
+                    StaticProvider::ok(2.0) as DynEmbeddingProvider,
+                ),
+            ],
+            Some(Duration::from_millis(20)),
+        );
+
+        let (_, source) = chain
+            .embed_with_source("hi", InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(source.as_deref(), Some("secondary"));
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_when_every_member_fails() {
+        let chain = FallbackProvider::new(
+            vec![
+                (
+                    "primary".to_string(),
+                    StaticProvider::failing(503) as DynEmbeddingProvider,
+                ),
+                (
+                    "secondary".to_string(),
+                    StaticProvider::failing(502) as DynEmbeddingProvider,
+                ),
+            ],
+            None,
+        );
+
+        let result = chain.embed("hi").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::ProviderError { status: 502, .. })
+        ));
+    }
+}
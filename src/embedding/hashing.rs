@@ -0,0 +1,213 @@
+//! Offline, deterministic feature-hashing embedding provider.
+//!
+//! Runs entirely in-process: no model server, no network.  Intended for tests,
+//! CI and air-gapped development, not for production-quality retrieval.
+//!
+//! Each input is lower-cased and split into alphanumeric words.  Two kinds of
+//! features are extracted:
+//! - whole words (weight 1.0)
+//! - character trigrams of each word padded with `<` and `>` (weight 0.5),
+//!   so that inflections and typos still overlap
+//!
+//! Every feature is hashed with 64-bit FNV-1a into one of `dimensions`
+//! buckets; one hash bit chooses the sign to reduce collision bias.  The result
+//! is L2-normalised, so cosine similarity reflects shared vocabulary.
+//!
+//! FNV-1a is used instead of `std`'s `DefaultHasher` because its output is
+//! specified and therefore stable across Rust releases; vectors stored today
+//! stay comparable with vectors produced by future builds.
+
+use async_trait::async_trait;
+
+use crate::{config::ProviderConfig, error::EmbeddingError};
+
+use super::{Embedding, EmbeddingProvider, InputKind};
+
+/// Output size used when the provider config does not declare `dimensions`.
+pub const DEFAULT_DIMENSIONS: usize = 384;
+
+/// Batches are computed locally, so the limit only bounds per-call memory.
+const MAX_BATCH_SIZE: usize = 1024;
+
+const WORD_WEIGHT: f32 = 1.0;
+const TRIGRAM_WEIGHT: f32 = 0.5;
+
+pub struct HashingProvider {
+    dimensions: usize,
+}
+
+impl HashingProvider {
+    pub fn new(cfg: &ProviderConfig) -> Result<Self, EmbeddingError> {
+        let dimensions = cfg.dimensions.unwrap_or(DEFAULT_DIMENSIONS);
+        if dimensions == 0 {
+            return Err(EmbeddingError::ConfigError(
+                "Hashing provider requires 'dimensions' greater than zero".to_string(),
+            ));
+        }
+        Ok(Self { dimensions })
+    }
+
+    fn embed_text(&self, text: &str) -> Embedding {
+        let mut vector = vec![0.0f32; self.dimensions];
+        let lowered = text.to_lowercase();
+
+        let mut words: Vec<&str> = lowered
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        // Punctuation-only input still gets a (stable) non-zero vector.
+        if words.is_empty() && !lowered.trim().is_empty() {
+            words.push(lowered.trim());
+        }
+
+        for word in words {
+            self.add_feature(&mut vector, b'w', word.as_bytes(), WORD_WEIGHT);
+
+            let padded: Vec<char> = std::iter::once('<')
+                .chain(word.chars())
+                .chain(std::iter::once('>'))
+                .collect();
+            for gram in padded.windows(3) {
+                let gram: String = gram.iter().collect();
+                self.add_feature(&mut vector, b'c', gram.as_bytes(), TRIGRAM_WEIGHT);
+            }
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+
+    /// Hash `feature` (namespaced by `kind` so a word and a trigram with the
+    /// same bytes land in different buckets) and add `weight` to its bucket.
+    fn add_feature(&self, vector: &mut [f32], kind: u8, feature: &[u8], weight: f32) {
+        let hash = fnv1a(kind, feature);
+        let index = (hash % self.dimensions as u64) as usize;
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[index] += sign * weight;
+    }
+}
+
+/// 64-bit FNV-1a over a one-byte namespace followed by `bytes`.
+fn fnv1a(kind: u8, bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    std::iter::once(&kind)
+        .chain(bytes)
+        .fold(OFFSET_BASIS, |hash, &b| {
+            (hash ^ u64::from(b)).wrapping_mul(PRIME)
+        })
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingProvider {
+    // The bag-of-features model is symmetric, so the input-kind hint is ignored.
+    async fn embed_as(&self, text: &str, _kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        Ok(self.embed_text(text))
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        _kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(texts.iter().map(|t| self.embed_text(t)).collect())
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_BATCH_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_provider(dimensions: Option<usize>) -> HashingProvider {
+        HashingProvider::new(&ProviderConfig {
+            provider_type: "hashing".to_string(),
+            dimensions,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[tokio::test]
+    async fn identical_text_gives_identical_vectors() {
+        let provider = make_provider(None);
+        let a = provider
+            .embed("The deployment target is AWS")
+            .await
+            .unwrap();
+        let b = provider
+            .embed("The deployment target is AWS")
+            .await
+            .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), DEFAULT_DIMENSIONS);
+    }
+
+    #[tokio::test]
+    async fn vectors_are_unit_length() {
+        let provider = make_provider(Some(64));
+        let v = provider.embed("hello world").await.unwrap();
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+    }
+
+    #[tokio::test]
+    async fn overlapping_text_is_more_similar_than_unrelated_text() {
+        let provider = make_provider(None);
+        let query = provider.embed("deploying to AWS").await.unwrap();
+        let related = provider
+            .embed("The deployment target is AWS us-east-1")
+            .await
+            .unwrap();
+        let unrelated = provider
+            .embed("The user prefers concise explanations")
+            .await
+            .unwrap();
+        assert!(cosine(&query, &related) > cosine(&query, &unrelated));
+    }
+
+    #[tokio::test]
+    async fn output_is_stable_across_builds() {
+        // Pin the hash and a small vector so an accidental change to hashing
+        // or tokenisation (which would silently invalidate stored vectors)
+        // fails loudly.
+        assert_eq!(fnv1a(b'w', b"hello"), 0x65d7_e212_c1e6_ce06);
+
+        let provider = make_provider(Some(8));
+        let v = provider.embed("ABC").await.unwrap();
+        let expected = [
+            0.0,
+            -0.377_964_5,
+            0.755_928_9,
+            0.0,
+            0.0,
+            -0.377_964_5,
+            -0.377_964_5,
+            0.0,
+        ];
+        for (got, want) in v.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-6, "{v:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_zero_dimensions() {
+        let result = HashingProvider::new(&ProviderConfig {
+            provider_type: "hashing".to_string(),
+            dimensions: Some(0),
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(EmbeddingError::ConfigError(_))));
+    }
+}
@@ -0,0 +1,176 @@
+//! Prometheus instrumentation for embedding providers.
+//!
+//! [`InstrumentedProvider`] records, per provider:
+//! - `embedding_request_duration_seconds` – latency of each call
+//! - `embedding_errors_total` – failures, labelled with [`EmbeddingError::kind`]
+//! - `embedding_dimensions` – size of the most recently returned vectors
+//!
+//! and the tokens and cost of each call for [`crate::usage`].
+//!
+//! It wraps the [`ResilientProvider`](super::resilience::ResilientProvider),
+//! so latency includes retries and backoff, and local rejections (open
+//! circuit, exhausted budget) are counted.  It sits inside the cache, so cache
+//! hits are not recorded.
+
+use std::time::Instant;
+
+use async_trait::async_trait;
+
+use crate::{
+    chunking::approx_token_count,
+    error::EmbeddingError,
+    metrics::metrics,
+    usage::{self, capture_reported, UsageTotals},
+};
+
+use super::{DynEmbeddingProvider, Embedding, EmbeddingProvider, InputKind};
+
+pub struct InstrumentedProvider {
+    inner: DynEmbeddingProvider,
+    provider: String,
+    cost_per_million_tokens: Option<f64>,
+}
+
+impl InstrumentedProvider {
+    pub fn new(
+        inner: DynEmbeddingProvider,
+        provider: &str,
+        cost_per_million_tokens: Option<f64>,
+    ) -> Self {
+        Self {
+            inner,
+            provider: provider.to_string(),
+            cost_per_million_tokens,
+        }
+    }
+
+    /// Record the tokens of one call: those the provider reported, which are
+    /// billed even if the call then failed, or otherwise an estimate of the
+    /// input of a successful call.
+    fn record_usage<'a>(
+        &self,
+        texts: impl Iterator<Item = &'a str>,
+        succeeded: bool,
+        reported: Option<u64>,
+    ) {
+        let usage = match reported {
+            Some(tokens) => UsageTotals::call(tokens, 0, self.cost_per_million_tokens),
+            None if succeeded => {
+                let tokens = texts.map(approx_token_count).sum::<usize>() as u64;
+                UsageTotals::call(tokens, tokens, self.cost_per_million_tokens)
+            }
+            None => return,
+        };
+        usage::record(&self.provider, usage);
+    }
+
+    fn record<T>(
+        &self,
+        operation: &str,
+        started: Instant,
+        result: &Result<T, EmbeddingError>,
+        dimensions: impl FnOnce(&T) -> Option<usize>,
+    ) {
+        let m = metrics();
+        m.embedding_duration
+            .observe(&[&self.provider, operation], started.elapsed());
+        match result {
+            Ok(value) => {
+                if let Some(dimensions) = dimensions(value) {
+                    m.embedding_dimensions
+                        .set(&[&self.provider], dimensions as f64);
+                }
+            }
+            Err(e) => m.embedding_errors.inc(&[&self.provider, e.kind()]),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for InstrumentedProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        let started = Instant::now();
+        let (result, reported) = capture_reported(self.inner.embed_as(text, kind)).await;
+        self.record("embed", started, &result, |e| Some(e.len()));
+        self.record_usage(std::iter::once(text), result.is_ok(), reported);
+        result
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let started = Instant::now();
+        let (result, reported) = capture_reported(self.inner.embed_batch_as(texts, kind)).await;
+        self.record("embed_batch", started, &result, |e| e.first().map(Vec::len));
+        self.record_usage(texts.iter().map(String::as_str), result.is_ok(), reported);
+        result
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    struct FixedProvider(Result<usize, fn() -> EmbeddingError>);
+
+    #[async_trait]
+    impl EmbeddingProvider for FixedProvider {
+        async fn embed_as(
+            &self,
+            _text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            match self.0 {
+                Ok(dimensions) => Ok(vec![0.0; dimensions]),
+                Err(error) => Err(error()),
+            }
+        }
+    }
+
+    fn rendered() -> String {
+        let mut out = String::new();
+        metrics().render(&mut out);
+        out
+    }
+
+    #[tokio::test]
+    async fn records_latency_dimensions_and_error_kinds() {
+        let name = format!("instrumented-{}", uuid::Uuid::new_v4());
+        let ok = InstrumentedProvider::new(Arc::new(FixedProvider(Ok(3))), &name, None);
+        ok.embed("hello").await.unwrap();
+
+        let failing = InstrumentedProvider::new(
+            Arc::new(FixedProvider(Err(|| {
+                EmbeddingError::Timeout("slow".to_string())
+            }))),
+            &name,
+            None,
+        );
+        failing.embed("hello").await.unwrap_err();
+        failing.embed("hello").await.unwrap_err();
+
+        let out = rendered();
+        assert!(
+            out.contains(&format!("embedding_request_duration_seconds_count{{provider=\"{name}\",operation=\"embed\"}} 3\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!("embedding_dimensions{{provider=\"{name}\"}} 3\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!(
+                "embedding_errors_total{{provider=\"{name}\",error=\"timeout\"}} 2\n"
+            )),
+            "{out}"
+        );
+    }
+}
@@ -1,27 +1,328 @@
-pub mod claude;
+pub mod cache;
+pub mod cohere;
+pub mod command;
+pub mod fallback;
+pub mod hashing;
+pub mod instrumented;
 pub mod ollama;
 pub mod openai;
+pub mod rate_limit;
+pub mod resilience;
+pub mod single_flight;
+pub mod tei;
+pub mod voyage;
 
-use std::sync::Arc;
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
 
 use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::{error, info, warn};
 
 use crate::{
     config::{EmbeddingConfig, ProviderConfig},
     error::EmbeddingError,
 };
 
+use self::{
+    cache::{CacheStats, CachedProvider, EmbeddingCache},
+    fallback::FallbackProvider,
+    instrumented::InstrumentedProvider,
+    rate_limit::RateLimitedProvider,
+    resilience::{BreakerStatus, CircuitBreaker, ResilientProvider},
+    single_flight::SingleFlightProvider,
+};
+
 /// The result of an embedding operation: a vector of floats representing semantic content.
 pub type Embedding = Vec<f32>;
 
+/// What an input text is used for.
+///
+/// Asymmetric models embed search queries and stored documents differently:
+/// Voyage takes an `input_type` field, nomic-embed-text expects
+/// `search_query: ` / `search_document: ` prefixes.  Symmetric models ignore
+/// the hint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputKind {
+    /// No hint; the text is embedded as-is.
+    #[default]
+    Unspecified,
+    /// A search query compared against stored documents.
+    Query,
+    /// Text that is stored and later searched.
+    Document,
+}
+
+impl InputKind {
+    /// The lower-case name used by upstream APIs, or `None` when unspecified.
+    pub fn as_str(self) -> Option<&'static str> {
+        match self {
+            InputKind::Unspecified => None,
+            InputKind::Query => Some("query"),
+            InputKind::Document => Some("document"),
+        }
+    }
+}
+
 /// Core abstraction for any embedding provider.
 ///
 /// Implementors are expected to be `Send + Sync` so they can be shared across
 /// async tasks and Axum handlers.
+///
+/// Implementors provide the [`InputKind`]-aware methods
+/// ([`embed_as`](Self::embed_as) and optionally
+/// [`embed_batch_as`](Self::embed_batch_as)); [`embed`](Self::embed) is a
+/// shorthand for [`InputKind::Unspecified`] and should not be overridden.
 #[async_trait]
 pub trait EmbeddingProvider: Send + Sync {
     /// Generate an embedding for the given input text.
-    async fn embed(&self, text: &str) -> Result<Embedding, EmbeddingError>;
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError>;
+
+    /// Generate an embedding without an input-kind hint.
+    async fn embed(&self, text: &str) -> Result<Embedding, EmbeddingError> {
+        self.embed_as(text, InputKind::Unspecified).await
+    }
+
+    /// Generate an embedding and report which registered provider produced it.
+    ///
+    /// Only composite providers such as fallback chains return `Some(name)`;
+    /// `None` means the vector came from this provider itself.
+    async fn embed_with_source(
+        &self,
+        text: &str,
+        kind: InputKind,
+    ) -> Result<(Embedding, Option<String>), EmbeddingError> {
+        Ok((self.embed_as(text, kind).await?, None))
+    }
+
+    /// Generate embeddings for several input texts in a single call.
+    ///
+    /// Results are returned in the same order as `texts`.  The default
+    /// implementation falls back to one [`embed_as`](Self::embed_as) call per
+    /// text; providers whose upstream API accepts list input override it.
+    ///
+    /// Callers must not pass more than [`max_batch_size`](Self::max_batch_size)
+    /// texts at once – use [`embed_in_batches`] to split larger inputs.
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed_as(text, kind).await?);
+        }
+        Ok(embeddings)
+    }
+
+    /// Batch counterpart of [`embed_with_source`](Self::embed_with_source).
+    async fn embed_batch_with_source(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<(Vec<Embedding>, Option<String>), EmbeddingError> {
+        Ok((self.embed_batch_as(texts, kind).await?, None))
+    }
+
+    /// Maximum number of texts accepted by a single [`embed_batch_as`](Self::embed_batch_as) call.
+    fn max_batch_size(&self) -> usize {
+        DEFAULT_MAX_BATCH_SIZE
+    }
+}
+
+/// Batch size used by providers that do not support native batching.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 16;
+
+/// Embed an arbitrary number of texts, splitting them into chunks no larger
+/// than the provider's [`EmbeddingProvider::max_batch_size`].
+///
+/// Results are returned in input order, together with the registered
+/// provider(s) that served them (see
+/// [`embed_with_source`](EmbeddingProvider::embed_with_source)).  When
+/// different batches were served by different members of a fallback chain,
+/// their names are joined with `", "` in the order they were first used.
+pub async fn embed_in_batches(
+    provider: &dyn EmbeddingProvider,
+    texts: &[String],
+    kind: InputKind,
+) -> Result<(Vec<Embedding>, Option<String>), EmbeddingError> {
+    let batch_size = provider.max_batch_size().max(1);
+    let mut embeddings = Vec::with_capacity(texts.len());
+    let mut sources: Vec<String> = Vec::new();
+    for chunk in texts.chunks(batch_size) {
+        let (batch, source) = provider.embed_batch_with_source(chunk, kind).await?;
+        if batch.len() != chunk.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, provider returned {}",
+                chunk.len(),
+                batch.len()
+            )));
+        }
+        embeddings.extend(batch);
+        if let Some(source) = source {
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+    }
+    let source = (!sources.is_empty()).then(|| sources.join(", "));
+    Ok((embeddings, source))
+}
+
+/// Convert a non-success upstream response into an [`EmbeddingError`].
+///
+/// HTTP 429 becomes [`EmbeddingError::RateLimited`] carrying the upstream
+/// `Retry-After` delay when it is given in seconds; every other status becomes
+/// [`EmbeddingError::ProviderError`].
+pub(crate) async fn error_from_response(response: reqwest::Response) -> EmbeddingError {
+    let status = response.status().as_u16();
+    let retry_after_secs = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    let message = response.text().await.unwrap_or_default();
+
+    if status == 429 {
+        EmbeddingError::RateLimited {
+            message,
+            retry_after_secs,
+        }
+    } else {
+        EmbeddingError::ProviderError { status, message }
+    }
+}
+
+/// Per-provider text prefixes for asymmetric models that are steered by the
+/// input text itself, such as nomic-embed-text.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InputPrefixes {
+    query: Option<String>,
+    document: Option<String>,
+}
+
+impl InputPrefixes {
+    pub(crate) fn from_config(cfg: &ProviderConfig) -> Self {
+        Self {
+            query: cfg.query_prefix.clone(),
+            document: cfg.document_prefix.clone(),
+        }
+    }
+
+    /// Prepend the configured prefix for `kind` to every input.
+    pub(crate) fn apply<'a>(&self, input: &[&'a str], kind: InputKind) -> Vec<Cow<'a, str>> {
+        let prefix = match kind {
+            InputKind::Unspecified => None,
+            InputKind::Query => self.query.as_deref(),
+            InputKind::Document => self.document.as_deref(),
+        };
+        input
+            .iter()
+            .map(|text| match prefix {
+                Some(prefix) => Cow::Owned(format!("{prefix}{text}")),
+                None => Cow::Borrowed(*text),
+            })
+            .collect()
+    }
+}
+
+/// Client-side shaping of provider output: Matryoshka-style truncation to
+/// `output_dimensions` followed by optional L2 normalisation.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct OutputShape {
+    dimensions: Option<usize>,
+    normalize: bool,
+}
+
+impl OutputShape {
+    pub(crate) fn from_config(cfg: &ProviderConfig) -> Self {
+        Self {
+            dimensions: cfg.output_dimensions,
+            normalize: cfg.normalize.unwrap_or(false),
+        }
+    }
+
+    /// The configured output size, for providers that can request it upstream.
+    pub(crate) fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    /// Truncate and/or normalise every embedding.  A vector shorter than the
+    /// configured size is an error, since it cannot be padded meaningfully.
+    pub(crate) fn apply(
+        &self,
+        embeddings: Vec<Embedding>,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        embeddings
+            .into_iter()
+            .map(|mut embedding| {
+                if let Some(dimensions) = self.dimensions {
+                    if embedding.len() < dimensions {
+                        return Err(EmbeddingError::InvalidResponse(format!(
+                            "Model returned {} dimensions, fewer than output_dimensions = {dimensions}",
+                            embedding.len()
+                        )));
+                    }
+                    embedding.truncate(dimensions);
+                }
+                if self.normalize {
+                    let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+                    if norm > 0.0 {
+                        embedding.iter_mut().for_each(|v| *v /= norm);
+                    }
+                }
+                Ok(embedding)
+            })
+            .collect()
+    }
+}
+
+/// Resolve the effective batch size from an optional config override,
+/// clamping it to the provider's hard upstream limit.
+pub(crate) fn batch_size_from_config(configured: Option<usize>, upstream_limit: usize) -> usize {
+    configured
+        .unwrap_or(upstream_limit)
+        .clamp(1, upstream_limit)
+}
+
+/// Build the upstream HTTP client from the provider's HTTP settings.
+pub(crate) fn http_client_from_config(
+    cfg: &ProviderConfig,
+) -> Result<reqwest::Client, EmbeddingError> {
+    crate::http_client::build_client(&cfg.http).map_err(EmbeddingError::ConfigError)
+}
+
+/// Validate the configured `output_dtype`, allowing only the numeric types
+/// that yield one component per dimension.  The bit-packed `binary` types
+/// offered by Voyage and Cohere are rejected.
+pub(crate) fn output_dtype_from_config(
+    cfg: &ProviderConfig,
+) -> Result<Option<String>, EmbeddingError> {
+    const NUMERIC_DTYPES: &[&str] = &["float", "int8", "uint8"];
+    match cfg.output_dtype.as_deref() {
+        Some(dtype) if !NUMERIC_DTYPES.contains(&dtype) => Err(EmbeddingError::ConfigError(
+            format!("Unsupported output_dtype '{dtype}': expected one of {NUMERIC_DTYPES:?}"),
+        )),
+        dtype => Ok(dtype.map(str::to_string)),
+    }
+}
+
+/// Read and deserialize the body of a successful upstream response.
+///
+/// `upstream` names the service in parse errors.  A body that stalls past the
+/// request timeout becomes [`EmbeddingError::Timeout`] rather than a parse
+/// error, so it is reported as 504 like a slow response head.
+pub(crate) async fn parse_response<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+    upstream: &str,
+) -> Result<T, EmbeddingError> {
+    response.json().await.map_err(|e| {
+        if e.is_timeout() {
+            EmbeddingError::Timeout(e.to_string())
+        } else {
+            EmbeddingError::InvalidResponse(format!("Failed to parse {upstream} response: {e}"))
+        }
+    })
 }
 
 /// A type-erased, heap-allocated embedding provider.
@@ -29,33 +330,143 @@ pub type DynEmbeddingProvider = Arc<dyn EmbeddingProvider>;
 
 /// Build a concrete [`EmbeddingProvider`] from a [`ProviderConfig`].
 pub fn build_provider(
-    _name: &str,
+    name: &str,
     cfg: &ProviderConfig,
 ) -> Result<DynEmbeddingProvider, EmbeddingError> {
     match cfg.provider_type.as_str() {
-        "ollama" => Ok(Arc::new(ollama::OllamaProvider::new(cfg))),
+        "ollama" => Ok(Arc::new(ollama::OllamaProvider::new(cfg)?)),
         "openai" => Ok(Arc::new(openai::OpenAIProvider::new(cfg)?)),
-        "claude" => Ok(Arc::new(claude::ClaudeProvider::new(cfg))),
+        "voyage" => Ok(Arc::new(voyage::VoyageProvider::new(cfg)?)),
+        "cohere" => Ok(Arc::new(cohere::CohereProvider::new(cfg)?)),
+        "tei" => Ok(Arc::new(tei::TeiProvider::new(cfg)?)),
+        "command" => Ok(Arc::new(command::CommandProvider::new(name, cfg)?)),
+        "hashing" => Ok(Arc::new(hashing::HashingProvider::new(cfg)?)),
         unknown => Err(EmbeddingError::ConfigError(format!(
             "Unknown provider type: '{unknown}'"
         ))),
     }
 }
 
+/// The model identity used in cache keys: the provider type and model name
+/// plus every setting that changes the vectors it produces.  Settings left at
+/// their defaults add nothing, so keys stay stable for configs that do not use
+/// them.
+fn cache_model_id(cfg: &ProviderConfig) -> String {
+    let mut id = format!("{}\0{}", cfg.provider_type, cfg.model);
+    if let Some(dimensions) = cfg.dimensions {
+        id.push_str(&format!("\0size={dimensions}"));
+    }
+    if let Some(command) = &cfg.command {
+        id.push_str(&format!("\0command={command}"));
+        for arg in &cfg.args {
+            id.push_str(&format!("\0arg={arg}"));
+        }
+    }
+    if let Some(dimensions) = cfg.output_dimensions {
+        id.push_str(&format!("\0dims={dimensions}"));
+    }
+    if let Some(dtype) = &cfg.output_dtype {
+        id.push_str(&format!("\0dtype={dtype}"));
+    }
+    if let Some(normalize) = cfg.normalize {
+        id.push_str(&format!("\0normalize={normalize}"));
+    }
+    if let Some(prefix) = &cfg.query_prefix {
+        id.push_str(&format!("\0query={prefix}"));
+    }
+    if let Some(prefix) = &cfg.document_prefix {
+        id.push_str(&format!("\0document={prefix}"));
+    }
+    id
+}
+
 /// Registry of all configured embedding providers.
 pub struct ProviderRegistry {
     providers: std::collections::HashMap<String, DynEmbeddingProvider>,
     default: String,
+    cache: Option<Arc<EmbeddingCache>>,
+    breakers: std::collections::HashMap<String, Arc<CircuitBreaker>>,
+    /// Providers whose output cannot be stored in the vector store, with the
+    /// reason.  Filled by [`check_vector_dimensions`](Self::check_vector_dimensions).
+    vector_incompatible: BTreeMap<String, String>,
 }
 
+/// Text embedded at startup to discover each provider's output size.
+const PROBE_TEXT: &str = "dimension probe";
+
 impl ProviderRegistry {
     /// Build a registry from the embedding section of the application config.
-    pub fn from_config(cfg: &EmbeddingConfig) -> Result<Self, EmbeddingError> {
+    ///
+    /// Providers with `requests_per_minute`, `tokens_per_minute` or
+    /// `max_concurrency` are first wrapped in a [`RateLimitedProvider`], so
+    /// every upstream attempt is charged against the budget.
+    ///
+    /// Every provider is then wrapped in a [`ResilientProvider`] using its own
+    /// `resilience` settings or the `[embedding.resilience]` defaults, and in
+    /// an [`InstrumentedProvider`] recording Prometheus metrics, and in a
+    /// [`SingleFlightProvider`] coalescing concurrent identical calls.  When
+    /// `cache` is supplied the result is further wrapped in a
+    /// [`CachedProvider`] sharing that cache, so cache hits never touch the
+    /// circuit breaker.
+    ///
+    /// Providers with a `fallback` list are then registered as a
+    /// [`FallbackProvider`] over the wrapped members of their chain.
+    pub fn from_config(
+        cfg: &EmbeddingConfig,
+        cache: Option<Arc<EmbeddingCache>>,
+    ) -> Result<Self, EmbeddingError> {
         let mut providers = std::collections::HashMap::new();
+        let mut breakers = std::collections::HashMap::new();
         for (name, provider_cfg) in &cfg.providers {
-            let provider = build_provider(name, provider_cfg)?;
+            let resilience_cfg = provider_cfg.resilience.as_ref().unwrap_or(&cfg.resilience);
+            let mut raw = build_provider(name, provider_cfg)?;
+            if provider_cfg.has_rate_limits() {
+                raw = Arc::new(RateLimitedProvider::new(raw, name, provider_cfg));
+            }
+            let resilient = ResilientProvider::new(raw, name, resilience_cfg);
+            breakers.insert(name.clone(), resilient.breaker());
+
+            let instrumented: DynEmbeddingProvider = Arc::new(InstrumentedProvider::new(
+                Arc::new(resilient),
+                name,
+                provider_cfg.cost_per_million_tokens,
+            ));
+            let mut provider: DynEmbeddingProvider =
+                Arc::new(SingleFlightProvider::new(instrumented, name));
+            if let Some(cache) = &cache {
+                provider = Arc::new(CachedProvider::new(
+                    provider,
+                    cache.clone(),
+                    name,
+                    &cache_model_id(provider_cfg),
+                ));
+            }
             providers.insert(name.clone(), provider);
         }
+
+        let mut chains = Vec::new();
+        for (name, provider_cfg) in &cfg.providers {
+            if provider_cfg.fallback.is_empty() {
+                continue;
+            }
+            let mut chain = Vec::with_capacity(provider_cfg.fallback.len() + 1);
+            for member in std::iter::once(name).chain(&provider_cfg.fallback) {
+                let provider = providers.get(member).ok_or_else(|| {
+                    EmbeddingError::ConfigError(format!(
+                        "Provider '{name}' falls back to unknown provider '{member}'"
+                    ))
+                })?;
+                chain.push((member.clone(), provider.clone()));
+            }
+            let timeout = provider_cfg
+                .fallback_timeout_ms
+                .map(std::time::Duration::from_millis);
+            chains.push((name.clone(), FallbackProvider::new(chain, timeout)));
+        }
+        for (name, chain) in chains {
+            providers.insert(name, Arc::new(chain));
+        }
+
         if !providers.contains_key(&cfg.default_provider) {
             return Err(EmbeddingError::ConfigError(format!(
                 "Default provider '{}' is not registered",
@@ -66,6 +477,9 @@ impl ProviderRegistry {
         Ok(Self {
             providers,
             default: cfg.default_provider.clone(),
+            cache,
+            breakers,
+            vector_incompatible: BTreeMap::new(),
         })
     }
 
@@ -86,4 +500,260 @@ impl ProviderRegistry {
     pub fn provider_names(&self) -> Vec<&str> {
         self.providers.keys().map(String::as_str).collect()
     }
+
+    /// Embedding cache counters, or `None` when caching is disabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|c| c.stats())
+    }
+
+    /// Embed a probe text with every provider and check its output size
+    /// against the vector store's `dimensions`.
+    ///
+    /// Providers that produce a different size are marked unusable for the
+    /// vector store (see [`vector_incompatibility`](Self::vector_incompatibility)).
+    /// Providers that cannot be reached are only logged, since they may come
+    /// up later.  Returns an error when the default provider is incompatible,
+    /// because every store and search without `?provider=` would fail.
+    pub async fn check_vector_dimensions(&mut self, dimensions: usize) -> Result<(), String> {
+        let mut names: Vec<&String> = self.providers.keys().collect();
+        names.sort();
+
+        let mut incompatible = BTreeMap::new();
+        for name in names {
+            match self.providers[name].embed(PROBE_TEXT).await {
+                Ok(embedding) if embedding.len() == dimensions => {
+                    info!(provider = %name, dimensions, "Provider output matches the vector store");
+                }
+                Ok(embedding) => {
+                    let reason = format!(
+                        "provider '{name}' produces {}-dimensional vectors but the Qdrant \
+                         collection expects {dimensions}",
+                        embedding.len()
+                    );
+                    error!(provider = %name, "{reason}; it cannot be used with /api/memory or /api/search");
+                    incompatible.insert(name.clone(), reason);
+                }
+                Err(e) => {
+                    warn!(
+                        provider = %name,
+                        error = %e,
+                        "Could not probe provider output size; a dimension mismatch will surface on first use"
+                    );
+                }
+            }
+        }
+
+        let default_reason = incompatible.get(&self.default).cloned();
+        self.vector_incompatible = incompatible;
+        match default_reason {
+            Some(reason) => Err(format!(
+                "Default embedding {reason}; set [qdrant] dimensions or change default_provider"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Why `name` cannot be used with the vector store, if it cannot.
+    pub fn vector_incompatibility(&self, name: &str) -> Option<&str> {
+        self.vector_incompatible.get(name).map(String::as_str)
+    }
+
+    /// All providers that cannot be used with the vector store, with reasons.
+    pub fn vector_incompatibilities(&self) -> &BTreeMap<String, String> {
+        &self.vector_incompatible
+    }
+
+    /// Circuit-breaker state of every provider, keyed by provider name.
+    pub fn breaker_states(&self) -> BTreeMap<String, BreakerStatus> {
+        self.breakers
+            .iter()
+            .map(|(name, breaker)| (name.clone(), breaker.status()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Provider without native batching that records how many calls it served.
+    struct CountingProvider {
+        calls: AtomicUsize,
+        batch_size: usize,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn embed_as(
+            &self,
+            text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![text.len() as f32])
+        }
+
+        fn max_batch_size(&self) -> usize {
+            self.batch_size
+        }
+    }
+
+    #[tokio::test]
+    async fn default_embed_batch_falls_back_to_single_calls() {
+        let provider = CountingProvider {
+            calls: AtomicUsize::new(0),
+            batch_size: 8,
+        };
+        let texts = vec!["a".to_string(), "bb".to_string(), "ccc".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0], vec![3.0]]);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn embed_in_batches_preserves_order_across_chunks() {
+        let provider = CountingProvider {
+            calls: AtomicUsize::new(0),
+            batch_size: 2,
+        };
+        let texts: Vec<String> = (1..=5).map(|n| "x".repeat(n)).collect();
+        let (embeddings, source) = embed_in_batches(&provider, &texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert!(source.is_none());
+        let lengths: Vec<f32> = embeddings.into_iter().map(|e| e[0]).collect();
+        assert_eq!(lengths, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    fn hashing_registry(default: &str) -> ProviderRegistry {
+        let cfg: EmbeddingConfig = toml::from_str(&format!(
+            r#"
+            default_provider = "{default}"
+            [providers.small]
+            type = "hashing"
+            dimensions = 8
+            [providers.large]
+            type = "hashing"
+            dimensions = 16
+            "#
+        ))
+        .unwrap();
+        ProviderRegistry::from_config(&cfg, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn check_vector_dimensions_marks_mismatched_providers() {
+        let mut registry = hashing_registry("small");
+        registry.check_vector_dimensions(8).await.unwrap();
+
+        assert!(registry.vector_incompatibility("small").is_none());
+        let reason = registry.vector_incompatibility("large").unwrap();
+        assert!(reason.contains("16") && reason.contains('8'), "{reason}");
+    }
+
+    #[tokio::test]
+    async fn check_vector_dimensions_rejects_mismatched_default() {
+        let mut registry = hashing_registry("large");
+        let err = registry.check_vector_dimensions(8).await.unwrap_err();
+        assert!(err.contains("'large'"), "{err}");
+    }
+
+    #[test]
+    fn input_prefixes_apply_only_to_their_kind() {
+        let prefixes = InputPrefixes::from_config(&ProviderConfig {
+            query_prefix: Some("search_query: ".to_string()),
+            document_prefix: Some("search_document: ".to_string()),
+            ..Default::default()
+        });
+        let input = ["hello"];
+        assert_eq!(
+            prefixes.apply(&input, InputKind::Query),
+            ["search_query: hello"]
+        );
+        assert_eq!(
+            prefixes.apply(&input, InputKind::Document),
+            ["search_document: hello"]
+        );
+        assert_eq!(prefixes.apply(&input, InputKind::Unspecified), ["hello"]);
+    }
+
+    #[test]
+    fn output_shape_truncates_then_normalises() {
+        let shape = OutputShape::from_config(&ProviderConfig {
+            output_dimensions: Some(2),
+            normalize: Some(true),
+            ..Default::default()
+        });
+        let shaped = shape.apply(vec![vec![3.0, 4.0, 12.0]]).unwrap();
+        assert_eq!(shaped, vec![vec![0.6, 0.8]]);
+
+        let err = shape.apply(vec![vec![1.0]]).unwrap_err();
+        assert!(matches!(err, EmbeddingError::InvalidResponse(_)));
+    }
+
+    #[test]
+    fn cache_model_id_reflects_vector_shaping_settings() {
+        let plain = ProviderConfig {
+            provider_type: "hashing".to_string(),
+            model: "m".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(cache_model_id(&plain), "hashing\0m");
+
+        let shaped = ProviderConfig {
+            output_dimensions: Some(256),
+            ..plain.clone()
+        };
+        let prefixed = ProviderConfig {
+            query_prefix: Some("q: ".to_string()),
+            ..plain.clone()
+        };
+        assert_ne!(cache_model_id(&shaped), cache_model_id(&plain));
+        assert_ne!(cache_model_id(&prefixed), cache_model_id(&plain));
+
+        let small = ProviderConfig {
+            dimensions: Some(8),
+            ..plain.clone()
+        };
+        let large = ProviderConfig {
+            dimensions: Some(16),
+            ..plain.clone()
+        };
+        assert_ne!(cache_model_id(&small), cache_model_id(&large));
+
+        let command = ProviderConfig {
+            provider_type: "command".to_string(),
+            command: Some("embedder".to_string()),
+            args: vec!["--model".to_string(), "a".to_string()],
+            ..plain.clone()
+        };
+        let other_args = ProviderConfig {
+            args: vec!["--model".to_string(), "b".to_string()],
+            ..command.clone()
+        };
+        assert_ne!(cache_model_id(&command), cache_model_id(&other_args));
+        assert_ne!(cache_model_id(&command), cache_model_id(&plain));
+    }
+
+    #[test]
+    fn default_output_shape_leaves_vectors_unchanged() {
+        let shape = OutputShape::default();
+        assert_eq!(
+            shape.apply(vec![vec![3.0, 4.0]]).unwrap(),
+            vec![vec![3.0, 4.0]]
+        );
+    }
+
+    #[test]
+    fn batch_size_from_config_clamps_to_range() {
+        assert_eq!(batch_size_from_config(None, 100), 100);
+        assert_eq!(batch_size_from_config(Some(10), 100), 10);
+        assert_eq!(batch_size_from_config(Some(0), 100), 1);
+        assert_eq!(batch_size_from_config(Some(500), 100), 100);
+    }
 }
@@ -3,79 +3,128 @@
 //! API reference: <https://github.com/ollama/ollama/blob/main/docs/api.md#generate-embeddings>
 //!
 //! Request  → POST {base_url}/api/embed
-//! Body     → `{"model": "…", "input": "…"}`
-//! Response → `{"embeddings": [[…]]}`
+//! Body     → `{"model": "…", "input": ["…", …]}`
+//! Response → `{"embeddings": [[…], …]}`
+//!
+//! `input` accepts a list, so batches are sent in a single request.
+//!
+//! Ollama has no input-type field; asymmetric models such as nomic-embed-text
+//! are steered with `query_prefix` / `document_prefix` in the provider config.
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{config::ProviderConfig, error::EmbeddingError};
+use crate::{config::ProviderConfig, error::EmbeddingError, usage::report_tokens};
+
+use super::{
+    batch_size_from_config, error_from_response, http_client_from_config, parse_response,
+    Embedding, EmbeddingProvider, InputKind, InputPrefixes, OutputShape,
+};
 
-use super::{Embedding, EmbeddingProvider};
+/// Upper bound on inputs per request; Ollama has no hard limit, but very large
+/// batches hold the model lock for a long time.
+const MAX_BATCH_SIZE: usize = 512;
 
 pub struct OllamaProvider {
     client: reqwest::Client,
     base_url: String,
     model: String,
+    max_batch_size: usize,
+    prefixes: InputPrefixes,
+    shape: OutputShape,
 }
 
 impl OllamaProvider {
-    pub fn new(cfg: &ProviderConfig) -> Self {
-        Self {
-            client: reqwest::Client::new(),
+    pub fn new(cfg: &ProviderConfig) -> Result<Self, EmbeddingError> {
+        Ok(Self {
+            client: http_client_from_config(cfg)?,
             base_url: cfg.base_url.trim_end_matches('/').to_string(),
             model: cfg.model.clone(),
+            max_batch_size: batch_size_from_config(cfg.max_batch_size, MAX_BATCH_SIZE),
+            prefixes: InputPrefixes::from_config(cfg),
+            shape: OutputShape::from_config(cfg),
+        })
+    }
+
+    async fn request_embeddings(
+        &self,
+        input: &[&str],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let url = format!("{}/api/embed", self.base_url);
+        let prefixed = self.prefixes.apply(input, kind);
+        let input: Vec<&str> = prefixed.iter().map(AsRef::as_ref).collect();
+        let body = OllamaRequest {
+            model: &self.model,
+            input: &input,
+        };
+
+        let response = self.client.post(&url).json(&body).send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(error_from_response(response).await);
         }
+
+        let parsed: OllamaResponse = parse_response(response, "Ollama").await?;
+
+        if let Some(tokens) = parsed.prompt_eval_count {
+            report_tokens(tokens);
+        }
+        self.shape.apply(parsed.embeddings)
     }
 }
 
 #[derive(Serialize)]
 struct OllamaRequest<'a> {
     model: &'a str,
-    input: &'a str,
+    input: &'a [&'a str],
 }
 
 #[derive(Deserialize)]
 struct OllamaResponse {
     embeddings: Vec<Vec<f32>>,
+    /// Input tokens processed; reported by Ollama 0.3 and later.
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
 }
 
 #[async_trait]
 impl EmbeddingProvider for OllamaProvider {
-    async fn embed(&self, text: &str) -> Result<Embedding, EmbeddingError> {
-        let url = format!("{}/api/embed", self.base_url);
-        let body = OllamaRequest {
-            model: &self.model,
-            input: text,
-        };
-
-        let response = self.client.post(&url).json(&body).send().await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let message = response.text().await.unwrap_or_default();
-            return Err(EmbeddingError::ProviderError {
-                status: status.as_u16(),
-                message,
-            });
-        }
-
-        let parsed: OllamaResponse = response.json().await.map_err(|e| {
-            EmbeddingError::InvalidResponse(format!("Failed to parse Ollama response: {e}"))
-        })?;
-
-        parsed
-            .embeddings
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.request_embeddings(&[text], kind)
+            .await?
             .into_iter()
             .next()
             .ok_or_else(|| EmbeddingError::InvalidResponse("Empty embeddings array".to_string()))
     }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let input: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.request_embeddings(&input, kind).await?;
+        if embeddings.len() != texts.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, Ollama returned {}",
+                texts.len(),
+                embeddings.len()
+            )));
+        }
+        Ok(embeddings)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use wiremock::{
-        matchers::{method, path},
+        matchers::{body_json, method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -90,6 +139,7 @@ mod tests {
             api_key: None,
             auth_scheme: None,
             embeddings_path: None,
+            ..Default::default()
         }
     }
 
@@ -105,7 +155,7 @@ mod tests {
             .mount(&server)
             .await;
 
-        let provider = OllamaProvider::new(&make_config(&server.uri()));
+        let provider = OllamaProvider::new(&make_config(&server.uri())).unwrap();
         let embedding = provider.embed("hello world").await.unwrap();
         assert_eq!(embedding, vec![0.1, 0.2, 0.3]);
     }
@@ -122,7 +172,7 @@ mod tests {
             .mount(&server)
             .await;
 
-        let provider = OllamaProvider::new(&make_config(&server.uri()));
+        let provider = OllamaProvider::new(&make_config(&server.uri())).unwrap();
         let result = provider.embed("hello world").await;
         assert!(matches!(
             result,
@@ -142,11 +192,88 @@ mod tests {
             .mount(&server)
             .await;
 
-        let provider = OllamaProvider::new(&make_config(&server.uri()));
+        let provider = OllamaProvider::new(&make_config(&server.uri())).unwrap();
         let result = provider.embed("hello world").await;
         assert!(matches!(
             result,
             Err(EmbeddingError::InvalidResponse(_))
         ));
     }
+
+    #[tokio::test]
+    async fn embed_batch_sends_list_input_and_preserves_order() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/embed"))
+            .and(body_json(serde_json::json!({
+                "model": "nomic-embed-text",
+                "input": ["first", "second"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "embeddings": [[1.0_f32, 0.0_f32], [0.0_f32, 1.0_f32]]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = OllamaProvider::new(&make_config(&server.uri())).unwrap();
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+
+    #[tokio::test]
+    async fn embed_batch_returns_error_on_count_mismatch() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/embed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "embeddings": [[1.0_f32, 0.0_f32]]
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = OllamaProvider::new(&make_config(&server.uri())).unwrap();
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let result = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await;
+        assert!(matches!(result, Err(EmbeddingError::InvalidResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn configured_prefixes_are_applied_per_input_kind() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/embed"))
+            .and(body_json(serde_json::json!({
+                "model": "nomic-embed-text",
+                "input": ["search_document: first", "search_document: second"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "embeddings": [[1.0_f32], [2.0_f32]]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = OllamaProvider::new(&ProviderConfig {
+            query_prefix: Some("search_query: ".to_string()),
+            document_prefix: Some("search_document: ".to_string()),
+            ..make_config(&server.uri())
+        })
+        .unwrap();
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Document)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0]]);
+    }
 }
@@ -6,19 +6,35 @@
 //! including Azure OpenAI, LocalAI, vLLM, and similar proxies.
 //!
 //! Request  → POST {base_url}{embeddings_path}
-//! Body     → `{"model": "…", "input": "…"}`
-//! Response → `{"data": [{"embedding": […]}]}`
+//! Body     → `{"model": "…", "input": ["…", …], "dimensions": N}`
+//! Response → `{"data": [{"index": 0, "embedding": […]}, …]}`
+//!
+//! `dimensions` is sent only when `output_dimensions` is configured; the
+//! response is still truncated client-side in case a server ignores it.
+//!
+//! Batches are sent as a single request; results are re-ordered by `index`
+//! because the API does not guarantee that `data` follows input order.
 //!
 //! Auth schemes (configured via `auth_scheme`):
 //! - `"bearer"` (default) → `Authorization: Bearer <api_key>`
 //! - `"api-key"`          → `api-key: <api_key>` (Azure OpenAI style)
+//!
+//! OpenAI models are symmetric; the input-kind hint only applies the optional
+//! `query_prefix` / `document_prefix` for compatible servers hosting
+//! asymmetric models.
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{config::ProviderConfig, error::EmbeddingError};
+use crate::{config::ProviderConfig, error::EmbeddingError, usage::report_tokens};
 
-use super::{Embedding, EmbeddingProvider};
+use super::{
+    batch_size_from_config, error_from_response, http_client_from_config, parse_response,
+    Embedding, EmbeddingProvider, InputKind, InputPrefixes, OutputShape,
+};
+
+/// Maximum number of inputs accepted by the OpenAI embeddings endpoint.
+const MAX_BATCH_SIZE: usize = 2048;
 
 pub struct OpenAIProvider {
     client: reqwest::Client,
@@ -27,6 +43,9 @@ pub struct OpenAIProvider {
     api_key: String,
     auth_scheme: String,
     embeddings_path: String,
+    max_batch_size: usize,
+    prefixes: InputPrefixes,
+    shape: OutputShape,
 }
 
 impl OpenAIProvider {
@@ -46,39 +65,34 @@ impl OpenAIProvider {
         };
 
         Ok(Self {
-            client: reqwest::Client::new(),
+            client: http_client_from_config(cfg)?,
             base_url: cfg.base_url.trim_end_matches('/').to_string(),
             model: cfg.model.clone(),
-            api_key: cfg.api_key.clone().unwrap_or_default(),
+            api_key: cfg
+                .api_key
+                .as_ref()
+                .map(|k| k.expose().to_string())
+                .unwrap_or_default(),
             auth_scheme,
             embeddings_path,
+            max_batch_size: batch_size_from_config(cfg.max_batch_size, MAX_BATCH_SIZE),
+            prefixes: InputPrefixes::from_config(cfg),
+            shape: OutputShape::from_config(cfg),
         })
     }
-}
 
-#[derive(Serialize)]
-struct OpenAIRequest<'a> {
-    model: &'a str,
-    input: &'a str,
-}
-
-#[derive(Deserialize)]
-struct EmbeddingObject {
-    embedding: Vec<f32>,
-}
-
-#[derive(Deserialize)]
-struct OpenAIResponse {
-    data: Vec<EmbeddingObject>,
-}
-
-#[async_trait]
-impl EmbeddingProvider for OpenAIProvider {
-    async fn embed(&self, text: &str) -> Result<Embedding, EmbeddingError> {
+    async fn request_embeddings(
+        &self,
+        input: &[&str],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
         let url = format!("{}{}", self.base_url, self.embeddings_path);
+        let prefixed = self.prefixes.apply(input, kind);
+        let input: Vec<&str> = prefixed.iter().map(AsRef::as_ref).collect();
         let body = OpenAIRequest {
             model: &self.model,
-            input: text,
+            input: &input,
+            dimensions: self.shape.dimensions(),
         };
 
         let mut request = self.client.post(&url).json(&body);
@@ -96,30 +110,89 @@ impl EmbeddingProvider for OpenAIProvider {
             return Err(EmbeddingError::AuthenticationError);
         }
         if !status.is_success() {
-            let message = response.text().await.unwrap_or_default();
-            return Err(EmbeddingError::ProviderError {
-                status: status.as_u16(),
-                message,
-            });
+            return Err(error_from_response(response).await);
         }
 
-        let parsed: OpenAIResponse = response.json().await.map_err(|e| {
-            EmbeddingError::InvalidResponse(format!("Failed to parse OpenAI response: {e}"))
-        })?;
+        let mut parsed: OpenAIResponse = parse_response(response, "OpenAI").await?;
 
+        if let Some(usage) = parsed.usage {
+            report_tokens(usage.prompt_tokens);
+        }
+        // Objects without an `index` (some compatible proxies omit it) keep
+        // their relative position thanks to the stable sort.
         parsed
             .data
+            .sort_by_key(|obj| obj.index.unwrap_or(usize::MAX));
+        self.shape
+            .apply(parsed.data.into_iter().map(|obj| obj.embedding).collect())
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+    /// Requested output size (`text-embedding-3-*` and compatible servers).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingObject {
+    #[serde(default)]
+    index: Option<usize>,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct OpenAIResponse {
+    data: Vec<EmbeddingObject>,
+    /// Absent from some compatible servers.
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.request_embeddings(&[text], kind)
+            .await?
             .into_iter()
             .next()
-            .map(|obj| obj.embedding)
             .ok_or_else(|| EmbeddingError::InvalidResponse("Empty data array".to_string()))
     }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let input: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.request_embeddings(&input, kind).await?;
+        if embeddings.len() != texts.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, OpenAI returned {}",
+                texts.len(),
+                embeddings.len()
+            )));
+        }
+        Ok(embeddings)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use wiremock::{
-        matchers::{header, method, path},
+        matchers::{body_json, body_partial_json, header, method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -131,9 +204,10 @@ mod tests {
             provider_type: "openai".to_string(),
             base_url: base_url.to_string(),
             model: "text-embedding-3-small".to_string(),
-            api_key: Some("test-key".to_string()),
+            api_key: Some("test-key".into()),
             auth_scheme: None,
             embeddings_path: None,
+            ..Default::default()
         }
     }
 
@@ -209,9 +283,10 @@ mod tests {
             provider_type: "openai".to_string(),
             base_url: server.uri(),
             model: "text-embedding-3-small".to_string(),
-            api_key: Some("test-key".to_string()),
+            api_key: Some("test-key".into()),
             auth_scheme: Some("api-key".to_string()),
             embeddings_path: None,
+            ..Default::default()
         };
         let provider = OpenAIProvider::new(&cfg).unwrap();
         let embedding = provider.embed("hello world").await.unwrap();
@@ -237,9 +312,10 @@ mod tests {
             provider_type: "openai".to_string(),
             base_url: server.uri(),
             model: "text-embedding-3-small".to_string(),
-            api_key: Some("test-key".to_string()),
+            api_key: Some("test-key".into()),
             auth_scheme: None,
             embeddings_path: Some(custom_path.to_string()),
+            ..Default::default()
         };
         let provider = OpenAIProvider::new(&cfg).unwrap();
         let embedding = provider.embed("hello world").await.unwrap();
@@ -252,9 +328,10 @@ mod tests {
             provider_type: "openai".to_string(),
             base_url: "https://api.openai.com".to_string(),
             model: "text-embedding-3-small".to_string(),
-            api_key: Some("key".to_string()),
+            api_key: Some("key".into()),
             auth_scheme: Some("api_key".to_string()), // typo: underscore instead of hyphen
             embeddings_path: None,
+            ..Default::default()
         };
         let result = OpenAIProvider::new(&cfg);
         assert!(matches!(result, Err(EmbeddingError::ConfigError(_))));
@@ -281,9 +358,163 @@ mod tests {
             api_key: None,
             auth_scheme: None,
             embeddings_path: Some("v1/embeddings".to_string()), // no leading slash
+            ..Default::default()
         };
         let provider = OpenAIProvider::new(&cfg).unwrap();
         let embedding = provider.embed("hello world").await.unwrap();
         assert_eq!(embedding, vec![0.5, 0.6]);
     }
+
+    #[tokio::test]
+    async fn embed_batch_orders_results_by_index() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(body_json(serde_json::json!({
+                "model": "text-embedding-3-small",
+                "input": ["first", "second", "third"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [
+                    {"embedding": [3.0_f32], "index": 2, "object": "embedding"},
+                    {"embedding": [1.0_f32], "index": 0, "object": "embedding"},
+                    {"embedding": [2.0_f32], "index": 1, "object": "embedding"}
+                ],
+                "model": "text-embedding-3-small",
+                "object": "list"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = OpenAIProvider::new(&make_config(&server.uri())).unwrap();
+        let texts = vec![
+            "first".to_string(),
+            "second".to_string(),
+            "third".to_string(),
+        ];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0], vec![3.0]]);
+    }
+
+    #[tokio::test]
+    async fn reported_usage_is_passed_on() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{"embedding": [0.1_f32], "index": 0, "object": "embedding"}],
+                "usage": {"prompt_tokens": 42, "total_tokens": 42}
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = OpenAIProvider::new(&make_config(&server.uri())).unwrap();
+        let (result, reported) = crate::usage::capture_reported(provider.embed("hello")).await;
+        result.unwrap();
+        assert_eq!(reported, Some(42));
+    }
+
+    #[test]
+    fn max_batch_size_is_clamped_to_upstream_limit() {
+        let cfg = ProviderConfig {
+            max_batch_size: Some(10_000),
+            ..make_config("https://api.openai.com")
+        };
+        let provider = OpenAIProvider::new(&cfg).unwrap();
+        assert_eq!(provider.max_batch_size(), MAX_BATCH_SIZE);
+    }
+
+    #[tokio::test]
+    async fn slow_upstream_fails_with_timeout() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_delay(std::time::Duration::from_millis(500))
+                    .set_body_json(serde_json::json!({"data": []})),
+            )
+            .mount(&server)
+            .await;
+
+        let mut cfg = make_config(&server.uri());
+        cfg.http.request_timeout_ms = Some(50);
+        let provider = OpenAIProvider::new(&cfg).unwrap();
+        let result = provider.embed("hello world").await;
+        assert!(
+            matches!(result, Err(EmbeddingError::Timeout(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn stalled_response_body_fails_with_timeout() {
+        let mut cfg = make_config(&crate::http_client::stalled_body_server().await);
+        cfg.http.request_timeout_ms = Some(100);
+        let provider = OpenAIProvider::new(&cfg).unwrap();
+        let result = provider.embed("hello world").await;
+        assert!(
+            matches!(result, Err(EmbeddingError::Timeout(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn embed_returns_rate_limited_with_retry_after_on_429() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "7")
+                    .set_body_string("Rate limit reached"),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = OpenAIProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello world").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::RateLimited {
+                retry_after_secs: Some(7),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn output_dimensions_are_requested_upstream_and_enforced() {
+        let server = MockServer::start().await;
+
+        // The server ignores `dimensions` and returns the full vector; the
+        // provider still truncates and normalises it.
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(body_partial_json(serde_json::json!({ "dimensions": 2 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{"embedding": [3.0_f32, 4.0_f32, 5.0_f32], "index": 0}],
+                "model": "text-embedding-3-small"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = OpenAIProvider::new(&ProviderConfig {
+            output_dimensions: Some(2),
+            normalize: Some(true),
+            ..make_config(&server.uri())
+        })
+        .unwrap();
+        let embedding = provider.embed("hello world").await.unwrap();
+        assert_eq!(embedding, vec![0.6, 0.8]);
+    }
 }
@@ -0,0 +1,392 @@
+//! Client-side rate limiting for embedding providers.
+//!
+//! [`RateLimitedProvider`] wraps a concrete provider and enforces the
+//! per-provider budgets from the config:
+//! - `requests_per_minute` – token bucket of upstream requests
+//! - `tokens_per_minute`   – token bucket of approximate input tokens
+//! - `max_concurrency`     – semaphore bounding requests in flight
+//!
+//! Calls that exceed a budget queue until it refills.  When the queueing time
+//! would exceed `max_queue_wait_ms`, the call is rejected immediately with
+//! [`EmbeddingError::BudgetExceeded`], which the server returns as HTTP 429
+//! with a `Retry-After` header.
+//!
+//! A call whose input alone needs more tokens than the token budget can
+//! supply within `max_queue_wait_ms`, even when the budget is full, could
+//! never be sent; it fails right away with [`EmbeddingError::BadRequest`]
+//! (HTTP 400) instead.
+//!
+//! The wrapper sits *inside* the retry layer, so every upstream attempt –
+//! including retries – is charged against the budget.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::{sync::Semaphore, time::sleep};
+
+use crate::{chunking::approx_token_count, config::ProviderConfig, error::EmbeddingError};
+
+use super::{DynEmbeddingProvider, Embedding, EmbeddingProvider, InputKind};
+
+/// Queueing limit used when `max_queue_wait_ms` is not configured.
+const DEFAULT_MAX_QUEUE_WAIT: Duration = Duration::from_secs(10);
+
+// ---------------------------------------------------------------------------
+// TokenBucket
+// ---------------------------------------------------------------------------
+
+/// A token bucket that allows reservations into debt.
+///
+/// A caller that reserves more than is available drives the balance negative
+/// and sleeps until the refill catches up, so queued callers are served in
+/// reservation order without a separate wait queue.
+struct TokenBucket {
+    capacity: f64,
+    /// Tokens added per second.
+    rate: f64,
+    balance: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(amount: u32, now: Instant) -> Self {
+        let capacity = f64::from(amount.max(1));
+        Self {
+            capacity,
+            rate: capacity / 60.0,
+            balance: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.balance = (self.balance + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` tokens are available.
+    fn wait_for(&self, amount: f64) -> Duration {
+        if self.balance >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.balance) / self.rate)
+        }
+    }
+
+    /// Time until `amount` tokens are available starting from a full bucket:
+    /// the shortest wait `amount` can ever have.
+    fn min_wait_for(&self, amount: f64) -> Duration {
+        Duration::from_secs_f64(((amount - self.capacity) / self.rate).max(0.0))
+    }
+}
+
+/// The request and token buckets of one provider, reserved together so a
+/// call never holds one budget while being rejected by the other.
+struct Budgets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+impl Budgets {
+    /// Reserve one request and `tokens` tokens, returning how long to wait
+    /// before sending.  Nothing is reserved when the wait would exceed
+    /// `max_wait`; the required wait is returned as the error instead.
+    fn reserve(
+        &mut self,
+        now: Instant,
+        tokens: f64,
+        max_wait: Duration,
+    ) -> Result<Duration, Duration> {
+        let mut wait = Duration::ZERO;
+        if let Some(bucket) = &mut self.requests {
+            bucket.refill(now);
+            wait = wait.max(bucket.wait_for(1.0));
+        }
+        if let Some(bucket) = &mut self.tokens {
+            bucket.refill(now);
+            wait = wait.max(bucket.wait_for(tokens));
+        }
+        if wait > max_wait {
+            return Err(wait);
+        }
+        if let Some(bucket) = &mut self.requests {
+            bucket.balance -= 1.0;
+        }
+        if let Some(bucket) = &mut self.tokens {
+            bucket.balance -= tokens;
+        }
+        Ok(wait)
+    }
+
+    /// Whether `tokens` need a longer wait than `max_wait` even from a full
+    /// token budget, so a reservation for them can never succeed.
+    fn never_fits(&self, tokens: f64, max_wait: Duration) -> bool {
+        self.tokens
+            .as_ref()
+            .is_some_and(|bucket| bucket.min_wait_for(tokens) > max_wait)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// RateLimitedProvider
+// ---------------------------------------------------------------------------
+
+/// An [`EmbeddingProvider`] that enforces request, token and concurrency
+/// budgets before calling the wrapped provider.
+pub struct RateLimitedProvider {
+    inner: DynEmbeddingProvider,
+    provider: String,
+    budgets: Mutex<Budgets>,
+    concurrency: Option<Semaphore>,
+    max_wait: Duration,
+}
+
+impl RateLimitedProvider {
+    pub fn new(inner: DynEmbeddingProvider, provider: &str, cfg: &ProviderConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            inner,
+            provider: provider.to_string(),
+            budgets: Mutex::new(Budgets {
+                requests: cfg
+                    .requests_per_minute
+                    .map(|n| TokenBucket::per_minute(n, now)),
+                tokens: cfg
+                    .tokens_per_minute
+                    .map(|n| TokenBucket::per_minute(n, now)),
+            }),
+            concurrency: cfg.max_concurrency.map(|n| Semaphore::new(n.max(1))),
+            max_wait: cfg
+                .max_queue_wait_ms
+                .map_or(DEFAULT_MAX_QUEUE_WAIT, Duration::from_millis),
+        }
+    }
+
+    fn rejected(&self, message: &str, wait: Duration) -> EmbeddingError {
+        EmbeddingError::BudgetExceeded {
+            provider: self.provider.clone(),
+            message: message.to_string(),
+            retry_after_secs: wait.as_secs_f64().ceil().max(1.0) as u64,
+        }
+    }
+
+    /// Wait for a concurrency slot and for the rate budgets, then run `op`.
+    async fn call<T, F>(&self, texts: &[&str], op: F) -> Result<T, EmbeddingError>
+    where
+        F: std::future::Future<Output = Result<T, EmbeddingError>> + Send,
+    {
+        let tokens: usize = texts.iter().map(|t| approx_token_count(t)).sum();
+        if self
+            .budgets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .never_fits(tokens as f64, self.max_wait)
+        {
+            return Err(EmbeddingError::BadRequest(format!(
+                "Input of about {tokens} tokens can never fit the tokens_per_minute budget of \
+                 provider '{}' within max_queue_wait_ms; send fewer or shorter texts",
+                self.provider
+            )));
+        }
+        let deadline = Instant::now() + self.max_wait;
+
+        let _slot = match &self.concurrency {
+            Some(semaphore) => Some(
+                tokio::time::timeout(self.max_wait, semaphore.acquire())
+                    .await
+                    .map_err(|_| {
+                        self.rejected("too many concurrent requests", Duration::from_secs(1))
+                    })?
+                    .expect("rate-limit semaphore is never closed"),
+            ),
+            None => None,
+        };
+
+        let now = Instant::now();
+        let remaining = deadline.saturating_duration_since(now);
+        let wait = self
+            .budgets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .reserve(now, tokens as f64, remaining)
+            .map_err(|wait| self.rejected("request or token budget exhausted", wait))?;
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+
+        op.await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RateLimitedProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.call(&[text], self.inner.embed_as(text, kind)).await
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.call(&refs, self.inner.embed_batch_as(texts, kind))
+            .await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    struct SlowProvider {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for SlowProvider {
+        async fn embed_as(
+            &self,
+            _text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            sleep(self.delay).await;
+            Ok(vec![1.0])
+        }
+    }
+
+    fn limited(delay: Duration, cfg: ProviderConfig) -> Arc<RateLimitedProvider> {
+        Arc::new(RateLimitedProvider::new(
+            Arc::new(SlowProvider { delay }),
+            "limited",
+            &cfg,
+        ))
+    }
+
+    #[test]
+    fn bucket_queues_within_max_wait_and_rejects_beyond_it() {
+        let start = Instant::now();
+        let mut budgets = Budgets {
+            requests: Some(TokenBucket::per_minute(60, start)),
+            tokens: None,
+        };
+        // Drain the burst capacity.
+        for _ in 0..60 {
+            assert_eq!(
+                budgets.reserve(start, 0.0, Duration::ZERO),
+                Ok(Duration::ZERO)
+            );
+        }
+        // The next request must wait one refill interval (1 s at 60 rpm).
+        let wait = budgets.reserve(start, 0.0, Duration::from_secs(5)).unwrap();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-6, "{wait:?}");
+        // That reservation is now owed, so the one after waits 2 s; with a
+        // 1.5 s limit it is rejected and nothing is reserved.
+        let rejected = budgets
+            .reserve(start, 0.0, Duration::from_millis(1500))
+            .unwrap_err();
+        assert!((rejected.as_secs_f64() - 2.0).abs() < 1e-6, "{rejected:?}");
+        // After 2 s of refill the request fits again without waiting.
+        let later = start + Duration::from_secs(2);
+        assert_eq!(
+            budgets.reserve(later, 0.0, Duration::ZERO),
+            Ok(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn token_budget_is_reserved_together_with_request_budget() {
+        let start = Instant::now();
+        let mut budgets = Budgets {
+            requests: Some(TokenBucket::per_minute(100, start)),
+            tokens: Some(TokenBucket::per_minute(60, start)),
+        };
+        assert!(budgets.reserve(start, 50.0, Duration::ZERO).is_ok());
+        // 20 tokens are not available; the request budget must stay untouched.
+        assert!(budgets.reserve(start, 20.0, Duration::ZERO).is_err());
+        let requests = budgets.requests.as_ref().unwrap();
+        assert!((requests.balance - 99.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn rejects_with_retry_after_when_budget_is_exhausted() {
+        let provider = limited(
+            Duration::ZERO,
+            ProviderConfig {
+                requests_per_minute: Some(1),
+                max_queue_wait_ms: Some(0),
+                ..Default::default()
+            },
+        );
+
+        provider.embed("first").await.unwrap();
+        let err = provider.embed("second").await.unwrap_err();
+        match err {
+            EmbeddingError::BudgetExceeded {
+                retry_after_secs, ..
+            } => assert_eq!(retry_after_secs, 60),
+            other => panic!("expected BudgetExceeded, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn input_larger_than_the_token_budget_allows_is_rejected_up_front() {
+        let provider = limited(
+            Duration::ZERO,
+            ProviderConfig {
+                tokens_per_minute: Some(60),
+                max_queue_wait_ms: Some(1_000),
+                ..Default::default()
+            },
+        );
+
+        // 200 tokens need 140 s of refill beyond the 60-token burst.
+        let long = "word ".repeat(200);
+        let err = provider.embed(&long).await.unwrap_err();
+        assert!(matches!(err, EmbeddingError::BadRequest(_)), "{err:?}");
+        assert!(!err.is_transient());
+
+        // Nothing was reserved, so input within the burst is still served.
+        provider.embed("short").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_queues_then_rejects() {
+        let provider = limited(
+            Duration::from_millis(200),
+            ProviderConfig {
+                max_concurrency: Some(1),
+                max_queue_wait_ms: Some(20),
+                ..Default::default()
+            },
+        );
+
+        let busy = tokio::spawn({
+            let provider = provider.clone();
+            async move { provider.embed("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let err = provider.embed("queued").await.unwrap_err();
+        assert!(
+            matches!(err, EmbeddingError::BudgetExceeded { .. }),
+            "{err:?}"
+        );
+        assert!(!err.is_transient());
+        busy.await.unwrap().unwrap();
+    }
+}
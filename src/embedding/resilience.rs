@@ -0,0 +1,473 @@
+//! Retry, backoff and circuit breaking for embedding providers.
+//!
+//! [`ResilientProvider`] wraps a concrete provider and:
+//! - retries transient failures (network errors, HTTP 429 and 5xx) with
+//!   jittered exponential backoff, honouring an upstream `Retry-After`
+//! - tracks consecutive failures in a per-provider [`CircuitBreaker`] and
+//!   fails fast with [`EmbeddingError::CircuitOpen`] while the provider is down
+//!
+//! Breaker states:
+//! - `closed`    – requests flow normally; failures are counted
+//! - `open`      – requests are rejected until `open_duration_secs` elapses
+//! - `half_open` – a single probe request is admitted; success closes the
+//!   breaker, failure re-opens it
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use rand::Rng;
+use serde::Serialize;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{config::ResilienceConfig, error::EmbeddingError};
+
+use super::{DynEmbeddingProvider, Embedding, EmbeddingProvider, InputKind};
+
+// ---------------------------------------------------------------------------
+// CircuitBreaker
+// ---------------------------------------------------------------------------
+
+/// Externally visible breaker state, reported by `/health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerStatus {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: bool },
+}
+
+/// Consecutive-failure circuit breaker for a single provider.
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+    failure_threshold: u32,
+    open_duration: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+        }
+    }
+
+    /// Current state; an open breaker whose cooldown has elapsed reports
+    /// `half_open` because the next request will be admitted as a probe.
+    pub fn status(&self) -> BreakerStatus {
+        match *self.state.lock().unwrap_or_else(|e| e.into_inner()) {
+            BreakerState::Closed { .. } => BreakerStatus::Closed,
+            BreakerState::Open { until } if Instant::now() < until => BreakerStatus::Open,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => BreakerStatus::HalfOpen,
+        }
+    }
+
+    /// Admit a request, or return how long the caller should wait.
+    fn acquire(&self) -> Result<Permit<'_>, Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let probe = match *state {
+            BreakerState::Closed { .. } => false,
+            BreakerState::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(until - now);
+                }
+                true
+            }
+            // Another request is already probing; ask the caller to come back
+            // shortly rather than piling onto a provider that may still be down.
+            BreakerState::HalfOpen { probing: true } => return Err(Duration::from_secs(1)),
+            BreakerState::HalfOpen { probing: false } => true,
+        };
+        if probe {
+            *state = BreakerState::HalfOpen { probing: true };
+        }
+        Ok(Permit {
+            breaker: self,
+            probe,
+            settled: false,
+        })
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) =
+            BreakerState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let open = match *state {
+            BreakerState::Closed { failures } => {
+                let failures = failures + 1;
+                if failures < self.failure_threshold {
+                    *state = BreakerState::Closed { failures };
+                    false
+                } else {
+                    true
+                }
+            }
+            BreakerState::HalfOpen { .. } => true,
+            BreakerState::Open { .. } => false,
+        };
+        if open {
+            *state = BreakerState::Open {
+                until: Instant::now() + self.open_duration,
+            };
+        }
+    }
+}
+
+/// Admission ticket for one logical request.
+///
+/// Dropping an unsettled probe (e.g. when the caller's future is cancelled)
+/// returns the breaker to `half_open` so the next request can probe instead.
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    settled: bool,
+}
+
+impl Permit<'_> {
+    fn settle(mut self, outcome: Result<(), &EmbeddingError>) {
+        // A local rate-limit rejection never reached the provider, so it says
+        // nothing about its health; dropping unsettled frees any probe slot.
+        if let Err(EmbeddingError::BudgetExceeded { .. }) = outcome {
+            return;
+        }
+        self.settled = true;
+        match outcome {
+            // Rate limiting means the provider is up, just busy; other
+            // non-transient errors (bad input, auth) also prove it answered.
+            Err(e) if e.is_transient() && !matches!(e, EmbeddingError::RateLimited { .. }) => {
+                self.breaker.record_failure()
+            }
+            _ => self.breaker.record_success(),
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.settled {
+            let mut state = self.breaker.state.lock().unwrap_or_else(|e| e.into_inner());
+            if let BreakerState::HalfOpen { probing: true } = *state {
+                *state = BreakerState::HalfOpen { probing: false };
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ResilientProvider
+// ---------------------------------------------------------------------------
+
+/// An [`EmbeddingProvider`] that retries transient failures and guards the
+/// wrapped provider with a [`CircuitBreaker`].
+pub struct ResilientProvider {
+    inner: DynEmbeddingProvider,
+    provider: String,
+    breaker: Arc<CircuitBreaker>,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl ResilientProvider {
+    pub fn new(inner: DynEmbeddingProvider, provider: &str, cfg: &ResilienceConfig) -> Self {
+        Self {
+            inner,
+            provider: provider.to_string(),
+            breaker: Arc::new(CircuitBreaker::new(
+                cfg.failure_threshold,
+                Duration::from_secs(cfg.open_duration_secs),
+            )),
+            max_retries: cfg.max_retries,
+            initial_backoff: Duration::from_millis(cfg.initial_backoff_ms),
+            max_backoff: Duration::from_millis(cfg.max_backoff_ms),
+        }
+    }
+
+    /// The breaker guarding this provider, shared with the registry for
+    /// health reporting.
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        self.breaker.clone()
+    }
+
+    async fn call<T, F, Fut>(&self, op: F) -> Result<T, EmbeddingError>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T, EmbeddingError>> + Send,
+        T: Send,
+    {
+        let permit = self
+            .breaker
+            .acquire()
+            .map_err(|wait| EmbeddingError::CircuitOpen {
+                provider: self.provider.clone(),
+                retry_after_secs: wait.as_secs().max(1),
+            })?;
+
+        let mut attempt = 0u32;
+        loop {
+            let error = match op().await {
+                Ok(value) => {
+                    permit.settle(Ok(()));
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+
+            let delay = if error.is_transient() && attempt < self.max_retries {
+                self.retry_delay(attempt, &error)
+            } else {
+                None
+            };
+            let Some(delay) = delay else {
+                permit.settle(Err(&error));
+                return Err(error);
+            };
+
+            attempt += 1;
+            warn!(
+                provider = %self.provider,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                error = %error,
+                "Transient embedding provider error, retrying"
+            );
+            sleep(delay).await;
+        }
+    }
+
+    /// Delay before retry number `attempt + 1`, or `None` when the upstream
+    /// asked us to wait longer than `max_backoff`.
+    fn retry_delay(&self, attempt: u32, error: &EmbeddingError) -> Option<Duration> {
+        if let EmbeddingError::RateLimited {
+            retry_after_secs: Some(secs),
+            ..
+        } = error
+        {
+            let requested = Duration::from_secs(*secs);
+            return (requested <= self.max_backoff).then_some(requested);
+        }
+
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        // "Equal jitter": wait between half and the full exponential delay so
+        // that concurrent callers spread out without retrying immediately.
+        let half = exponential / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        Some(half + Duration::from_millis(jitter))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for ResilientProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.call(|| self.inner.embed_as(text, kind)).await
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.call(|| self.inner.embed_batch_as(texts, kind)).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// Provider that replays a scripted sequence of outcomes, then succeeds.
+    struct ScriptedProvider {
+        script: Mutex<VecDeque<EmbeddingError>>,
+        calls: AtomicUsize,
+    }
+
+    impl ScriptedProvider {
+        fn new(errors: Vec<EmbeddingError>) -> Arc<Self> {
+            Arc::new(Self {
+                script: Mutex::new(errors.into()),
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for ScriptedProvider {
+        async fn embed_as(
+            &self,
+            _text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.script.lock().unwrap().pop_front() {
+                Some(e) => Err(e),
+                None => Ok(vec![1.0]),
+            }
+        }
+    }
+
+    fn unavailable() -> EmbeddingError {
+        EmbeddingError::ProviderError {
+            status: 503,
+            message: "unavailable".to_string(),
+        }
+    }
+
+    fn fast_config(max_retries: u32, failure_threshold: u32) -> ResilienceConfig {
+        ResilienceConfig {
+            max_retries,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+            failure_threshold,
+            open_duration_secs: 60,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors_until_success() {
+        let inner = ScriptedProvider::new(vec![unavailable(), unavailable()]);
+        let provider = ResilientProvider::new(inner.clone(), "test", &fast_config(3, 5));
+
+        assert_eq!(provider.embed("hi").await.unwrap(), vec![1.0]);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(provider.breaker.status(), BreakerStatus::Closed);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_transient_errors() {
+        let inner = ScriptedProvider::new(vec![EmbeddingError::ProviderError {
+            status: 400,
+            message: "bad input".to_string(),
+        }]);
+        let provider = ResilientProvider::new(inner.clone(), "test", &fast_config(3, 5));
+
+        let result = provider.embed("hi").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::ProviderError { status: 400, .. })
+        ));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_retry_after_exceeds_max_backoff() {
+        let inner = ScriptedProvider::new(vec![EmbeddingError::RateLimited {
+            message: "slow down".to_string(),
+            retry_after_secs: Some(120),
+        }]);
+        let provider = ResilientProvider::new(inner.clone(), "test", &fast_config(3, 5));
+
+        let result = provider.embed("hi").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::RateLimited {
+                retry_after_secs: Some(120),
+                ..
+            })
+        ));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_after_threshold_and_fails_fast() {
+        let inner = ScriptedProvider::new(vec![unavailable(), unavailable(), unavailable()]);
+        let provider = ResilientProvider::new(inner.clone(), "test", &fast_config(0, 2));
+
+        assert!(provider.embed("a").await.is_err());
+        assert_eq!(provider.breaker.status(), BreakerStatus::Closed);
+        assert!(provider.embed("b").await.is_err());
+        assert_eq!(provider.breaker.status(), BreakerStatus::Open);
+
+        let result = provider.embed("c").await;
+        assert!(matches!(result, Err(EmbeddingError::CircuitOpen { .. })));
+        // The open breaker must not have forwarded the third request.
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn half_open_probe_success_closes_breaker() {
+        let inner = ScriptedProvider::new(vec![unavailable()]);
+        let cfg = ResilienceConfig {
+            open_duration_secs: 0,
+            ..fast_config(0, 1)
+        };
+        let provider = ResilientProvider::new(inner.clone(), "test", &cfg);
+
+        assert!(provider.embed("a").await.is_err());
+        // Zero cooldown: the breaker is immediately ready to probe.
+        assert_eq!(provider.breaker.status(), BreakerStatus::HalfOpen);
+        assert_eq!(provider.embed("b").await.unwrap(), vec![1.0]);
+        assert_eq!(provider.breaker.status(), BreakerStatus::Closed);
+    }
+
+    #[tokio::test]
+    async fn local_budget_rejection_does_not_affect_breaker() {
+        let budget = || EmbeddingError::BudgetExceeded {
+            provider: "test".to_string(),
+            message: "budget".to_string(),
+            retry_after_secs: 1,
+        };
+        let inner = ScriptedProvider::new(vec![unavailable(), budget(), unavailable()]);
+        let provider = ResilientProvider::new(inner.clone(), "test", &fast_config(0, 2));
+
+        assert!(provider.embed("a").await.is_err());
+        assert!(matches!(
+            provider.embed("b").await,
+            Err(EmbeddingError::BudgetExceeded { .. })
+        ));
+        // The rejection neither reset nor extended the failure count, so the
+        // next upstream failure is the second consecutive one.
+        assert!(provider.embed("c").await.is_err());
+        assert_eq!(provider.breaker.status(), BreakerStatus::Open);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_within_bounds() {
+        let inner = ScriptedProvider::new(vec![]);
+        let cfg = ResilienceConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            ..ResilienceConfig::default()
+        };
+        let provider = ResilientProvider::new(inner, "test", &cfg);
+
+        for attempt in 0..6 {
+            let full = Duration::from_millis((100u64 << attempt).min(1_000));
+            let delay = provider.retry_delay(attempt, &unavailable()).unwrap();
+            assert!(
+                delay >= full / 2 && delay <= full,
+                "attempt {attempt}: {delay:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Single-flight deduplication of concurrent embedding calls.
+//!
+//! [`SingleFlightProvider`] coalesces concurrent `embed` calls for the same
+//! text and input kind into one upstream request.  The first caller (the
+//! leader) calls the wrapped provider; callers arriving while that request is
+//! in flight wait for it and receive a copy of its result, success or error.
+//! Each coalesced call increments `embedding_coalesced_total`.
+//!
+//! Nothing is remembered once the request completes – repeated calls are the
+//! cache's job.  If the leader is cancelled (e.g. its client disconnects), one
+//! of the waiters takes over and issues the request itself.
+//!
+//! Batch calls are passed through unchanged.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+use crate::{error::EmbeddingError, metrics::metrics};
+
+use super::{DynEmbeddingProvider, Embedding, EmbeddingProvider, InputKind};
+
+/// Result of an in-flight request as delivered to waiters.
+type Shared = Result<Embedding, Arc<EmbeddingError>>;
+
+type Key = (InputKind, String);
+
+pub struct SingleFlightProvider {
+    inner: DynEmbeddingProvider,
+    provider: String,
+    in_flight: Mutex<HashMap<Key, broadcast::Sender<Shared>>>,
+}
+
+enum Role {
+    Leader(broadcast::Sender<Shared>),
+    Waiter(broadcast::Receiver<Shared>),
+}
+
+impl SingleFlightProvider {
+    pub fn new(inner: DynEmbeddingProvider, provider: &str) -> Self {
+        Self {
+            inner,
+            provider: provider.to_string(),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Join the request in flight for `key`, or register a new one.
+    fn join(&self, key: &Key) -> Role {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        match in_flight.get(key) {
+            Some(sender) => Role::Waiter(sender.subscribe()),
+            None => {
+                let (sender, _) = broadcast::channel(1);
+                in_flight.insert(key.clone(), sender.clone());
+                Role::Leader(sender)
+            }
+        }
+    }
+}
+
+/// Removes the leader's entry when its request completes or is cancelled.
+struct InFlightGuard<'a> {
+    in_flight: &'a Mutex<HashMap<Key, broadcast::Sender<Shared>>>,
+    key: &'a Key,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(self.key);
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for SingleFlightProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        let key = (kind, text.to_string());
+        loop {
+            match self.join(&key) {
+                Role::Waiter(mut receiver) => match receiver.recv().await {
+                    Ok(shared) => {
+                        metrics().embedding_coalesced.inc(&[&self.provider]);
+                        return shared.map_err(|e| e.duplicate());
+                    }
+                    // The leader was cancelled before finishing; retry, most
+                    // likely as the new leader.
+                    Err(_) => continue,
+                },
+                Role::Leader(sender) => {
+                    let guard = InFlightGuard {
+                        in_flight: &self.in_flight,
+                        key: &key,
+                    };
+                    let result = self.inner.embed_as(text, kind).await;
+                    // Unregister before publishing so no waiter subscribes
+                    // after the result has been sent.
+                    drop(guard);
+                    if sender.receiver_count() > 0 {
+                        let shared = match &result {
+                            Ok(embedding) => Ok(embedding.clone()),
+                            Err(e) => Err(Arc::new(e.duplicate())),
+                        };
+                        let _ = sender.send(shared);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.inner.embed_batch_as(texts, kind).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// Counts upstream calls and answers after a short delay.
+    struct SlowProvider {
+        calls: AtomicUsize,
+        fail: bool,
+    }
+
+    impl SlowProvider {
+        fn new(fail: bool) -> Arc<Self> {
+            Arc::new(Self {
+                calls: AtomicUsize::new(0),
+                fail,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for SlowProvider {
+        async fn embed_as(
+            &self,
+            text: &str,
+            _kind: InputKind,
+        ) -> Result<Embedding, EmbeddingError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if self.fail {
+                return Err(EmbeddingError::ProviderError {
+                    status: 503,
+                    message: "overloaded".to_string(),
+                });
+            }
+            Ok(vec![text.len() as f32])
+        }
+    }
+
+    fn coalesced(provider: &str) -> String {
+        let mut out = String::new();
+        metrics().render(&mut out);
+        out.lines()
+            .find(|l| {
+                l.starts_with(&format!(
+                    "embedding_coalesced_total{{provider=\"{provider}\"}}"
+                ))
+            })
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn concurrent_identical_calls_share_one_request() {
+        let name = format!("single-flight-{}", uuid::Uuid::new_v4());
+        let inner = SlowProvider::new(false);
+        let provider = SingleFlightProvider::new(inner.clone(), &name);
+
+        let (a, b, c) = tokio::join!(
+            provider.embed("hello"),
+            provider.embed("hello"),
+            provider.embed("hello")
+        );
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        for result in [a, b, c] {
+            assert_eq!(result.unwrap(), vec![5.0]);
+        }
+        assert!(coalesced(&name).ends_with(" 2"), "{}", coalesced(&name));
+
+        // Completed requests are not remembered.
+        provider.embed("hello").await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn different_texts_and_input_kinds_are_not_coalesced() {
+        let inner = SlowProvider::new(false);
+        let provider = SingleFlightProvider::new(inner.clone(), "single-flight-distinct");
+
+        let (a, b, c) = tokio::join!(
+            provider.embed_as("hello", InputKind::Query),
+            provider.embed_as("hello", InputKind::Document),
+            provider.embed_as("world", InputKind::Query)
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn errors_fan_out_to_every_waiter() {
+        let inner = SlowProvider::new(true);
+        let provider = SingleFlightProvider::new(inner.clone(), "single-flight-errors");
+
+        let (a, b) = tokio::join!(provider.embed("hello"), provider.embed("hello"));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        for result in [a, b] {
+            assert!(matches!(
+                result,
+                Err(EmbeddingError::ProviderError { status: 503, .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn waiter_takes_over_when_leader_is_cancelled() {
+        let inner = SlowProvider::new(false);
+        let provider = SingleFlightProvider::new(inner.clone(), "single-flight-cancel");
+
+        let (leader, waiter) = tokio::join!(
+            tokio::time::timeout(Duration::from_millis(10), provider.embed("hello")),
+            provider.embed("hello")
+        );
+        assert!(leader.is_err());
+        assert_eq!(waiter.unwrap(), vec![5.0]);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,245 @@
+//! Hugging Face text-embeddings-inference (TEI) provider.
+//!
+//! API reference: <https://huggingface.github.io/text-embeddings-inference/>
+//!
+//! Request  → POST {base_url}/embed
+//! Body     → `{"inputs": ["…", …], "normalize": bool, "truncate": bool}`
+//! Response → `[[…], …]`
+//!
+//! A TEI server hosts a single model, so `model` is not sent.  `normalize` and
+//! `truncate` are sent only when `normalize` and `truncation` are configured,
+//! so TEI's own defaults (normalised, not truncated) apply otherwise.  `api_key`, when set, is
+//! sent as a bearer token for deployments behind an authenticating proxy.
+//!
+//! TEI has no input-type field; asymmetric models are steered with
+//! `query_prefix` / `document_prefix` in the provider config.
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{config::ProviderConfig, error::EmbeddingError};
+
+use super::{
+    batch_size_from_config, error_from_response, http_client_from_config, parse_response,
+    Embedding, EmbeddingProvider, InputKind, InputPrefixes, OutputShape,
+};
+
+/// TEI's default `--max-client-batch-size`.
+const MAX_BATCH_SIZE: usize = 32;
+
+pub struct TeiProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    normalize: Option<bool>,
+    truncate: Option<bool>,
+    max_batch_size: usize,
+    prefixes: InputPrefixes,
+    shape: OutputShape,
+}
+
+impl TeiProvider {
+    pub fn new(cfg: &ProviderConfig) -> Result<Self, EmbeddingError> {
+        Ok(Self {
+            client: http_client_from_config(cfg)?,
+            base_url: cfg.base_url.trim_end_matches('/').to_string(),
+            api_key: cfg
+                .api_key
+                .as_ref()
+                .map(|k| k.expose().to_string())
+                .filter(|k| !k.is_empty()),
+            normalize: cfg.normalize,
+            truncate: cfg.truncation,
+            max_batch_size: batch_size_from_config(cfg.max_batch_size, MAX_BATCH_SIZE),
+            prefixes: InputPrefixes::from_config(cfg),
+            shape: OutputShape::from_config(cfg),
+        })
+    }
+
+    async fn request_embeddings(
+        &self,
+        input: &[&str],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let url = format!("{}/embed", self.base_url);
+        let prefixed = self.prefixes.apply(input, kind);
+        let inputs: Vec<&str> = prefixed.iter().map(AsRef::as_ref).collect();
+        let body = TeiRequest {
+            inputs: &inputs,
+            normalize: self.normalize,
+            truncate: self.truncate,
+        };
+
+        let mut request = self.client.post(&url).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(EmbeddingError::AuthenticationError);
+        }
+        if !status.is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let embeddings: Vec<Vec<f32>> = parse_response(response, "TEI").await?;
+
+        self.shape.apply(embeddings)
+    }
+}
+
+#[derive(Serialize)]
+struct TeiRequest<'a> {
+    inputs: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalize: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncate: Option<bool>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for TeiProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.request_embeddings(&[text], kind)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| EmbeddingError::InvalidResponse("Empty embeddings array".to_string()))
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let input: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.request_embeddings(&input, kind).await?;
+        if embeddings.len() != texts.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, TEI returned {}",
+                texts.len(),
+                embeddings.len()
+            )));
+        }
+        Ok(embeddings)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::config::ProviderConfig;
+
+    fn make_config(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            provider_type: "tei".to_string(),
+            base_url: base_url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn embed_batch_sends_inputs_in_one_request() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .and(body_json(serde_json::json!({
+                "inputs": ["first", "second"]
+            })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([[1.0_f32, 2.0_f32], [3.0_f32, 4.0_f32]])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = TeiProvider::new(&make_config(&server.uri())).unwrap();
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+    }
+
+    #[tokio::test]
+    async fn normalize_truncate_and_api_key_are_sent_when_configured() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .and(header("authorization", "Bearer proxy-key"))
+            .and(body_json(serde_json::json!({
+                "inputs": ["hello"],
+                "normalize": true,
+                "truncate": true
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([[0.6_f32, 0.8_f32]])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = TeiProvider::new(&ProviderConfig {
+            api_key: Some("proxy-key".into()),
+            normalize: Some(true),
+            truncation: Some(true),
+            ..make_config(&server.uri())
+        })
+        .unwrap();
+        let embedding = provider.embed("hello").await.unwrap();
+        assert_eq!(embedding, vec![0.6, 0.8]);
+    }
+
+    #[tokio::test]
+    async fn overlong_input_error_is_reported_as_provider_error() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .respond_with(ResponseTemplate::new(413).set_body_json(serde_json::json!({
+                "error": "Input validation error: inputs must have less than 512 tokens",
+                "error_type": "Validation"
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = TeiProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("very long text").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::ProviderError { status: 413, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn malformed_response_is_an_invalid_response() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "embeddings": []
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = TeiProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello").await;
+        assert!(matches!(result, Err(EmbeddingError::InvalidResponse(_))));
+    }
+}
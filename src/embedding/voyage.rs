@@ -0,0 +1,384 @@
+//! Voyage AI embedding provider.
+//!
+//! API reference: <https://docs.voyageai.com/reference/embeddings-api>
+//!
+//! Request  → POST {base_url}/v1/embeddings   (`base_url` defaults to
+//!             `https://api.voyageai.com`)
+//! Headers  → `Authorization: Bearer <api_key>`
+//! Body     → `{"model": "…", "input": ["…", …], "input_type": "query"|"document",
+//!             "truncation": bool, "output_dimension": N, "output_dtype": "…"}`
+//! Response → `{"data": [{"index": 0, "embedding": […]}, …]}`
+//!
+//! `input_type` is sent only when the caller passes an [`InputKind`] hint;
+//! `truncation`, `output_dimension` and `output_dtype` only when configured
+//! (`truncation`, `output_dimensions`, `output_dtype`).  The response is still
+//! truncated client-side in case the model ignores `output_dimension`.
+//!
+//! Only the numeric output types (`float`, `int8`, `uint8`) are supported;
+//! the bit-packed `binary` types do not have one component per dimension.
+//!
+//! This provider replaces the former `claude` type, which pointed at an
+//! Anthropic endpoint; see [`crate::config::EmbeddingConfig::migrate_legacy_providers`].
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{ProviderConfig, VOYAGE_BASE_URL},
+    error::EmbeddingError,
+    usage::report_tokens,
+};
+
+use super::{
+    batch_size_from_config, error_from_response, http_client_from_config, output_dtype_from_config,
+    parse_response, Embedding, EmbeddingProvider, InputKind, InputPrefixes, OutputShape,
+};
+
+/// Maximum number of inputs per request accepted by the Voyage API.
+const MAX_BATCH_SIZE: usize = 1000;
+
+pub struct VoyageProvider {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: String,
+    truncation: Option<bool>,
+    output_dtype: Option<String>,
+    max_batch_size: usize,
+    prefixes: InputPrefixes,
+    shape: OutputShape,
+}
+
+impl VoyageProvider {
+    pub fn new(cfg: &ProviderConfig) -> Result<Self, EmbeddingError> {
+        Ok(Self {
+            client: http_client_from_config(cfg)?,
+            base_url: match cfg.base_url.trim_end_matches('/') {
+                "" => VOYAGE_BASE_URL.to_string(),
+                base_url => base_url.to_string(),
+            },
+            model: cfg.model.clone(),
+            api_key: cfg
+                .api_key
+                .as_ref()
+                .map(|k| k.expose().to_string())
+                .unwrap_or_default(),
+            truncation: cfg.truncation,
+            output_dtype: output_dtype_from_config(cfg)?,
+            max_batch_size: batch_size_from_config(cfg.max_batch_size, MAX_BATCH_SIZE),
+            prefixes: InputPrefixes::from_config(cfg),
+            shape: OutputShape::from_config(cfg),
+        })
+    }
+
+    async fn request_embeddings(
+        &self,
+        input: &[&str],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        if self.api_key.is_empty() {
+            return Err(EmbeddingError::AuthenticationError);
+        }
+
+        let url = format!("{}/v1/embeddings", self.base_url);
+        let prefixed = self.prefixes.apply(input, kind);
+        let input: Vec<&str> = prefixed.iter().map(AsRef::as_ref).collect();
+        let body = VoyageRequest {
+            model: &self.model,
+            input: &input,
+            input_type: kind.as_str(),
+            truncation: self.truncation,
+            output_dimension: self.shape.dimensions(),
+            output_dtype: self.output_dtype.as_deref(),
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(EmbeddingError::AuthenticationError);
+        }
+        if !status.is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let mut parsed: VoyageResponse = parse_response(response, "Voyage").await?;
+
+        if let Some(usage) = parsed.usage {
+            report_tokens(usage.total_tokens);
+        }
+        parsed
+            .data
+            .sort_by_key(|obj| obj.index.unwrap_or(usize::MAX));
+        self.shape
+            .apply(parsed.data.into_iter().map(|obj| obj.embedding).collect())
+    }
+}
+
+#[derive(Serialize)]
+struct VoyageRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+    /// `"query"` or `"document"`; omitted when the caller gave no hint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncation: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dimension: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dtype: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingObject {
+    #[serde(default)]
+    index: Option<usize>,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct VoyageUsage {
+    total_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct VoyageResponse {
+    data: Vec<EmbeddingObject>,
+    #[serde(default)]
+    usage: Option<VoyageUsage>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for VoyageProvider {
+    async fn embed_as(&self, text: &str, kind: InputKind) -> Result<Embedding, EmbeddingError> {
+        self.request_embeddings(&[text], kind)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| EmbeddingError::InvalidResponse("Empty data array".to_string()))
+    }
+
+    async fn embed_batch_as(
+        &self,
+        texts: &[String],
+        kind: InputKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let input: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.request_embeddings(&input, kind).await?;
+        if embeddings.len() != texts.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} embeddings, Voyage returned {}",
+                texts.len(),
+                embeddings.len()
+            )));
+        }
+        Ok(embeddings)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::config::ProviderConfig;
+
+    fn make_config(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            provider_type: "voyage".to_string(),
+            base_url: base_url.to_string(),
+            model: "voyage-3".to_string(),
+            api_key: Some("test-voyage-key".into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn embed_sends_bearer_auth_and_returns_first_embedding() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(header("authorization", "Bearer test-voyage-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [{"object": "embedding", "embedding": [0.7_f32, 0.8_f32, 0.9_f32], "index": 0}],
+                "model": "voyage-3",
+                "usage": {"total_tokens": 2}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = VoyageProvider::new(&make_config(&server.uri())).unwrap();
+        let embedding = provider.embed("hello world").await.unwrap();
+        assert_eq!(embedding, vec![0.7, 0.8, 0.9]);
+    }
+
+    #[tokio::test]
+    async fn embed_returns_auth_error_when_api_key_missing() {
+        let provider = VoyageProvider::new(&ProviderConfig {
+            api_key: None,
+            ..make_config("http://localhost:11434")
+        })
+        .unwrap();
+        let result = provider.embed("hello world").await;
+        assert!(matches!(result, Err(EmbeddingError::AuthenticationError)));
+    }
+
+    #[tokio::test]
+    async fn embed_returns_auth_error_on_401() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("Unauthorized"))
+            .mount(&server)
+            .await;
+
+        let provider = VoyageProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello world").await;
+        assert!(matches!(result, Err(EmbeddingError::AuthenticationError)));
+    }
+
+    #[tokio::test]
+    async fn embed_returns_error_on_empty_data() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [],
+                "model": "voyage-3"
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = VoyageProvider::new(&make_config(&server.uri())).unwrap();
+        let result = provider.embed("hello world").await;
+        assert!(matches!(result, Err(EmbeddingError::InvalidResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn embed_batch_sends_all_inputs_in_one_request() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(body_json(serde_json::json!({
+                "model": "voyage-3",
+                "input": ["first", "second"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [
+                    {"embedding": [2.0_f32], "index": 1},
+                    {"embedding": [1.0_f32], "index": 0}
+                ],
+                "model": "voyage-3"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = VoyageProvider::new(&make_config(&server.uri())).unwrap();
+        let texts = vec!["first".to_string(), "second".to_string()];
+        let embeddings = provider
+            .embed_batch_as(&texts, InputKind::Unspecified)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0]]);
+    }
+
+    #[tokio::test]
+    async fn input_kind_is_sent_as_input_type() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(body_json(serde_json::json!({
+                "model": "voyage-3",
+                "input": ["what is the deployment target?"],
+                "input_type": "query"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{"embedding": [0.5_f32], "index": 0}],
+                "model": "voyage-3"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = VoyageProvider::new(&make_config(&server.uri())).unwrap();
+        let embedding = provider
+            .embed_as("what is the deployment target?", InputKind::Query)
+            .await
+            .unwrap();
+        assert_eq!(embedding, vec![0.5]);
+    }
+
+    #[tokio::test]
+    async fn configured_request_options_are_sent() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(body_json(serde_json::json!({
+                "model": "voyage-3-large",
+                "input": ["stored text"],
+                "input_type": "document",
+                "truncation": false,
+                "output_dimension": 2,
+                "output_dtype": "int8"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{"embedding": [12, -7], "index": 0}],
+                "model": "voyage-3-large"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = VoyageProvider::new(&ProviderConfig {
+            model: "voyage-3-large".to_string(),
+            truncation: Some(false),
+            output_dimensions: Some(2),
+            output_dtype: Some("int8".to_string()),
+            ..make_config(&server.uri())
+        })
+        .unwrap();
+        let embedding = provider
+            .embed_as("stored text", InputKind::Document)
+            .await
+            .unwrap();
+        assert_eq!(embedding, vec![12.0, -7.0]);
+    }
+
+    #[test]
+    fn bit_packed_output_dtypes_are_rejected() {
+        let result = VoyageProvider::new(&ProviderConfig {
+            output_dtype: Some("binary".to_string()),
+            ..make_config("http://localhost")
+        });
+        assert!(matches!(result, Err(EmbeddingError::ConfigError(_))));
+    }
+
+    #[test]
+    fn base_url_defaults_to_the_voyage_api() {
+        let provider = VoyageProvider::new(&make_config("")).unwrap();
+        assert_eq!(provider.base_url, VOYAGE_BASE_URL);
+    }
+}
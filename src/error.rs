@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -52,7 +52,7 @@ impl IntoResponse for SessionError {
 #[derive(Debug, Error)]
 pub enum EmbeddingError {
     #[error("HTTP request failed: {0}")]
-    HttpError(#[from] reqwest::Error),
+    HttpError(reqwest::Error),
 
     #[error("Provider '{0}' is not configured")]
     ProviderNotFound(String),
@@ -74,10 +74,149 @@ pub enum EmbeddingError {
 
     #[error("Memory entry '{0}' not found")]
     MemoryNotFound(String),
+
+    #[error("Memory store error: {0}")]
+    MemoryStorage(String),
+
+    #[error("Provider rate limit exceeded: {message}")]
+    RateLimited {
+        message: String,
+        /// Delay requested by the upstream `Retry-After` header, if any.
+        retry_after_secs: Option<u64>,
+    },
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Provider '{provider}' is temporarily unavailable (circuit open)")]
+    CircuitOpen {
+        provider: String,
+        /// Seconds until the breaker admits a probe request.
+        retry_after_secs: u64,
+    },
+
+    #[error("Rate limit for provider '{provider}' exceeded: {message}")]
+    BudgetExceeded {
+        provider: String,
+        message: String,
+        /// Seconds until the local budget can admit the request.
+        retry_after_secs: u64,
+    },
+}
+
+/// Timeouts become [`EmbeddingError::Timeout`] (HTTP 504); every other
+/// transport failure is an [`EmbeddingError::HttpError`].
+impl From<reqwest::Error> for EmbeddingError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            EmbeddingError::Timeout(e.to_string())
+        } else {
+            EmbeddingError::HttpError(e)
+        }
+    }
+}
+
+impl EmbeddingError {
+    /// Short snake_case name of the variant, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EmbeddingError::HttpError(_) => "http",
+            EmbeddingError::ProviderNotFound(_) => "provider_not_found",
+            EmbeddingError::InvalidResponse(_) => "invalid_response",
+            EmbeddingError::AuthenticationError => "authentication",
+            EmbeddingError::ProviderError { .. } => "provider_error",
+            EmbeddingError::ConfigError(_) => "config",
+            EmbeddingError::BadRequest(_) => "bad_request",
+            EmbeddingError::MemoryNotFound(_) => "memory_not_found",
+            EmbeddingError::MemoryStorage(_) => "memory_storage",
+            EmbeddingError::RateLimited { .. } => "rate_limited",
+            EmbeddingError::Timeout(_) => "timeout",
+            EmbeddingError::CircuitOpen { .. } => "circuit_open",
+            EmbeddingError::BudgetExceeded { .. } => "budget_exceeded",
+        }
+    }
+
+    /// A copy of this error for callers that shared one upstream request.
+    ///
+    /// `reqwest::Error` cannot be cloned, so an `HttpError` is copied as a
+    /// 502 `ProviderError` carrying its message, which is equally transient.
+    pub fn duplicate(&self) -> EmbeddingError {
+        match self {
+            EmbeddingError::HttpError(e) => EmbeddingError::ProviderError {
+                status: e.status().map_or(502, |s| s.as_u16()),
+                message: e.to_string(),
+            },
+            EmbeddingError::ProviderNotFound(name) => {
+                EmbeddingError::ProviderNotFound(name.clone())
+            }
+            EmbeddingError::InvalidResponse(msg) => EmbeddingError::InvalidResponse(msg.clone()),
+            EmbeddingError::AuthenticationError => EmbeddingError::AuthenticationError,
+            EmbeddingError::ProviderError { status, message } => EmbeddingError::ProviderError {
+                status: *status,
+                message: message.clone(),
+            },
+            EmbeddingError::ConfigError(msg) => EmbeddingError::ConfigError(msg.clone()),
+            EmbeddingError::BadRequest(msg) => EmbeddingError::BadRequest(msg.clone()),
+            EmbeddingError::MemoryNotFound(id) => EmbeddingError::MemoryNotFound(id.clone()),
+            EmbeddingError::MemoryStorage(msg) => EmbeddingError::MemoryStorage(msg.clone()),
+            EmbeddingError::RateLimited {
+                message,
+                retry_after_secs,
+            } => EmbeddingError::RateLimited {
+                message: message.clone(),
+                retry_after_secs: *retry_after_secs,
+            },
+            EmbeddingError::Timeout(msg) => EmbeddingError::Timeout(msg.clone()),
+            EmbeddingError::CircuitOpen {
+                provider,
+                retry_after_secs,
+            } => EmbeddingError::CircuitOpen {
+                provider: provider.clone(),
+                retry_after_secs: *retry_after_secs,
+            },
+            EmbeddingError::BudgetExceeded {
+                provider,
+                message,
+                retry_after_secs,
+            } => EmbeddingError::BudgetExceeded {
+                provider: provider.clone(),
+                message: message.clone(),
+                retry_after_secs: *retry_after_secs,
+            },
+        }
+    }
+
+    /// Whether the failure is likely to succeed on retry: network errors,
+    /// upstream rate limiting and 5xx gateway/availability errors.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            EmbeddingError::HttpError(_)
+                | EmbeddingError::Timeout(_)
+                | EmbeddingError::RateLimited { .. }
+                | EmbeddingError::ProviderError {
+                    status: 500 | 502 | 503 | 504,
+                    ..
+                }
+        )
+    }
 }
 
 impl IntoResponse for EmbeddingError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            EmbeddingError::RateLimited {
+                retry_after_secs, ..
+            } => *retry_after_secs,
+            EmbeddingError::CircuitOpen {
+                retry_after_secs, ..
+            }
+            | EmbeddingError::BudgetExceeded {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            _ => None,
+        };
+
         let (status, message) = match &self {
             EmbeddingError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             EmbeddingError::ProviderNotFound(_) => (StatusCode::BAD_REQUEST, self.to_string()),
@@ -96,9 +235,83 @@ impl IntoResponse for EmbeddingError {
             EmbeddingError::ConfigError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
             }
+            EmbeddingError::RateLimited { .. } | EmbeddingError::BudgetExceeded { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
+            }
+            EmbeddingError::CircuitOpen { .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, self.to_string())
+            }
+            EmbeddingError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
             _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
 
+        let body = Json(json!({ "error": message }));
+        match retry_after {
+            Some(secs) => (status, [(RETRY_AFTER, secs.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("Admin endpoints are disabled: ADMIN_API_KEY is not set")]
+    Disabled,
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Configuration reload rejected: {0}")]
+    ReloadRejected(String),
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AdminError::Disabled => StatusCode::FORBIDDEN,
+            AdminError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AdminError::ReloadRejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UsageError {
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Usage accounting requires [database] to be configured")]
+    NotConfigured,
+
+    #[error("Usage reports are disabled: ADMIN_API_KEY is not set")]
+    Disabled,
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+}
+
+impl From<sqlx::Error> for UsageError {
+    fn from(e: sqlx::Error) -> Self {
+        UsageError::Database(e.to_string())
+    }
+}
+
+impl IntoResponse for UsageError {
+    fn into_response(self) -> Response {
+        let (status, message) = match &self {
+            UsageError::NotConfigured => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            UsageError::Disabled => (StatusCode::FORBIDDEN, self.to_string()),
+            UsageError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            UsageError::Unauthorized(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            UsageError::Database(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            ),
+        };
         (status, Json(json!({ "error": message }))).into_response()
     }
 }
@@ -106,7 +319,13 @@ impl IntoResponse for EmbeddingError {
 #[derive(Debug, Error)]
 pub enum VectorStoreError {
     #[error("HTTP request failed: {0}")]
-    Http(#[from] reqwest::Error),
+    Http(reqwest::Error),
+
+    #[error("Qdrant request timed out: {0}")]
+    Timeout(String),
+
+    #[error("Invalid Qdrant configuration: {0}")]
+    Config(String),
 
     #[error("Qdrant API error: {status} - {message}")]
     Api { status: u16, message: String },
@@ -126,10 +345,26 @@ pub enum VectorStoreError {
     #[error("Internal dependency error: {0}")]
     InternalDependencyError(String),
 
+    #[error("Qdrant collection does not match configuration: {0}")]
+    CollectionMismatch(String),
+
+    #[error("Incompatible embedding provider: {0}")]
+    IncompatibleProvider(String),
+
     #[error("Embedding error: {0}")]
     Embedding(#[from] EmbeddingError),
 }
 
+impl From<reqwest::Error> for VectorStoreError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            VectorStoreError::Timeout(e.to_string())
+        } else {
+            VectorStoreError::Http(e)
+        }
+    }
+}
+
 impl IntoResponse for VectorStoreError {
     fn into_response(self) -> Response {
         match self {
@@ -139,9 +374,11 @@ impl IntoResponse for VectorStoreError {
                     VectorStoreError::NotConfigured => StatusCode::SERVICE_UNAVAILABLE,
                     VectorStoreError::BadRequest(_) => StatusCode::BAD_REQUEST,
                     VectorStoreError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
-                    VectorStoreError::InternalDependencyError(_) => {
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    }
+                    VectorStoreError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+                    VectorStoreError::InternalDependencyError(_)
+                    | VectorStoreError::Config(_)
+                    | VectorStoreError::CollectionMismatch(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                    VectorStoreError::IncompatibleProvider(_) => StatusCode::UNPROCESSABLE_ENTITY,
                     VectorStoreError::Api { status, .. } => {
                         StatusCode::from_u16(*status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
                     }
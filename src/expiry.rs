@@ -0,0 +1,93 @@
+//! Expiry of stored memories.
+//!
+//! `POST /memory` and `POST /api/memory` accept `ttl_seconds` or
+//! `expires_at`.  Both stores hide expired entries from searches as soon as
+//! they expire; the sweeper started here deletes them for good every
+//! `[server] expiry_sweep_interval_secs`.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use tracing::{info, warn};
+
+use crate::routes::AppState;
+
+/// The expiry requested by `ttl_seconds` or `expires_at`, if either is set.
+///
+/// Supplying both, a zero TTL or an `expires_at` that has already passed is
+/// an error, returned as a message for a 400 response.
+pub fn resolve_expiry(
+    ttl_seconds: Option<u64>,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    let now = Utc::now();
+    match (ttl_seconds, expires_at) {
+        (Some(_), Some(_)) => {
+            Err("Supply either 'ttl_seconds' or 'expires_at', not both".to_string())
+        }
+        (Some(0), None) => Err("'ttl_seconds' must be greater than 0".to_string()),
+        (Some(ttl), None) => i64::try_from(ttl)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|ttl| now.checked_add_signed(ttl))
+            .map(Some)
+            .ok_or_else(|| "'ttl_seconds' is too large".to_string()),
+        (None, Some(at)) if at <= now => Err("'expires_at' is in the past".to_string()),
+        (None, at) => Ok(at),
+    }
+}
+
+/// Periodically delete expired entries from the `/memory` store and, when
+/// configured, from Qdrant.  Failures are logged and retried on the next
+/// tick.
+pub fn spawn_expiry_sweeper(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match state.memory.purge_expired().await {
+                Ok(0) => {}
+                Ok(removed) => info!(removed, "Removed expired memory entries"),
+                Err(e) => warn!(error = %e, "Failed to remove expired memory entries"),
+            }
+            if let Some(store) = &state.vector_store {
+                if let Err(e) = store.purge_expired().await {
+                    warn!(error = %e, "Failed to delete expired Qdrant points");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ttl_and_expires_at_are_resolved() {
+        assert_eq!(resolve_expiry(None, None), Ok(None));
+
+        let before = Utc::now();
+        let at = resolve_expiry(Some(60), None).unwrap().unwrap();
+        assert!(at >= before + TimeDelta::seconds(60) && at <= Utc::now() + TimeDelta::seconds(60));
+
+        let later = Utc::now() + TimeDelta::hours(1);
+        assert_eq!(resolve_expiry(None, Some(later)), Ok(Some(later)));
+    }
+
+    #[test]
+    fn invalid_expiry_is_rejected() {
+        let past = Utc::now() - TimeDelta::seconds(1);
+        let future = Utc::now() + TimeDelta::hours(1);
+        for (ttl, at, message) in [
+            (Some(60), Some(future), "not both"),
+            (Some(0), None, "greater than 0"),
+            (Some(u64::MAX), None, "too large"),
+            (None, Some(past), "in the past"),
+        ] {
+            let error = resolve_expiry(ttl, at).unwrap_err();
+            assert!(error.contains(message), "{error}");
+        }
+    }
+}
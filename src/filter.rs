@@ -0,0 +1,391 @@
+//! Metadata filter expressions for `/memory/search`.
+//!
+//! A filter is a JSON object, either a condition on one metadata key or a
+//! combination of filters:
+//!
+//! ```json
+//! { "and": [
+//!     { "key": "kind", "eq": "note" },
+//!     { "key": "tag", "in": ["rust", "go"] },
+//!     { "key": "priority", "gte": 2, "lt": 5 },
+//!     { "key": "due", "lt": "2025-01-01" },
+//!     { "not": { "key": "archived", "exists": true } }
+//! ] }
+//! ```
+//!
+//! Metadata values are strings.  A numeric `eq` / `in` value or range bound
+//! compares the value parsed as a number; a string range bound is a date
+//! (RFC 3339 or `YYYY-MM-DD`) and compares the value parsed as a date.  A
+//! value that does not parse never matches.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// A parsed filter expression.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "Value")]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Field { key: String, condition: Condition },
+}
+
+/// A test applied to the value of one metadata key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Eq(Scalar),
+    In(Vec<Scalar>),
+    /// `true`: the key is present; `false`: it is absent.
+    Exists(bool),
+    Number(Range<f64>),
+    Date(Range<DateTime<Utc>>),
+}
+
+/// A value to compare a metadata value with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scalar {
+    Text(String),
+    Number(f64),
+}
+
+/// Bounds of a range condition; absent bounds are unbounded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range<T> {
+    pub gt: Option<T>,
+    pub gte: Option<T>,
+    pub lt: Option<T>,
+    pub lte: Option<T>,
+}
+
+const RANGE_OPS: [&str; 4] = ["gt", "gte", "lt", "lte"];
+
+impl Filter {
+    /// Parse a filter from its JSON text, e.g. a query parameter.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(text).map_err(|e| format!("invalid filter JSON: {e}"))?;
+        Self::try_from(value)
+    }
+
+    /// Whether an entry with `metadata` passes the filter.
+    pub fn matches(&self, metadata: &HashMap<String, String>) -> bool {
+        match self {
+            Filter::And(filters) => filters.iter().all(|f| f.matches(metadata)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(metadata)),
+            Filter::Not(filter) => !filter.matches(metadata),
+            Filter::Field { key, condition } => {
+                condition.matches(metadata.get(key).map(String::as_str))
+            }
+        }
+    }
+}
+
+impl Condition {
+    fn matches(&self, value: Option<&str>) -> bool {
+        match self {
+            Condition::Exists(exists) => value.is_some() == *exists,
+            Condition::Eq(scalar) => value.is_some_and(|v| scalar.matches(v)),
+            Condition::In(scalars) => value.is_some_and(|v| scalars.iter().any(|s| s.matches(v))),
+            Condition::Number(range) => value
+                .and_then(parse_number)
+                .is_some_and(|v| range.contains(&v)),
+            Condition::Date(range) => value
+                .and_then(parse_date)
+                .is_some_and(|v| range.contains(&v)),
+        }
+    }
+}
+
+impl Scalar {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Scalar::Text(text) => text == value,
+            Scalar::Number(number) => parse_number(value) == Some(*number),
+        }
+    }
+}
+
+impl<T: PartialOrd> Range<T> {
+    fn contains(&self, value: &T) -> bool {
+        self.gt.as_ref().is_none_or(|b| value > b)
+            && self.gte.as_ref().is_none_or(|b| value >= b)
+            && self.lt.as_ref().is_none_or(|b| value < b)
+            && self.lte.as_ref().is_none_or(|b| value <= b)
+    }
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// An RFC 3339 timestamp, or a `YYYY-MM-DD` date taken as midnight UTC.
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+            Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+        })
+}
+
+// ---------------------------------------------------------------------------
+// Parsing
+// ---------------------------------------------------------------------------
+
+impl TryFrom<Value> for Filter {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let Value::Object(mut object) = value else {
+            return Err("a filter must be a JSON object".to_string());
+        };
+        if object.contains_key("key") {
+            return parse_field(object);
+        }
+        if object.len() != 1 {
+            return Err(
+                "a filter needs a \"key\" or exactly one of \"and\", \"or\" and \"not\""
+                    .to_string(),
+            );
+        }
+        let (op, operand) = object
+            .iter_mut()
+            .next()
+            .map(|(k, v)| (k.clone(), v.take()))
+            .unwrap();
+        match op.as_str() {
+            "and" | "or" => {
+                let Value::Array(operands) = operand else {
+                    return Err(format!("\"{op}\" takes an array of filters"));
+                };
+                let filters = operands
+                    .into_iter()
+                    .map(Filter::try_from)
+                    .collect::<Result<_, _>>()?;
+                Ok(if op == "and" {
+                    Filter::And(filters)
+                } else {
+                    Filter::Or(filters)
+                })
+            }
+            "not" => Ok(Filter::Not(Box::new(Filter::try_from(operand)?))),
+            _ => Err(format!("unknown filter operator \"{op}\"")),
+        }
+    }
+}
+
+fn parse_field(mut object: Map<String, Value>) -> Result<Filter, String> {
+    let Some(Value::String(key)) = object.remove("key") else {
+        return Err("\"key\" must be a string".to_string());
+    };
+    let mut ops: Vec<(String, Value)> = object.into_iter().collect();
+    if let Some((op, _)) = ops.iter().find(|(op, _)| {
+        !matches!(op.as_str(), "eq" | "in" | "exists") && !RANGE_OPS.contains(&op.as_str())
+    }) {
+        return Err(format!("unknown condition \"{op}\" on key \"{key}\""));
+    }
+
+    let condition = if ops.iter().all(|(op, _)| RANGE_OPS.contains(&op.as_str())) && !ops.is_empty()
+    {
+        parse_range(&key, ops)?
+    } else if ops.len() == 1 {
+        let (op, operand) = ops.pop().unwrap();
+        match op.as_str() {
+            "eq" => Condition::Eq(parse_scalar(&key, operand)?),
+            "in" => {
+                let Value::Array(values) = operand else {
+                    return Err(format!("\"in\" on key \"{key}\" takes an array"));
+                };
+                Condition::In(
+                    values
+                        .into_iter()
+                        .map(|v| parse_scalar(&key, v))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            _ => {
+                let Value::Bool(exists) = operand else {
+                    return Err(format!("\"exists\" on key \"{key}\" takes true or false"));
+                };
+                Condition::Exists(exists)
+            }
+        }
+    } else {
+        return Err(format!(
+            "key \"{key}\" needs one of \"eq\", \"in\" and \"exists\", or range bounds \"gt\", \"gte\", \"lt\", \"lte\""
+        ));
+    };
+    Ok(Filter::Field { key, condition })
+}
+
+fn parse_scalar(key: &str, value: Value) -> Result<Scalar, String> {
+    match value {
+        Value::String(text) => Ok(Scalar::Text(text)),
+        Value::Number(number) => number
+            .as_f64()
+            .map(Scalar::Number)
+            .ok_or_else(|| format!("number on key \"{key}\" is out of range")),
+        Value::Bool(b) => Ok(Scalar::Text(b.to_string())),
+        _ => Err(format!(
+            "values compared with key \"{key}\" must be strings, numbers or booleans"
+        )),
+    }
+}
+
+/// A numeric range when every bound is a number, a date range when every
+/// bound is a date string.
+fn parse_range(key: &str, bounds: Vec<(String, Value)>) -> Result<Condition, String> {
+    if bounds.iter().all(|(_, v)| v.is_number()) {
+        let mut range = Range {
+            gt: None,
+            gte: None,
+            lt: None,
+            lte: None,
+        };
+        for (op, value) in bounds {
+            *range.bound(&op) = value.as_f64();
+        }
+        return Ok(Condition::Number(range));
+    }
+    let mut range = Range {
+        gt: None,
+        gte: None,
+        lt: None,
+        lte: None,
+    };
+    for (op, value) in bounds {
+        let date = value
+            .as_str()
+            .and_then(parse_date)
+            .ok_or_else(|| format!("bounds on key \"{key}\" must all be numbers or all be dates (RFC 3339 or YYYY-MM-DD)"))?;
+        *range.bound(&op) = Some(date);
+    }
+    Ok(Condition::Date(range))
+}
+
+impl<T> Range<T> {
+    fn bound(&mut self, op: &str) -> &mut Option<T> {
+        match op {
+            "gt" => &mut self.gt,
+            "gte" => &mut self.gte,
+            "lt" => &mut self.lt,
+            _ => &mut self.lte,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn matches(filter: &str, pairs: &[(&str, &str)]) -> bool {
+        Filter::parse(filter).unwrap().matches(&metadata(pairs))
+    }
+
+    #[test]
+    fn equality_in_and_exists() {
+        assert!(matches(
+            r#"{"key": "kind", "eq": "note"}"#,
+            &[("kind", "note")]
+        ));
+        assert!(!matches(
+            r#"{"key": "kind", "eq": "note"}"#,
+            &[("kind", "task")]
+        ));
+        assert!(!matches(r#"{"key": "kind", "eq": "note"}"#, &[]));
+        assert!(matches(r#"{"key": "n", "eq": 2}"#, &[("n", "2.0")]));
+        assert!(matches(
+            r#"{"key": "done", "eq": true}"#,
+            &[("done", "true")]
+        ));
+
+        assert!(matches(
+            r#"{"key": "tag", "in": ["a", "b"]}"#,
+            &[("tag", "b")]
+        ));
+        assert!(!matches(
+            r#"{"key": "tag", "in": ["a", "b"]}"#,
+            &[("tag", "c")]
+        ));
+
+        assert!(matches(r#"{"key": "tag", "exists": true}"#, &[("tag", "")]));
+        assert!(!matches(r#"{"key": "tag", "exists": true}"#, &[]));
+        assert!(matches(r#"{"key": "tag", "exists": false}"#, &[]));
+    }
+
+    #[test]
+    fn numeric_and_date_ranges() {
+        let priority = r#"{"key": "priority", "gte": 2, "lt": 5}"#;
+        assert!(matches(priority, &[("priority", "2")]));
+        assert!(matches(priority, &[("priority", "4.5")]));
+        assert!(!matches(priority, &[("priority", "5")]));
+        assert!(!matches(priority, &[("priority", "high")]));
+        assert!(!matches(priority, &[]));
+
+        let due = r#"{"key": "due", "gt": "2024-06-01", "lte": "2024-07-01T12:00:00+02:00"}"#;
+        assert!(matches(due, &[("due", "2024-06-01T00:00:01Z")]));
+        assert!(matches(due, &[("due", "2024-07-01")]));
+        assert!(matches(due, &[("due", "2024-07-01T10:00:00Z")]));
+        assert!(!matches(due, &[("due", "2024-07-01T10:00:01Z")]));
+        assert!(!matches(due, &[("due", "2024-06-01")]));
+        assert!(!matches(due, &[("due", "soon")]));
+    }
+
+    #[test]
+    fn combinations() {
+        let filter = r#"{"and": [
+            {"or": [{"key": "kind", "eq": "note"}, {"key": "kind", "eq": "task"}]},
+            {"not": {"key": "archived", "exists": true}}
+        ]}"#;
+        assert!(matches(filter, &[("kind", "task")]));
+        assert!(!matches(filter, &[("kind", "task"), ("archived", "yes")]));
+        assert!(!matches(filter, &[("kind", "event")]));
+        assert!(matches(r#"{"and": []}"#, &[]));
+        assert!(!matches(r#"{"or": []}"#, &[]));
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        for (filter, message) in [
+            ("nope", "invalid filter JSON"),
+            ("[]", "must be a JSON object"),
+            (r#"{"xor": []}"#, "unknown filter operator"),
+            (r#"{"and": [], "or": []}"#, "exactly one of"),
+            (r#"{"and": {}}"#, "takes an array"),
+            (r#"{"key": 1, "eq": "a"}"#, "must be a string"),
+            (r#"{"key": "a", "like": "b"}"#, "unknown condition"),
+            (r#"{"key": "a"}"#, "needs one of"),
+            (r#"{"key": "a", "eq": "b", "gt": 1}"#, "needs one of"),
+            (r#"{"key": "a", "in": "b"}"#, "takes an array"),
+            (
+                r#"{"key": "a", "eq": null}"#,
+                "must be strings, numbers or booleans",
+            ),
+            (r#"{"key": "a", "exists": "yes"}"#, "takes true or false"),
+            (
+                r#"{"key": "a", "gt": 1, "lt": "2024-01-01"}"#,
+                "all be numbers or all be dates",
+            ),
+            (
+                r#"{"key": "a", "gt": "tomorrow"}"#,
+                "all be numbers or all be dates",
+            ),
+        ] {
+            let error = Filter::parse(filter).unwrap_err();
+            assert!(error.contains(message), "{filter}: {error}");
+        }
+    }
+}
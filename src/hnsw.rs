@@ -0,0 +1,456 @@
+//! Hierarchical navigable small world (HNSW) index for approximate
+//! nearest-neighbour search by cosine similarity.
+//!
+//! Vectors are normalised on insert, so similarity is a dot product.  Each
+//! dimensionality gets its own graph, since vectors of different sizes are
+//! never comparable.  Zero vectors have no cosine similarity and are not
+//! indexed.
+//!
+//! Removal marks a node as deleted: it still routes searches but is never
+//! returned.  Once deleted nodes outnumber live ones the graph is rebuilt
+//! from the live vectors.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::config::HnswConfig;
+
+/// Deleted nodes tolerated before a rebuild is considered at all.
+const MIN_DELETED_FOR_REBUILD: usize = 64;
+
+/// Indexes of every dimensionality, keyed by an entry ID.
+pub struct VectorIndex {
+    config: HnswConfig,
+    graphs: HashMap<usize, Graph>,
+}
+
+impl VectorIndex {
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            graphs: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &HnswConfig {
+        &self.config
+    }
+
+    /// Index `vector` under `id`, replacing any vector already indexed for it.
+    pub fn insert(&mut self, id: &str, vector: &[f32]) {
+        self.remove(id);
+        let Some(vector) = normalized(vector) else {
+            return;
+        };
+        let config = &self.config;
+        self.graphs
+            .entry(vector.len())
+            .or_insert_with(|| Graph::new(config))
+            .insert(id.to_string(), vector);
+    }
+
+    /// Remove `id` from the index. Returns `true` if it was indexed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        self.graphs.values_mut().any(|graph| graph.remove(id))
+    }
+
+    /// Number of live vectors of `dimensions` components.
+    pub fn len(&self, dimensions: usize) -> usize {
+        self.graphs.get(&dimensions).map_or(0, |g| g.live)
+    }
+
+    /// Up to `ef` accepted entries most similar to `query`, with their cosine
+    /// similarity, most similar first.
+    pub fn search<'a>(
+        &'a self,
+        query: &[f32],
+        ef: usize,
+        accept: impl Fn(&str) -> bool,
+    ) -> Vec<(&'a str, f32)> {
+        let Some(query) = normalized(query) else {
+            return Vec::new();
+        };
+        let Some(graph) = self.graphs.get(&query.len()) else {
+            return Vec::new();
+        };
+        graph
+            .search(&query, ef.max(1), &accept)
+            .into_iter()
+            .map(|(distance, node)| (graph.nodes[node as usize].id.as_str(), 1.0 - distance.0))
+            .collect()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Graph
+// ---------------------------------------------------------------------------
+
+/// `1 − cosine similarity`, ordered totally so it can key a heap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Distance(f32);
+
+impl Eq for Distance {}
+
+impl PartialOrd for Distance {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Distance {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+struct Node {
+    id: String,
+    vector: Vec<f32>,
+    /// Neighbours on each layer from 0 up to the node's level.
+    links: Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+/// One HNSW graph over vectors of a single dimensionality.
+struct Graph {
+    m: usize,
+    ef_construction: usize,
+    level_factor: f64,
+    rng: StdRng,
+    nodes: Vec<Node>,
+    ids: HashMap<String, u32>,
+    entry_point: Option<u32>,
+    live: usize,
+}
+
+impl Graph {
+    fn new(config: &HnswConfig) -> Self {
+        Self {
+            m: config.m,
+            ef_construction: config.ef_construction,
+            level_factor: 1.0 / (config.m as f64).ln(),
+            // Seeded so the graph, and so search results, are reproducible.
+            rng: StdRng::seed_from_u64(0x4e53_5748),
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry_point: None,
+            live: 0,
+        }
+    }
+
+    /// Maximum links per node on `layer`; the bottom layer is denser.
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.m * 2
+        } else {
+            self.m
+        }
+    }
+
+    fn distance(&self, query: &[f32], node: u32) -> Distance {
+        let vector = &self.nodes[node as usize].vector;
+        Distance(1.0 - query.iter().zip(vector).map(|(a, b)| a * b).sum::<f32>())
+    }
+
+    fn top_level(&self) -> usize {
+        self.entry_point
+            .map_or(0, |ep| self.nodes[ep as usize].links.len() - 1)
+    }
+
+    fn insert(&mut self, id: String, vector: Vec<f32>) {
+        let level =
+            (-self.rng.gen::<f64>().max(f64::MIN_POSITIVE).ln() * self.level_factor) as usize;
+        let node = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            id: id.clone(),
+            vector,
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.ids.insert(id, node);
+        self.live += 1;
+
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(node);
+            return;
+        };
+        let top_level = self.top_level();
+        let query = self.nodes[node as usize].vector.clone();
+
+        // Descend greedily to the node's level, then link it on every layer
+        // it shares with the graph.
+        let mut entry = vec![(self.distance(&query, entry_point), entry_point)];
+        for layer in (level + 1..=top_level).rev() {
+            entry = self.search_layer(&query, &entry, 1, layer, &|_| true);
+        }
+        for layer in (0..=level.min(top_level)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry, self.ef_construction, layer, &|_| true);
+            let neighbours = self.select_neighbours(&candidates, self.m);
+            for &neighbour in &neighbours {
+                self.link(neighbour, node, layer);
+            }
+            self.nodes[node as usize].links[layer] = neighbours;
+            entry = candidates;
+        }
+
+        if level > top_level {
+            self.entry_point = Some(node);
+        }
+    }
+
+    /// Add a link from `from` to `to` on `layer`, pruning `from`'s links
+    /// back to the limit when they overflow.
+    fn link(&mut self, from: u32, to: u32, layer: usize) {
+        let max_links = self.max_links(layer);
+        let links = &mut self.nodes[from as usize].links[layer];
+        links.push(to);
+        if links.len() <= max_links {
+            return;
+        }
+        let base = self.nodes[from as usize].vector.clone();
+        let mut candidates: Vec<(Distance, u32)> = self.nodes[from as usize].links[layer]
+            .iter()
+            .map(|&n| (self.distance(&base, n), n))
+            .collect();
+        candidates.sort_unstable();
+        self.nodes[from as usize].links[layer] = self.select_neighbours(&candidates, max_links);
+    }
+
+    /// Pick up to `m` of `candidates` (sorted nearest first), preferring ones
+    /// closer to the base than to any neighbour already picked so the links
+    /// spread in different directions, then topping up with the nearest of
+    /// the rest.
+    fn select_neighbours(&self, candidates: &[(Distance, u32)], m: usize) -> Vec<u32> {
+        let mut selected: Vec<u32> = Vec::with_capacity(m);
+        let mut skipped = Vec::new();
+        for &(distance, candidate) in candidates {
+            if selected.len() == m {
+                break;
+            }
+            let vector = &self.nodes[candidate as usize].vector;
+            if selected
+                .iter()
+                .all(|&s| self.distance(vector, s) > distance)
+            {
+                selected.push(candidate);
+            } else {
+                skipped.push(candidate);
+            }
+        }
+        let missing = m.saturating_sub(selected.len());
+        selected.extend(skipped.into_iter().take(missing));
+        selected
+    }
+
+    /// Best-first search of one layer from `entry`, returning up to `ef`
+    /// accepted nodes, nearest first.  Rejected nodes are still traversed.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry: &[(Distance, u32)],
+        ef: usize,
+        layer: usize,
+        accept: &dyn Fn(u32) -> bool,
+    ) -> Vec<(Distance, u32)> {
+        let mut visited: HashSet<u32> = entry.iter().map(|&(_, n)| n).collect();
+        let mut candidates: BinaryHeap<Reverse<(Distance, u32)>> =
+            entry.iter().copied().map(Reverse).collect();
+        let mut results: BinaryHeap<(Distance, u32)> =
+            entry.iter().copied().filter(|&(_, n)| accept(n)).collect();
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(Reverse((distance, current))) = candidates.pop() {
+            if results.len() >= ef && results.peek().is_some_and(|&(worst, _)| distance > worst) {
+                break;
+            }
+            let Some(links) = self.nodes[current as usize].links.get(layer) else {
+                continue;
+            };
+            for &neighbour in links {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let distance = self.distance(query, neighbour);
+                if results.len() < ef || results.peek().is_some_and(|&(worst, _)| distance < worst)
+                {
+                    candidates.push(Reverse((distance, neighbour)));
+                    if accept(neighbour) {
+                        results.push((distance, neighbour));
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+        results.into_sorted_vec()
+    }
+
+    fn search(
+        &self,
+        query: &[f32],
+        ef: usize,
+        accept: &dyn Fn(&str) -> bool,
+    ) -> Vec<(Distance, u32)> {
+        let Some(entry_point) = self.entry_point else {
+            return Vec::new();
+        };
+        let mut entry = vec![(self.distance(query, entry_point), entry_point)];
+        for layer in (1..=self.top_level()).rev() {
+            entry = self.search_layer(query, &entry, 1, layer, &|_| true);
+        }
+        let accept = |node: u32| {
+            let node = &self.nodes[node as usize];
+            !node.deleted && accept(&node.id)
+        };
+        self.search_layer(query, &entry, ef, 0, &accept)
+    }
+
+    fn remove(&mut self, id: &str) -> bool {
+        let Some(node) = self.ids.remove(id) else {
+            return false;
+        };
+        self.nodes[node as usize].deleted = true;
+        self.live -= 1;
+
+        let deleted = self.nodes.len() - self.live;
+        if deleted >= MIN_DELETED_FOR_REBUILD && deleted > self.live {
+            self.rebuild();
+        }
+        true
+    }
+
+    /// Rebuild the graph from its live nodes, dropping the deleted ones.
+    fn rebuild(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.ids.clear();
+        self.entry_point = None;
+        self.live = 0;
+        for node in nodes.into_iter().filter(|n| !n.deleted) {
+            self.insert(node.id, node.vector);
+        }
+    }
+}
+
+/// `vector` scaled to unit length, or `None` for a zero vector.
+fn normalized(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    (norm > 0.0 && norm.is_finite()).then(|| vector.iter().map(|v| v / norm).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HnswConfig {
+        HnswConfig::default()
+    }
+
+    fn random_vectors(rng: &mut StdRng, count: usize, dimensions: usize) -> Vec<Vec<f32>> {
+        (0..count)
+            .map(|_| (0..dimensions).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    fn brute_force(vectors: &[Vec<f32>], query: &[f32], k: usize) -> Vec<String> {
+        let query = normalized(query).unwrap();
+        let mut scored: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let v = normalized(v).unwrap();
+                (query.iter().zip(&v).map(|(a, b)| a * b).sum(), i)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(k)
+            .map(|(_, i)| i.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn recall_against_brute_force() {
+        const K: usize = 10;
+        let mut rng = StdRng::seed_from_u64(7);
+        let vectors = random_vectors(&mut rng, 1_000, 32);
+        let queries = random_vectors(&mut rng, 50, 32);
+
+        let mut index = VectorIndex::new(HnswConfig {
+            ef_construction: 100,
+            ..config()
+        });
+        for (i, v) in vectors.iter().enumerate() {
+            index.insert(&i.to_string(), v);
+        }
+
+        let mut found = 0;
+        for query in &queries {
+            let exact = brute_force(&vectors, query, K);
+            let approx: HashSet<&str> = index
+                .search(query, config().ef_search, |_| true)
+                .into_iter()
+                .take(K)
+                .map(|(id, _)| id)
+                .collect();
+            found += exact
+                .iter()
+                .filter(|id| approx.contains(id.as_str()))
+                .count();
+        }
+        let recall = found as f64 / (queries.len() * K) as f64;
+        assert!(recall >= 0.95, "recall@{K} was {recall:.3}");
+    }
+
+    #[test]
+    fn removed_and_rejected_entries_are_not_returned() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let vectors = random_vectors(&mut rng, 500, 8);
+        let mut index = VectorIndex::new(config());
+        for (i, v) in vectors.iter().enumerate() {
+            index.insert(&i.to_string(), v);
+        }
+
+        // Removing most entries triggers a rebuild from the live ones.
+        for i in 0..400 {
+            assert!(index.remove(&i.to_string()));
+        }
+        assert!(!index.remove("0"));
+        assert_eq!(index.len(8), 100);
+        assert!(
+            index.graphs[&8].nodes.len() < 200,
+            "deleted nodes were not dropped"
+        );
+
+        let even = |id: &str| id.parse::<usize>().unwrap() % 2 == 0;
+        let hits = index.search(&vectors[450], 20, even);
+        assert_eq!(hits.len(), 20);
+        assert_eq!(hits[0].0, "450");
+        assert!((hits[0].1 - 1.0).abs() < 1e-5);
+        assert!(hits
+            .iter()
+            .all(|&(id, _)| even(id) && id.parse::<usize>().unwrap() >= 400));
+        assert!(hits.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn dimensions_and_zero_vectors_are_kept_apart() {
+        let mut index = VectorIndex::new(config());
+        index.insert("a", &[1.0, 0.0]);
+        index.insert("b", &[1.0, 0.0, 0.0]);
+        index.insert("zero", &[0.0, 0.0]);
+        assert_eq!((index.len(2), index.len(3)), (1, 1));
+
+        let hits = index.search(&[1.0, 0.0], 10, |_| true);
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), vec!["a"]);
+        assert!(index.search(&[0.0, 0.0], 10, |_| true).is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! Construction of the HTTP clients used for upstream calls.
+//!
+//! Every embedding provider and the Qdrant store build their `reqwest`
+//! client from an [`HttpClientConfig`], so timeouts, connection pooling, the
+//! proxy, extra headers and trusted CAs are configured the same way for all of
+//! them.
+
+use std::{fs, time::Duration};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Certificate, Client, Proxy,
+};
+
+use crate::config::HttpClientConfig;
+
+/// Connect timeout used when `connect_timeout_ms` is not configured.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Request timeout used when `request_timeout_ms` is not configured.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Build a client from `cfg`.
+///
+/// Fails with a description of the offending setting when the proxy URL, a
+/// header or a CA file is invalid.
+pub fn build_client(cfg: &HttpClientConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .connect_timeout(
+            cfg.connect_timeout_ms
+                .map_or(DEFAULT_CONNECT_TIMEOUT, Duration::from_millis),
+        )
+        .timeout(
+            cfg.request_timeout_ms
+                .map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_millis),
+        );
+
+    if let Some(max_idle) = cfg.pool_max_idle {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+
+    if let Some(proxy) = &cfg.proxy {
+        let proxy = Proxy::all(proxy).map_err(|e| format!("invalid proxy '{proxy}': {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    if !cfg.headers.is_empty() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &cfg.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name '{name}'"))?;
+            let mut header_value = HeaderValue::from_str(value.expose())
+                .map_err(|_| format!("invalid value for header '{name}'"))?;
+            header_value.set_sensitive(true);
+            headers.insert(header_name, header_value);
+        }
+        builder = builder.default_headers(headers);
+    }
+
+    for path in &cfg.ca_certs {
+        let pem = fs::read(path).map_err(|e| format!("cannot read CA file '{path}': {e}"))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("invalid CA file '{path}': {e}"))?;
+        if certs.is_empty() {
+            return Err(format!("CA file '{path}' contains no PEM certificates"));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("failed to build HTTP client: {e}"))
+}
+
+/// Start a server that answers every request with response headers and the
+/// start of a JSON body, then stalls without finishing it.  Returns its base
+/// URL.
+#[cfg(test)]
+pub(crate) async fn stalled_body_server() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = [0u8; 8192];
+                let _ = socket.read(&mut request).await;
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                          content-length: 1024\r\n\r\n{\"result\": [",
+                    )
+                    .await;
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn headers_are_sent_and_proxy_is_used() {
+        let proxy = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/status"))
+            .and(header("x-tenant", "agents"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&proxy)
+            .await;
+
+        let client = build_client(&HttpClientConfig {
+            proxy: Some(proxy.uri()),
+            headers: HashMap::from([("X-Tenant".to_string(), "agents".into())]),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // The host does not resolve; the request only succeeds via the proxy.
+        let response = client
+            .get("http://upstream.invalid/status")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn request_timeout_is_reported_as_timeout() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .mount(&server)
+            .await;
+
+        let client = build_client(&HttpClientConfig {
+            request_timeout_ms: Some(50),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let err = client.get(server.uri()).send().await.unwrap_err();
+        assert!(err.is_timeout(), "{err}");
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let invalid = [
+            HttpClientConfig {
+                proxy: Some("not a url".to_string()),
+                ..Default::default()
+            },
+            HttpClientConfig {
+                headers: HashMap::from([("bad header".to_string(), "x".into())]),
+                ..Default::default()
+            },
+            HttpClientConfig {
+                ca_certs: vec!["/nonexistent/ca.pem".to_string()],
+                ..Default::default()
+            },
+        ];
+        for cfg in invalid {
+            assert!(build_client(&cfg).is_err(), "{cfg:?}");
+        }
+
+        let path = std::env::temp_dir().join(format!("ca-{}.pem", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        let err = build_client(&HttpClientConfig {
+            ca_certs: vec![path.to_string_lossy().into_owned()],
+            ..Default::default()
+        })
+        .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.contains("no PEM certificates"), "{err}");
+    }
+}
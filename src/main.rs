@@ -1,26 +1,50 @@
+mod chunking;
 mod config;
 mod embedding;
 mod error;
+mod expiry;
+mod filter;
+mod hnsw;
+mod http_client;
 mod memory;
+mod metrics;
+mod reload;
 mod routes;
 mod session_store;
+mod sparse;
+mod usage;
 mod vector_store;
 
-use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
-use axum::{routing::{delete, get, post}, Router};
-use tracing::info;
+use axum::{
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use crate::{
+    chunking::Chunker,
     config::Config,
-    embedding::ProviderRegistry,
+    embedding::{cache::EmbeddingCache, ProviderRegistry},
+    expiry::spawn_expiry_sweeper,
     memory::MemoryStore,
+    metrics::track_http,
+    reload::{spawn_config_watcher, Reloader, SharedRegistry},
     routes::{
-        create_session, delete_memory, embed, get_session, health, list_sessions,
-        search_memory, search_memory_qdrant, store_memory, store_memory_qdrant, AppState,
+        admin_reload, create_session, delete_memory, embed, embed_batch, get_session, get_usage,
+        health, list_sessions, metrics_handler, search_memory, search_memory_post,
+        search_memory_qdrant, store_memory, store_memory_qdrant, AppState,
     },
     session_store::SessionStore,
+    sparse::build_encoder,
+    usage::{track_usage, UsageStore},
     vector_store::QdrantStore,
 };
 
@@ -37,7 +61,65 @@ async fn main() {
         .unwrap_or_else(|e| panic!("Failed to load configuration from '{config_path}': {e}"));
     info!("Loaded configuration from '{config_path}'");
 
-    let registry = ProviderRegistry::from_config(&config.embedding)
+    // Initialise the SQLite session store if configured.
+    let session_store = if let Some(db_cfg) = &config.database {
+        let store = Arc::new(
+            SessionStore::new(&db_cfg.url)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to initialise session store: {e}")),
+        );
+        info!(url = %db_cfg.url, "Session store ready");
+        Some(store)
+    } else {
+        info!("Database not configured – /api/sessions and /api/usage endpoints are disabled");
+        None
+    };
+
+    // The /memory store is persisted in the session store's database when
+    // [memory] persistent is set.
+    let memory_config = config.memory.clone().unwrap_or_default();
+    let memory = match &session_store {
+        Some(store) if memory_config.persistent => {
+            let memory = MemoryStore::load(store.pool().clone(), &memory_config)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to load the memory store: {e}"));
+            info!(
+                entries = memory.len(),
+                "Memory store loaded from the database"
+            );
+            memory
+        }
+        None if memory_config.persistent => {
+            warn!("memory.persistent is set but [database] is not configured; /memory entries are kept in memory only");
+            MemoryStore::new(&memory_config)
+        }
+        _ => MemoryStore::new(&memory_config),
+    };
+
+    // Embedding usage is accounted in the session store's database.
+    let usage_store = session_store
+        .as_ref()
+        .map(|s| Arc::new(UsageStore::new(s.pool().clone())));
+
+    // The embedding cache shares the session store's SQLite pool for its
+    // persistent tier.
+    let embedding_cache = config.embedding.cache.as_ref().map(|cache_cfg| {
+        if cache_cfg.persistent && session_store.is_none() {
+            warn!("embedding.cache.persistent is set but [database] is not configured; using the in-process tier only");
+        }
+        let pool = session_store.as_ref().map(|s| s.pool().clone());
+        info!(
+            max_entries = cache_cfg.max_entries,
+            ttl_seconds = ?cache_cfg.ttl_seconds,
+            persistent = cache_cfg.persistent && pool.is_some(),
+            "Embedding cache enabled"
+        );
+        let cache = Arc::new(EmbeddingCache::new(cache_cfg, pool));
+        cache.spawn_purge();
+        cache
+    });
+
+    let mut registry = ProviderRegistry::from_config(&config.embedding, embedding_cache.clone())
         .expect("Failed to initialise embedding provider registry");
 
     info!(
@@ -48,7 +130,26 @@ async fn main() {
 
     // Initialise the Qdrant vector store if configured.
     let vector_store = if let Some(qdrant_cfg) = &config.qdrant {
-        let store = Arc::new(QdrantStore::new(qdrant_cfg));
+        let mut store = QdrantStore::new(qdrant_cfg)
+            .unwrap_or_else(|e| panic!("Failed to create Qdrant client: {e}"));
+        // The BM25 encoder keeps its corpus statistics in the session store's
+        // SQLite database when there is one.
+        if let Some(sparse_cfg) = &qdrant_cfg.sparse {
+            if sparse_cfg.encoder_type == "bm25" && session_store.is_none() {
+                warn!("[database] is not configured; BM25 statistics are kept in memory and reset on restart");
+            }
+            let pool = session_store.as_ref().map(|s| s.pool().clone());
+            let encoder = build_encoder(sparse_cfg, pool)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to initialise sparse encoder: {e}"));
+            info!(
+                encoder = %sparse_cfg.encoder_type,
+                vector = %sparse_cfg.vector_name,
+                "Sparse vectors enabled"
+            );
+            store = store.with_sparse_encoder(&sparse_cfg.vector_name, encoder);
+        }
+        let store = Arc::new(store);
         store
             .ensure_collection()
             .await
@@ -59,26 +160,18 @@ async fn main() {
             dimensions = qdrant_cfg.dimensions,
             "Qdrant vector store ready"
         );
+        // Probe every provider so a dimension mismatch fails here rather than
+        // as an opaque Qdrant 400 on the first upsert.
+        registry
+            .check_vector_dimensions(qdrant_cfg.dimensions as usize)
+            .await
+            .unwrap_or_else(|e| panic!("Embedding provider is incompatible with Qdrant: {e}"));
         Some(store)
     } else {
         info!("Qdrant not configured – /api/memory and /api/search are disabled");
         None
     };
 
-    // Initialise the SQLite session store if configured.
-    let session_store = if let Some(db_cfg) = &config.database {
-        let store = Arc::new(
-            SessionStore::new(&db_cfg.url)
-                .await
-                .unwrap_or_else(|e| panic!("Failed to initialise session store: {e}")),
-        );
-        info!(url = %db_cfg.url, "Session store ready");
-        Some(store)
-    } else {
-        info!("Database not configured – /api/sessions endpoints are disabled");
-        None
-    };
-
     let session_api_key = std::env::var("SESSION_API_KEY")
         .ok()
         .filter(|k| !k.is_empty());
@@ -88,24 +181,69 @@ async fn main() {
         info!("SESSION_API_KEY not set – /api/sessions endpoints are unauthenticated");
     }
 
+    let admin_api_key = std::env::var("ADMIN_API_KEY")
+        .ok()
+        .filter(|k| !k.is_empty());
+    if admin_api_key.is_none() {
+        info!("ADMIN_API_KEY not set – /admin endpoints and /api/usage are disabled");
+    }
+
+    // Reload the embedding providers on SIGHUP, on config file changes and via
+    // POST /admin/reload.
+    let registry = Arc::new(SharedRegistry::new(registry));
+    let reloader = Arc::new(Reloader::new(
+        &config_path,
+        registry.clone(),
+        embedding_cache,
+        config.qdrant.as_ref().map(|q| q.dimensions as usize),
+    ));
+    #[cfg(unix)]
+    reload::spawn_sighup_listener(reloader.clone());
+    if config.server.config_poll_interval_ms > 0 {
+        spawn_config_watcher(
+            reloader.clone(),
+            Duration::from_millis(config.server.config_poll_interval_ms),
+        );
+    }
+
     let state = Arc::new(AppState {
         registry,
+        reloader,
+        admin_api_key,
         vector_store,
-        memory: MemoryStore::new(),
+        memory,
         session_store,
         session_api_key,
+        chunker: config.chunking.as_ref().map(Chunker::new),
+        usage_store: usage_store.clone(),
     });
 
+    if config.server.expiry_sweep_interval_secs > 0 {
+        spawn_expiry_sweeper(
+            state.clone(),
+            Duration::from_secs(config.server.expiry_sweep_interval_secs),
+        );
+    }
+
     let app = Router::new()
         .route("/health", get(health))
+        .route("/metrics", get(metrics_handler))
         .route("/api/embed", post(embed))
+        .route("/api/embed/batch", post(embed_batch))
         .route("/api/memory", post(store_memory_qdrant))
         .route("/api/search", post(search_memory_qdrant))
         .route("/memory", post(store_memory))
-        .route("/memory/search", get(search_memory))
+        .route(
+            "/memory/search",
+            get(search_memory).post(search_memory_post),
+        )
         .route("/memory/{id}", delete(delete_memory))
         .route("/api/sessions", get(list_sessions).post(create_session))
         .route("/api/sessions/{id}", get(get_session))
+        .route("/api/usage", get(get_usage))
+        .route("/admin/reload", post(admin_reload))
+        .route_layer(middleware::from_fn_with_state(usage_store, track_usage))
+        .route_layer(middleware::from_fn(track_http))
         .with_state(state);
 
     let host: IpAddr = config.server.host.parse().expect("Invalid server host address");
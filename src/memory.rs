@@ -1,11 +1,41 @@
+//! In-process vector store behind the `/memory` endpoints.
+//!
+//! Entries live in a `HashMap`.  Small stores are searched by brute-force
+//! cosine similarity; larger ones through an HNSW index (see [`crate::hnsw`])
+//! kept up to date on every store and delete.
+//! When `[memory] persistent` is set they are also written to the SQLite
+//! `memory_entries` table before the map is updated, and loaded back from it
+//! on startup, so the store survives restarts.
+//!
+//! Entries may carry an expiry time.  Expired entries are never returned by
+//! [`MemoryStore::search`] and are removed by [`MemoryStore::purge_expired`],
+//! which the expiry sweeper calls periodically.
+//!
+//! With `[memory.capacity]` limits, storing entries that take the store (or,
+//! per session, the entry's session) over a limit evicts others according to
+//! the configured [`EvictionPolicy`].  Searches record a hit and access time
+//! on every entry they return for the LRU and LFU policies; these statistics
+//! are kept in memory only and start afresh on restart.
+
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering as AtomicOrdering};
 use std::sync::RwLock;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::chunking::PARENT_ID_KEY;
+use crate::config::{CapacityConfig, EvictionPolicy, MemoryConfig};
+use crate::embedding::cache::{decode_embedding, encode_embedding};
 use crate::embedding::Embedding;
+use crate::error::EmbeddingError;
+use crate::filter::Filter;
+use crate::hnsw::VectorIndex;
+use crate::metrics::metrics;
 
 /// A single memory entry stored in the vector store.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -16,8 +46,96 @@ pub struct MemoryEntry {
     pub metadata: HashMap<String, String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session: Option<String>,
+    /// Not part of the serialised form; persisted as a little-endian `f32`
+    /// blob in its own column.
     #[serde(skip)]
     pub embedding: Embedding,
+    /// When the entry stops being searchable; `None` keeps it indefinitely.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the entry was stored.
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip)]
+    access: Access,
+}
+
+impl MemoryEntry {
+    /// A new entry with a generated ID.
+    pub fn new(
+        text: String,
+        metadata: HashMap<String, String>,
+        session: Option<String>,
+        embedding: Embedding,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            text,
+            metadata,
+            session,
+            embedding,
+            expires_at,
+            created_at: Utc::now(),
+            access: Access::default(),
+        }
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    /// When a search last returned the entry, or when it was stored if none
+    /// has, in Unix milliseconds.
+    fn last_access_ms(&self) -> i64 {
+        self.access
+            .last_ms
+            .load(AtomicOrdering::Relaxed)
+            .max(self.created_at.timestamp_millis())
+    }
+
+    fn hits(&self) -> u64 {
+        self.access.hits.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Approximate heap footprint, counted against `max_bytes`.
+    fn size_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.id.len()
+            + self.text.len()
+            + self.session.as_ref().map_or(0, String::len)
+            + self
+                .metadata
+                .iter()
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>()
+            + std::mem::size_of_val(self.embedding.as_slice())
+    }
+}
+
+/// How often, and when last, searches returned an entry.  Atomic so that
+/// searches can record hits under the store's read lock.
+#[derive(Debug, Default)]
+struct Access {
+    hits: AtomicU64,
+    /// Unix milliseconds of the last hit; 0 before the first.
+    last_ms: AtomicI64,
+}
+
+impl Access {
+    fn record(&self, now_ms: i64) {
+        self.hits.fetch_add(1, AtomicOrdering::Relaxed);
+        self.last_ms.fetch_max(now_ms, AtomicOrdering::Relaxed);
+    }
+}
+
+impl Clone for Access {
+    fn clone(&self) -> Self {
+        Self {
+            hits: AtomicU64::new(self.hits.load(AtomicOrdering::Relaxed)),
+            last_ms: AtomicI64::new(self.last_ms.load(AtomicOrdering::Relaxed)),
+        }
+    }
 }
 
 /// A search result returned from a semantic similarity query.
@@ -28,117 +146,573 @@ pub struct SearchResult {
     pub metadata: HashMap<String, String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
     pub score: f32,
 }
 
+/// Parameters for [`MemoryStore::search`].
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions<'a> {
+    pub limit: usize,
+    /// Only consider entries tagged with this session.
+    pub session: Option<&'a str>,
+    /// Only consider entries whose metadata matches this filter.
+    pub filter: Option<&'a Filter>,
+    /// Return at most one hit per chunked memory: the best-scoring chunk,
+    /// reported under the parent's ID.
+    pub collapse_chunks: bool,
+    /// Scan every entry instead of using the approximate index.
+    pub exact: bool,
+}
+
 /// Wrapper for min-heap ordering: the *lowest* score is popped first so the
 /// heap always retains the top-k highest-scoring results.
-struct MinScored(SearchResult);
+struct MinScored<'a>(&'a MemoryEntry, f32);
 
-impl PartialEq for MinScored {
+impl PartialEq for MinScored<'_> {
     fn eq(&self, other: &Self) -> bool {
-        self.0.score == other.0.score
+        self.1 == other.1
     }
 }
 
-impl Eq for MinScored {}
+impl Eq for MinScored<'_> {}
 
-impl PartialOrd for MinScored {
+impl PartialOrd for MinScored<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for MinScored {
+impl Ord for MinScored<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
         // Reverse: lowest score = greatest in heap order → popped first.
-        other.0.score.partial_cmp(&self.0.score).unwrap_or(Ordering::Equal)
+        other.1.partial_cmp(&self.1).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Victims [`Contents::evict`] finds by linear scans before it sorts the
+/// remaining candidates instead.
+const LINEAR_EVICTIONS: usize = 4;
+
+/// Entries held against one capacity allowance.
+#[derive(Clone, Copy, Debug, Default)]
+struct Usage {
+    entries: usize,
+    bytes: usize,
+}
+
+/// Entries, the index over their embeddings and their capacity usage,
+/// guarded by one lock so they never disagree.
+struct Contents {
+    entries: HashMap<String, MemoryEntry>,
+    index: VectorIndex,
+    capacity: CapacityConfig,
+    /// Usage per eviction scope: the session tag with `per_session`, and
+    /// otherwise `None` for the whole store.
+    usage: HashMap<Option<String>, Usage>,
+}
+
+impl Contents {
+    fn new(config: &MemoryConfig) -> Self {
+        Self {
+            entries: HashMap::new(),
+            index: VectorIndex::new(config.index.clone()),
+            capacity: config.capacity.clone(),
+            usage: HashMap::new(),
+        }
+    }
+
+    /// The eviction scope `entry` counts against.
+    fn scope(&self, entry: &MemoryEntry) -> Option<String> {
+        entry.session.clone().filter(|_| self.capacity.per_session)
+    }
+
+    fn insert(&mut self, entry: MemoryEntry) {
+        self.remove(&entry.id);
+        let usage = self.usage.entry(self.scope(&entry)).or_default();
+        usage.entries += 1;
+        usage.bytes += entry.size_bytes();
+        self.index.insert(&entry.id, &entry.embedding);
+        self.entries.insert(entry.id.clone(), entry);
+    }
+
+    fn remove(&mut self, id: &str) -> Option<MemoryEntry> {
+        let entry = self.entries.remove(id)?;
+        self.index.remove(id);
+        let scope = self.scope(&entry);
+        if let Some(usage) = self.usage.get_mut(&scope) {
+            usage.entries -= 1;
+            usage.bytes -= entry.size_bytes();
+            if usage.entries == 0 {
+                self.usage.remove(&scope);
+            }
+        }
+        Some(entry)
+    }
+
+    /// Remove every entry matching `predicate`, returning the removed IDs.
+    fn remove_where(&mut self, predicate: impl Fn(&MemoryEntry) -> bool) -> Vec<String> {
+        let ids: Vec<String> = self
+            .entries
+            .values()
+            .filter(|e| predicate(e))
+            .map(|e| e.id.clone())
+            .collect();
+        for id in &ids {
+            self.remove(id);
+        }
+        ids
+    }
+
+    fn over_capacity(&self, scope: &Option<String>) -> bool {
+        let usage = self.usage.get(scope).copied().unwrap_or_default();
+        self.capacity
+            .max_entries
+            .is_some_and(|max| usage.entries > max)
+            || self.capacity.max_bytes.is_some_and(|max| usage.bytes > max)
+    }
+
+    /// Entries of `scope` that may be evicted: all but those in `keep`.
+    fn eviction_candidates<'a>(
+        &'a self,
+        scope: &'a Option<String>,
+        keep: &'a HashSet<String>,
+    ) -> impl Iterator<Item = &'a MemoryEntry> {
+        let per_session = self.capacity.per_session;
+        self.entries.values().filter(move |e| {
+            let in_scope = if per_session {
+                e.session == *scope
+            } else {
+                scope.is_none()
+            };
+            in_scope && !keep.contains(&e.id)
+        })
+    }
+
+    /// Evict entries of `scope`, other than those in `keep`, in policy order
+    /// until the scope is within its limits.  Returns the evicted IDs.
+    ///
+    /// An insert usually pushes a scope over its limits by a single entry, so
+    /// the first victims are found by a linear scan; only when more are
+    /// needed, as after loading a store saved under larger limits, are the
+    /// remaining candidates sorted once.
+    fn evict(&mut self, scope: &Option<String>, keep: &HashSet<String>) -> Vec<String> {
+        if !self.over_capacity(scope) {
+            return Vec::new();
+        }
+        let policy = self.capacity.policy;
+        let mut evicted = Vec::new();
+        while evicted.len() < LINEAR_EVICTIONS && self.over_capacity(scope) {
+            let importance_key = self.capacity.importance_key.as_str();
+            let Some(victim) = self
+                .eviction_candidates(scope, keep)
+                .min_by(|a, b| eviction_order(policy, importance_key, a, b))
+                .map(|e| e.id.clone())
+            else {
+                break;
+            };
+            self.remove(&victim);
+            evicted.push(victim);
+        }
+        if self.over_capacity(scope) {
+            let importance_key = self.capacity.importance_key.as_str();
+            let mut candidates: Vec<&MemoryEntry> = self.eviction_candidates(scope, keep).collect();
+            candidates.sort_by(|a, b| eviction_order(policy, importance_key, a, b));
+            let candidates: Vec<String> = candidates.into_iter().map(|e| e.id.clone()).collect();
+            for id in candidates {
+                if !self.over_capacity(scope) {
+                    break;
+                }
+                self.remove(&id);
+                evicted.push(id);
+            }
+        }
+        if !evicted.is_empty() {
+            metrics()
+                .memory_evictions
+                .inc_by(&[policy.as_str()], evicted.len() as u64);
+            info!(
+                evicted = evicted.len(),
+                policy = policy.as_str(),
+                session = ?scope,
+                "Evicted memory entries over capacity"
+            );
+        }
+        if self.over_capacity(scope) {
+            warn!(session = ?scope, "Memory store is over capacity even after evicting every other entry");
+        }
+        evicted
+    }
+
+    /// Bring every scope within its limits, e.g. after loading a store saved
+    /// under larger limits.
+    fn evict_all(&mut self) -> Vec<String> {
+        let scopes: Vec<Option<String>> = self.usage.keys().cloned().collect();
+        scopes
+            .iter()
+            .flat_map(|scope| self.evict(scope, &HashSet::new()))
+            .collect()
     }
 }
 
+/// The order in which `policy` evicts entries: `Less` goes first.
+fn eviction_order(
+    policy: EvictionPolicy,
+    importance_key: &str,
+    a: &MemoryEntry,
+    b: &MemoryEntry,
+) -> Ordering {
+    let importance = |e: &MemoryEntry| {
+        e.metadata
+            .get(importance_key)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+            .unwrap_or(f64::NEG_INFINITY)
+    };
+    let order = match policy {
+        EvictionPolicy::Lru => a.last_access_ms().cmp(&b.last_access_ms()),
+        EvictionPolicy::Lfu => a
+            .hits()
+            .cmp(&b.hits())
+            .then_with(|| a.last_access_ms().cmp(&b.last_access_ms())),
+        EvictionPolicy::Oldest => Ordering::Equal,
+        EvictionPolicy::Importance => importance(a).total_cmp(&importance(b)),
+    };
+    order.then_with(|| a.created_at.cmp(&b.created_at))
+}
+
 /// Thread-safe in-memory vector store for agent memory.
 pub struct MemoryStore {
-    entries: RwLock<HashMap<String, MemoryEntry>>,
+    contents: RwLock<Contents>,
+    /// Durable copy of the entries, when persistence is enabled.
+    pool: Option<SqlitePool>,
 }
 
 impl MemoryStore {
-    pub fn new() -> Self {
+    /// A store kept in memory only.
+    pub fn new(config: &MemoryConfig) -> Self {
         Self {
-            entries: RwLock::new(HashMap::new()),
+            contents: RwLock::new(Contents::new(config)),
+            pool: None,
+        }
+    }
+
+    /// A store persisted in `pool`, starting with the entries saved there.
+    ///
+    /// Entries over the configured capacity are evicted right away.
+    pub async fn load(pool: SqlitePool, config: &MemoryConfig) -> Result<Self, EmbeddingError> {
+        let rows: Vec<DbMemoryEntry> = sqlx::query_as(
+            "SELECT id, text, metadata, session, embedding, expires_at, created_at FROM memory_entries",
+        )
+        .fetch_all(&pool)
+        .await
+        .map_err(storage_error)?;
+
+        let mut contents = Contents::new(config);
+        for row in rows {
+            contents.insert(MemoryEntry::try_from(row)?);
         }
+        let evicted = contents.evict_all();
+        delete_rows(&pool, &evicted).await.map_err(storage_error)?;
+
+        Ok(Self {
+            contents: RwLock::new(contents),
+            pool: Some(pool),
+        })
+    }
+
+    /// Number of stored entries (each chunk counts separately).
+    pub fn len(&self) -> usize {
+        self.contents
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    /// Approximate size of the stored entries in bytes, as counted against
+    /// `max_bytes`.
+    pub fn size_bytes(&self) -> usize {
+        let contents = self.contents.read().unwrap_or_else(|e| e.into_inner());
+        contents.usage.values().map(|u| u.bytes).sum()
     }
 
     /// Store a new memory entry, returning its generated ID.
-    pub fn store(
+    pub async fn store(
         &self,
         text: String,
         metadata: HashMap<String, String>,
         session: Option<String>,
         embedding: Embedding,
-    ) -> String {
-        let id = Uuid::new_v4().to_string();
-        let entry = MemoryEntry {
-            id: id.clone(),
-            text,
-            metadata,
-            session,
-            embedding,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<String, EmbeddingError> {
+        let entry = MemoryEntry::new(text, metadata, session, embedding, expires_at);
+        let id = entry.id.clone();
+        self.insert(vec![entry]).await?;
+        Ok(id)
+    }
+
+    /// Store `entries` together, e.g. all chunks of one memory: when
+    /// persistence is enabled, either all of them are saved or none is.
+    ///
+    /// Other entries are then evicted as needed to bring the scopes written
+    /// to back within capacity; `entries` themselves are never evicted here.
+    pub async fn insert(&self, entries: Vec<MemoryEntry>) -> Result<(), EmbeddingError> {
+        if let Some(pool) = &self.pool {
+            persist(pool, &entries).await.map_err(storage_error)?;
+        }
+        let evicted = {
+            let mut contents = self.contents.write().unwrap_or_else(|e| e.into_inner());
+            let keep: HashSet<String> = entries.iter().map(|e| e.id.clone()).collect();
+            let scopes: HashSet<Option<String>> =
+                entries.iter().map(|e| contents.scope(e)).collect();
+            for entry in entries {
+                contents.insert(entry);
+            }
+            scopes
+                .iter()
+                .flat_map(|scope| contents.evict(scope, &keep))
+                .collect::<Vec<_>>()
         };
-        self.entries.write().unwrap_or_else(|e| e.into_inner()).insert(id.clone(), entry);
-        id
+        if let Some(pool) = &self.pool {
+            // The new entries are stored either way; evicted rows left behind
+            // are evicted again on the next load.
+            if let Err(e) = delete_rows(pool, &evicted).await {
+                warn!(error = %e, "Failed to delete evicted memory entries from the database");
+            }
+        }
+        Ok(())
     }
 
     /// Search for the top-k most similar entries to the given query embedding.
     ///
-    /// Uses a min-heap for O(N log k) selection instead of a full O(N log N)
-    /// sort, which is significantly faster when `limit` is much smaller than
-    /// the total number of stored entries.
+    /// Once at least `min_entries` entries share the query's dimensionality,
+    /// candidates come from the HNSW index: the `max(ef_search, limit)`
+    /// approximately nearest entries.  Smaller stores, and searches with
+    /// `exact` set, scan every entry instead.
+    ///
+    /// Either way a min-heap selects the top `limit` in O(N log k).
     ///
     /// Results are returned in descending order of cosine similarity.
-    /// An optional `session` filter limits results to a specific session tag.
+    /// Expired entries, and those outside the optional `session` and metadata
+    /// `filter`, are skipped before the top `limit` are picked, so a filtered
+    /// search still fills its page.  Each returned entry has a hit recorded
+    /// for the eviction policies.
     pub fn search(
         &self,
         query_embedding: &Embedding,
-        limit: usize,
-        session: Option<&str>,
+        options: &SearchOptions<'_>,
     ) -> Vec<SearchResult> {
-        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
-
-        // Min-heap keeps at most `limit` entries; the lowest score is on top
-        // so it can be cheaply evicted when a better candidate arrives.
-        let mut heap = BinaryHeap::<MinScored>::with_capacity(limit + 1);
+        let contents = self.contents.read().unwrap_or_else(|e| e.into_inner());
+        let Contents { entries, index, .. } = &*contents;
+        let now = Utc::now();
+        let accept = |e: &MemoryEntry| {
+            !e.is_expired(now)
+                && options
+                    .session
+                    .is_none_or(|s| e.session.as_deref() == Some(s))
+                && options.filter.is_none_or(|f| f.matches(&e.metadata))
+        };
 
-        for e in entries.values() {
-            if let Some(s) = session {
-                if e.session.as_deref() != Some(s) {
-                    continue;
+        let config = index.config();
+        let hits = if !options.exact && index.len(query_embedding.len()) >= config.min_entries {
+            let ef = config.ef_search.max(options.limit);
+            let hits = index.search(query_embedding, ef, |id| {
+                entries.get(id).is_some_and(accept)
+            });
+            let scored = hits
+                .into_iter()
+                .filter_map(|(id, score)| entries.get(id).map(|e| (e, score)));
+            top_k(scored, options)
+        } else {
+            let scored = entries.values().filter_map(|e| {
+                if !accept(e) {
+                    return None;
                 }
-            }
-            if let Some(score) = cosine_similarity(query_embedding, &e.embedding) {
-                let result = SearchResult {
-                    id: e.id.clone(),
+                Some((e, cosine_similarity(query_embedding, &e.embedding)?))
+            });
+            top_k(scored, options)
+        };
+
+        let now_ms = now.timestamp_millis();
+        hits.into_iter()
+            .map(|(e, score)| {
+                e.access.record(now_ms);
+                let id = match e.metadata.get(PARENT_ID_KEY) {
+                    Some(parent) if options.collapse_chunks => parent.clone(),
+                    _ => e.id.clone(),
+                };
+                SearchResult {
+                    id,
                     text: e.text.clone(),
                     metadata: e.metadata.clone(),
                     session: e.session.clone(),
+                    expires_at: e.expires_at,
                     score,
-                };
-                heap.push(MinScored(result));
-                if heap.len() > limit {
-                    heap.pop(); // evict the lowest-scoring entry
+                }
+            })
+            .collect()
+    }
+
+    /// Delete a memory entry by ID. Returns `true` if the entry existed.
+    ///
+    /// Deleting the parent ID of a chunked memory removes all of its chunks.
+    pub async fn delete(&self, id: &str) -> Result<bool, EmbeddingError> {
+        if let Some(pool) = &self.pool {
+            sqlx::query("DELETE FROM memory_entries WHERE id = ? OR parent_id = ?")
+                .bind(id)
+                .bind(id)
+                .execute(pool)
+                .await
+                .map_err(storage_error)?;
+        }
+        let mut contents = self.contents.write().unwrap_or_else(|e| e.into_inner());
+        let removed = contents.remove_where(|e| {
+            e.id == id || e.metadata.get(PARENT_ID_KEY).map(String::as_str) == Some(id)
+        });
+        Ok(!removed.is_empty())
+    }
+
+    /// Remove every entry that has expired, returning how many were removed.
+    pub async fn purge_expired(&self) -> Result<usize, EmbeddingError> {
+        let now = Utc::now();
+        if let Some(pool) = &self.pool {
+            sqlx::query("DELETE FROM memory_entries WHERE expires_at <= ?")
+                .bind(now.timestamp())
+                .execute(pool)
+                .await
+                .map_err(storage_error)?;
+        }
+        let mut contents = self.contents.write().unwrap_or_else(|e| e.into_inner());
+        Ok(contents.remove_where(|e| e.is_expired(now)).len())
+    }
+}
+
+/// The `options.limit` highest-scoring of `scored`, best first, after
+/// keeping only the best chunk per parent when collapsing chunks.
+fn top_k<'a>(
+    scored: impl Iterator<Item = (&'a MemoryEntry, f32)>,
+    options: &SearchOptions<'_>,
+) -> Vec<(&'a MemoryEntry, f32)> {
+    let limit = options.limit;
+
+    // Min-heap keeps at most `limit` entries; the lowest score is on top
+    // so it can be cheaply evicted when a better candidate arrives.
+    let mut heap = BinaryHeap::<MinScored>::with_capacity(limit + 1);
+    let mut push = |(entry, score): (&'a MemoryEntry, f32)| {
+        heap.push(MinScored(entry, score));
+        if heap.len() > limit {
+            heap.pop(); // evict the lowest-scoring entry
+        }
+    };
+
+    if options.collapse_chunks {
+        // Keep only the best chunk per parent before ranking.
+        let mut best: HashMap<&str, (&MemoryEntry, f32)> = HashMap::new();
+        for (entry, score) in scored {
+            let key = entry.metadata.get(PARENT_ID_KEY).unwrap_or(&entry.id);
+            match best.get(key.as_str()) {
+                Some(&(_, existing)) if existing >= score => {}
+                _ => {
+                    best.insert(key, (entry, score));
                 }
             }
         }
+        best.into_values().for_each(&mut push);
+    } else {
+        scored.for_each(&mut push);
+    }
 
-        // Drain into a vec and reverse so highest score comes first.
-        let mut results: Vec<SearchResult> = heap.into_iter().map(|ms| ms.0).collect();
-        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
-        results
+    // Drain into a vec and reverse so highest score comes first.
+    let mut results: Vec<(&MemoryEntry, f32)> = heap.into_iter().map(|ms| (ms.0, ms.1)).collect();
+    results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    results
+}
+
+// ---------------------------------------------------------------------------
+// Persistence
+// ---------------------------------------------------------------------------
+
+/// Raw database row; converted to [`MemoryEntry`] by decoding the metadata
+/// JSON and the embedding blob.
+#[derive(sqlx::FromRow)]
+struct DbMemoryEntry {
+    id: String,
+    text: String,
+    metadata: String,
+    session: Option<String>,
+    embedding: Vec<u8>,
+    expires_at: Option<i64>,
+    created_at: i64,
+}
+
+impl TryFrom<DbMemoryEntry> for MemoryEntry {
+    type Error = EmbeddingError;
+
+    fn try_from(row: DbMemoryEntry) -> Result<Self, Self::Error> {
+        let metadata = serde_json::from_str(&row.metadata).map_err(|e| {
+            EmbeddingError::MemoryStorage(format!("Invalid metadata of entry '{}': {e}", row.id))
+        })?;
+        let embedding = decode_embedding(&row.embedding).ok_or_else(|| {
+            EmbeddingError::MemoryStorage(format!("Invalid embedding of entry '{}'", row.id))
+        })?;
+        Ok(MemoryEntry {
+            id: row.id,
+            text: row.text,
+            metadata,
+            session: row.session,
+            embedding,
+            expires_at: row
+                .expires_at
+                .and_then(|at| DateTime::from_timestamp(at, 0)),
+            created_at: DateTime::from_timestamp(row.created_at, 0).unwrap_or_default(),
+            access: Access::default(),
+        })
     }
+}
 
-    /// Delete a memory entry by ID. Returns `true` if the entry existed.
-    pub fn delete(&self, id: &str) -> bool {
-        self.entries.write().unwrap_or_else(|e| e.into_inner()).remove(id).is_some()
+async fn persist(pool: &SqlitePool, entries: &[MemoryEntry]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for entry in entries {
+        let metadata =
+            serde_json::to_string(&entry.metadata).expect("a string map always serialises to JSON");
+        sqlx::query(
+            "INSERT OR REPLACE INTO memory_entries \
+             (id, text, metadata, session, parent_id, embedding, created_at, expires_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&entry.id)
+        .bind(&entry.text)
+        .bind(metadata)
+        .bind(&entry.session)
+        .bind(entry.metadata.get(PARENT_ID_KEY))
+        .bind(encode_embedding(&entry.embedding))
+        .bind(entry.created_at.timestamp())
+        .bind(entry.expires_at.map(|at| at.timestamp()))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Delete the rows of evicted entries, in one transaction.
+async fn delete_rows(pool: &SqlitePool, ids: &[String]) -> Result<(), sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    for id in ids {
+        sqlx::query("DELETE FROM memory_entries WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
     }
+    tx.commit().await
+}
+
+fn storage_error(e: sqlx::Error) -> EmbeddingError {
+    EmbeddingError::MemoryStorage(e.to_string())
 }
 
 /// Compute the cosine similarity between two vectors.
@@ -163,30 +737,85 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::HnswConfig;
 
-    #[test]
-    fn store_and_search_returns_ranked_results() {
-        let store = MemoryStore::new();
-        store.store(
-            "hello world".to_string(),
-            HashMap::new(),
-            None,
-            vec![1.0, 0.0, 0.0],
-        );
-        store.store(
-            "goodbye world".to_string(),
-            HashMap::new(),
-            None,
-            vec![0.0, 1.0, 0.0],
-        );
-        store.store(
-            "hello again".to_string(),
+    fn indexed(index: HnswConfig) -> MemoryConfig {
+        MemoryConfig {
+            index,
+            ..MemoryConfig::default()
+        }
+    }
+
+    fn capped(capacity: CapacityConfig) -> MemoryConfig {
+        MemoryConfig {
+            capacity,
+            ..MemoryConfig::default()
+        }
+    }
+
+    /// An entry stored `age_secs` ago.
+    fn aged(text: &str, session: Option<&str>, embedding: Embedding, age_secs: i64) -> MemoryEntry {
+        let mut entry = MemoryEntry::new(
+            text.to_string(),
             HashMap::new(),
+            session.map(String::from),
+            embedding,
             None,
-            vec![0.9, 0.1, 0.0],
         );
+        entry.created_at -= chrono::TimeDelta::seconds(age_secs);
+        entry
+    }
+
+    fn texts(store: &MemoryStore) -> Vec<String> {
+        let contents = store.contents.read().unwrap();
+        let mut texts: Vec<_> = contents.entries.values().map(|e| e.text.clone()).collect();
+        texts.sort();
+        texts
+    }
+
+    fn opts(limit: usize, session: Option<&str>) -> SearchOptions<'_> {
+        SearchOptions {
+            limit,
+            session,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn store_and_search_returns_ranked_results() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        store
+            .store(
+                "hello world".to_string(),
+                HashMap::new(),
+                None,
+                vec![1.0, 0.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "goodbye world".to_string(),
+                HashMap::new(),
+                None,
+                vec![0.0, 1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "hello again".to_string(),
+                HashMap::new(),
+                None,
+                vec![0.9, 0.1, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
 
-        let results = store.search(&vec![1.0, 0.0, 0.0], 10, None);
+        let results = store.search(&vec![1.0, 0.0, 0.0], &opts(10, None));
         assert_eq!(results.len(), 3);
         // "hello world" should be the top result (exact match)
         assert_eq!(results[0].text, "hello world");
@@ -198,63 +827,118 @@ mod tests {
         assert!(results[2].score.abs() < 1e-6);
     }
 
-    #[test]
-    fn search_respects_limit() {
-        let store = MemoryStore::new();
+    #[tokio::test]
+    async fn search_respects_limit() {
+        let store = MemoryStore::new(&MemoryConfig::default());
         for i in 1..=5 {
-            store.store(
-                format!("entry {i}"),
-                HashMap::new(),
-                None,
-                vec![i as f32, 1.0],
-            );
+            store
+                .store(
+                    format!("entry {i}"),
+                    HashMap::new(),
+                    None,
+                    vec![i as f32, 1.0],
+                    None,
+                )
+                .await
+                .unwrap();
         }
 
-        let results = store.search(&vec![1.0, 0.0], 2, None);
+        let results = store.search(&vec![1.0, 0.0], &opts(2, None));
         assert_eq!(results.len(), 2);
     }
 
-    #[test]
-    fn search_filters_by_session() {
-        let store = MemoryStore::new();
-        store.store(
-            "session a".to_string(),
-            HashMap::new(),
-            Some("a".to_string()),
-            vec![1.0, 0.0],
-        );
-        store.store(
-            "session b".to_string(),
-            HashMap::new(),
-            Some("b".to_string()),
-            vec![1.0, 0.0],
-        );
-        store.store(
-            "no session".to_string(),
-            HashMap::new(),
-            None,
-            vec![1.0, 0.0],
-        );
+    #[tokio::test]
+    async fn search_filters_by_session() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        store
+            .store(
+                "session a".to_string(),
+                HashMap::new(),
+                Some("a".to_string()),
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "session b".to_string(),
+                HashMap::new(),
+                Some("b".to_string()),
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "no session".to_string(),
+                HashMap::new(),
+                None,
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
 
-        let results = store.search(&vec![1.0, 0.0], 10, Some("a"));
+        let results = store.search(&vec![1.0, 0.0], &opts(10, Some("a")));
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].text, "session a");
     }
 
-    #[test]
-    fn delete_removes_entry() {
-        let store = MemoryStore::new();
-        let id = store.store(
-            "temp".to_string(),
-            HashMap::new(),
-            None,
-            vec![1.0],
-        );
+    #[tokio::test]
+    async fn metadata_filter_is_applied_before_the_limit() {
+        for min_entries in [usize::MAX, 0] {
+            let store = MemoryStore::new(&indexed(HnswConfig {
+                min_entries,
+                ..HnswConfig::default()
+            }));
+            // The closest entries are all odd and fail the filter.
+            for i in 0..20 {
+                let metadata = HashMap::from([("n".to_string(), i.to_string())]);
+                let angle = i as f32 / 20.0;
+                store
+                    .store(
+                        format!("entry {i}"),
+                        metadata,
+                        None,
+                        vec![angle.cos(), angle.sin()],
+                        None,
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            let filter = Filter::parse(
+                r#"{"and": [{"key": "n", "gte": 10}, {"not": {"key": "n", "in": [11, 13, 15, 17, 19]}}]}"#,
+            )
+            .unwrap();
+            let options = SearchOptions {
+                filter: Some(&filter),
+                ..opts(3, None)
+            };
+            let results = store.search(&vec![1.0, 0.0], &options);
+            let texts: Vec<_> = results.iter().map(|r| r.text.as_str()).collect();
+            assert_eq!(
+                texts,
+                vec!["entry 10", "entry 12", "entry 14"],
+                "min_entries = {min_entries}"
+            );
+        }
+    }
 
-        assert!(store.delete(&id));
-        assert!(!store.delete(&id)); // second delete returns false
+    #[tokio::test]
+    async fn delete_removes_entry() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        let id = store
+            .store("temp".to_string(), HashMap::new(), None, vec![1.0], None)
+            .await
+            .unwrap();
 
-        let results = store.search(&vec![1.0], 10, None);
+        assert!(store.delete(&id).await.unwrap());
+        assert!(!store.delete(&id).await.unwrap()); // second delete returns false
+
+        let results = store.search(&vec![1.0], &opts(10, None));
         assert!(results.is_empty());
     }
 
@@ -289,58 +973,504 @@ mod tests {
         assert!(cosine_similarity(&b, &a).is_none());
     }
 
-    #[test]
-    fn search_excludes_dimension_mismatched_entries() {
-        let store = MemoryStore::new();
-        store.store(
-            "3-dim entry".to_string(),
-            HashMap::new(),
-            None,
-            vec![1.0, 0.0, 0.0],
-        );
-        store.store(
-            "2-dim entry".to_string(),
-            HashMap::new(),
-            None,
-            vec![1.0, 0.0],
-        );
+    #[tokio::test]
+    async fn search_excludes_dimension_mismatched_entries() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        store
+            .store(
+                "3-dim entry".to_string(),
+                HashMap::new(),
+                None,
+                vec![1.0, 0.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "2-dim entry".to_string(),
+                HashMap::new(),
+                None,
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
 
         // Query with 3-dim vector: only the 3-dim entry should match
-        let results = store.search(&vec![1.0, 0.0, 0.0], 10, None);
+        let results = store.search(&vec![1.0, 0.0, 0.0], &opts(10, None));
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].text, "3-dim entry");
     }
 
-    #[test]
-    fn search_excludes_zero_magnitude_entries() {
-        let store = MemoryStore::new();
-        store.store(
-            "valid".to_string(),
-            HashMap::new(),
-            None,
-            vec![1.0, 0.0],
-        );
-        store.store(
-            "zero vector".to_string(),
-            HashMap::new(),
-            None,
-            vec![0.0, 0.0],
-        );
+    #[tokio::test]
+    async fn search_excludes_zero_magnitude_entries() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        store
+            .store(
+                "valid".to_string(),
+                HashMap::new(),
+                None,
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "zero vector".to_string(),
+                HashMap::new(),
+                None,
+                vec![0.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
 
-        let results = store.search(&vec![1.0, 0.0], 10, None);
+        let results = store.search(&vec![1.0, 0.0], &opts(10, None));
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].text, "valid");
     }
 
-    #[test]
-    fn store_preserves_metadata() {
-        let store = MemoryStore::new();
+    #[tokio::test]
+    async fn store_preserves_metadata() {
+        let store = MemoryStore::new(&MemoryConfig::default());
         let mut meta = HashMap::new();
         meta.insert("key".to_string(), "value".to_string());
 
-        store.store("with meta".to_string(), meta, None, vec![1.0]);
+        store
+            .store("with meta".to_string(), meta, None, vec![1.0], None)
+            .await
+            .unwrap();
+
+        let results = store.search(&vec![1.0], &opts(10, None));
+        assert_eq!(results[0].metadata.get("key").unwrap(), "value");
+    }
+
+    fn chunk_meta(parent: &str, index: usize) -> HashMap<String, String> {
+        HashMap::from([
+            (PARENT_ID_KEY.to_string(), parent.to_string()),
+            ("chunk_index".to_string(), index.to_string()),
+        ])
+    }
+
+    #[tokio::test]
+    async fn search_collapses_chunks_to_best_hit_per_parent() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        store
+            .store(
+                "chunk 0".to_string(),
+                chunk_meta("p", 0),
+                None,
+                vec![0.6, 0.8],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "chunk 1".to_string(),
+                chunk_meta("p", 1),
+                None,
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "other".to_string(),
+                HashMap::new(),
+                None,
+                vec![0.8, 0.6],
+                None,
+            )
+            .await
+            .unwrap();
+
+        let options = SearchOptions {
+            limit: 10,
+            collapse_chunks: true,
+            ..Default::default()
+        };
+        let results = store.search(&vec![1.0, 0.0], &options);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, "p");
+        assert_eq!(results[0].text, "chunk 1");
+        assert_eq!(results[1].text, "other");
+
+        // Without collapsing every chunk is returned.
+        assert_eq!(store.search(&vec![1.0, 0.0], &opts(10, None)).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn delete_by_parent_id_removes_all_chunks() {
+        let store = MemoryStore::new(&MemoryConfig::default());
+        store
+            .store(
+                "chunk 0".to_string(),
+                chunk_meta("p", 0),
+                None,
+                vec![1.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                "chunk 1".to_string(),
+                chunk_meta("p", 1),
+                None,
+                vec![1.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .store("other".to_string(), HashMap::new(), None, vec![1.0], None)
+            .await
+            .unwrap();
+
+        assert!(store.delete("p").await.unwrap());
+        let results = store.search(&vec![1.0], &opts(10, None));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].text, "other");
+    }
+
+    #[tokio::test]
+    async fn large_stores_are_searched_through_the_index() {
+        let store = MemoryStore::new(&indexed(HnswConfig {
+            min_entries: 10,
+            ..HnswConfig::default()
+        }));
+        for i in 0..50 {
+            let angle = i as f32 / 50.0 * std::f32::consts::FRAC_PI_2;
+            let session = Some(if i % 2 == 0 { "even" } else { "odd" }.to_string());
+            store
+                .store(
+                    format!("entry {i}"),
+                    HashMap::new(),
+                    session,
+                    vec![angle.cos(), angle.sin()],
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        let deleted = store.search(&vec![1.0, 0.0], &opts(1, None))[0].id.clone();
+        assert!(store.delete(&deleted).await.unwrap());
+
+        let query = vec![1.0, 0.0];
+        let indexed = store.search(&query, &opts(3, Some("odd")));
+        let exact = store.search(
+            &query,
+            &SearchOptions {
+                exact: true,
+                ..opts(3, Some("odd"))
+            },
+        );
+        let texts =
+            |results: &[SearchResult]| results.iter().map(|r| r.text.clone()).collect::<Vec<_>>();
+        assert_eq!(texts(&indexed), vec!["entry 1", "entry 3", "entry 5"]);
+        assert_eq!(texts(&indexed), texts(&exact));
+        assert!((indexed[0].score - exact[0].score).abs() < 1e-5);
+
+        let results = store.search(&query, &opts(3, None));
+        assert_eq!(
+            results[0].text, "entry 1",
+            "deleted entry must not be returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn each_policy_evicts_its_own_victim() {
+        for (policy, victim) in [
+            (EvictionPolicy::Lru, "a"),
+            (EvictionPolicy::Lfu, "b"),
+            (EvictionPolicy::Oldest, "c"),
+            (EvictionPolicy::Importance, "d"),
+        ] {
+            let store = MemoryStore::new(&capped(CapacityConfig {
+                max_entries: Some(4),
+                policy,
+                ..CapacityConfig::default()
+            }));
+            let entries = [
+                ("a", 20, "3"),
+                ("b", 10, "4"),
+                ("c", 30, "2"),
+                ("d", 5, "1"),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (text, age, importance))| {
+                let mut embedding = vec![0.0; 5];
+                embedding[i] = 1.0;
+                let mut entry = aged(text, None, embedding, age);
+                entry
+                    .metadata
+                    .insert("importance".to_string(), importance.to_string());
+                entry
+            })
+            .collect::<Vec<_>>();
+            let vectors: Vec<Embedding> = entries.iter().map(|e| e.embedding.clone()).collect();
+            store.insert(entries).await.unwrap();
+
+            // a is the most used but least recently; b, c and d one hit each.
+            for i in [0, 0, 0, 1, 2, 3] {
+                assert_eq!(store.search(&vectors[i], &opts(1, None)).len(), 1);
+                tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            }
+            store
+                .store(
+                    "e".to_string(),
+                    HashMap::new(),
+                    None,
+                    vec![0.0, 0.0, 0.0, 0.0, 1.0],
+                    None,
+                )
+                .await
+                .unwrap();
+
+            let mut expected: Vec<_> = ["a", "b", "c", "d", "e"]
+                .into_iter()
+                .filter(|t| *t != victim)
+                .collect();
+            expected.sort();
+            assert_eq!(texts(&store), expected, "{policy:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn per_session_limits_evict_only_from_that_session() {
+        let store = MemoryStore::new(&capped(CapacityConfig {
+            max_entries: Some(1),
+            per_session: true,
+            ..CapacityConfig::default()
+        }));
+        store
+            .insert(vec![aged("x1", Some("x"), vec![1.0], 10)])
+            .await
+            .unwrap();
+        store
+            .insert(vec![aged("y1", Some("y"), vec![1.0], 10)])
+            .await
+            .unwrap();
+        store
+            .insert(vec![aged("x2", Some("x"), vec![1.0], 5)])
+            .await
+            .unwrap();
+        assert_eq!(texts(&store), vec!["x2", "y1"]);
+
+        // A batch over the limit on its own is kept whole.
+        store
+            .insert(vec![
+                aged("y2", Some("y"), vec![1.0], 0),
+                aged("y3", Some("y"), vec![1.0], 0),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(texts(&store), vec!["x2", "y2", "y3"]);
 
-        let results = store.search(&vec![1.0], 10, None);
+        assert!(store.size_bytes() > 0);
+        let ids: Vec<String> = store
+            .contents
+            .read()
+            .unwrap()
+            .entries
+            .keys()
+            .cloned()
+            .collect();
+        for id in ids {
+            assert!(store.delete(&id).await.unwrap());
+        }
+        assert_eq!((store.len(), store.size_bytes()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn byte_limit_evicts_until_the_store_fits() {
+        let entry_size = aged("0", None, vec![1.0; 8], 0).size_bytes();
+        let store = MemoryStore::new(&capped(CapacityConfig {
+            max_bytes: Some(entry_size * 3),
+            policy: EvictionPolicy::Oldest,
+            ..CapacityConfig::default()
+        }));
+        for i in 0..5 {
+            store
+                .insert(vec![aged(&i.to_string(), None, vec![1.0; 8], 10 - i)])
+                .await
+                .unwrap();
+        }
+        assert_eq!(texts(&store), vec!["2", "3", "4"]);
+        assert_eq!(store.size_bytes(), entry_size * 3);
+    }
+
+    #[test]
+    fn evicting_many_entries_keeps_the_policy_order() {
+        let mut contents = Contents::new(&capped(CapacityConfig {
+            max_entries: Some(2),
+            policy: EvictionPolicy::Oldest,
+            ..CapacityConfig::default()
+        }));
+        for i in 0..LINEAR_EVICTIONS as i64 * 3 {
+            contents.insert(aged(&i.to_string(), None, vec![1.0], 100 - i));
+        }
+
+        let evicted = contents.evict_all();
+        assert_eq!(evicted.len(), LINEAR_EVICTIONS * 3 - 2);
+        let mut kept: Vec<i64> = contents
+            .entries
+            .values()
+            .map(|e| e.text.parse().unwrap())
+            .collect();
+        kept.sort();
+        let newest = LINEAR_EVICTIONS as i64 * 3;
+        assert_eq!(kept, vec![newest - 2, newest - 1]);
+    }
+
+    #[tokio::test]
+    async fn evicted_entries_are_deleted_from_the_database() {
+        let sessions = crate::session_store::SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        let pool = sessions.pool().clone();
+        let limit = |max_entries| {
+            capped(CapacityConfig {
+                max_entries: Some(max_entries),
+                policy: EvictionPolicy::Oldest,
+                ..CapacityConfig::default()
+            })
+        };
+
+        let store = MemoryStore::load(pool.clone(), &limit(3)).await.unwrap();
+        for i in 0..5 {
+            store
+                .insert(vec![aged(&i.to_string(), None, vec![1.0], 100 - i)])
+                .await
+                .unwrap();
+        }
+        assert_eq!(texts(&store), vec!["2", "3", "4"]);
+
+        // Loading under a smaller limit evicts, in the database too.
+        let reloaded = MemoryStore::load(pool.clone(), &limit(2)).await.unwrap();
+        assert_eq!(texts(&reloaded), vec!["3", "4"]);
+        let unlimited = MemoryStore::load(pool, &MemoryConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(texts(&unlimited), vec!["3", "4"]);
+    }
+
+    #[tokio::test]
+    async fn persistent_store_is_reloaded_with_embeddings() {
+        let sessions = crate::session_store::SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        let pool = sessions.pool().clone();
+
+        let store = MemoryStore::load(pool.clone(), &MemoryConfig::default())
+            .await
+            .unwrap();
+        let meta = HashMap::from([("key".to_string(), "value".to_string())]);
+        let kept = store
+            .store(
+                "kept".to_string(),
+                meta,
+                Some("s".to_string()),
+                vec![0.6, 0.8],
+                None,
+            )
+            .await
+            .unwrap();
+        let deleted = store
+            .store(
+                "deleted".to_string(),
+                HashMap::new(),
+                None,
+                vec![1.0, 0.0],
+                None,
+            )
+            .await
+            .unwrap();
+        store
+            .insert(vec![
+                MemoryEntry::new(
+                    "chunk 0".to_string(),
+                    chunk_meta("p", 0),
+                    None,
+                    vec![0.0, 1.0],
+                    None,
+                ),
+                MemoryEntry::new(
+                    "chunk 1".to_string(),
+                    chunk_meta("p", 1),
+                    None,
+                    vec![0.0, 1.0],
+                    None,
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(store.delete(&deleted).await.unwrap());
+        assert!(store.delete("p").await.unwrap());
+
+        let reloaded = MemoryStore::load(pool, &MemoryConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(reloaded.len(), 1);
+        let results = reloaded.search(&vec![0.6, 0.8], &opts(10, None));
+        assert_eq!(results[0].id, kept);
+        assert!((results[0].score - 1.0).abs() < 1e-6);
         assert_eq!(results[0].metadata.get("key").unwrap(), "value");
+        assert_eq!(results[0].session.as_deref(), Some("s"));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_hidden_then_purged() {
+        let sessions = crate::session_store::SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        let pool = sessions.pool().clone();
+        let store = MemoryStore::load(pool.clone(), &MemoryConfig::default())
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let expires_at = DateTime::from_timestamp(now.timestamp() + 3600, 0);
+        for (text, at) in [
+            ("expired", Some(now - chrono::TimeDelta::seconds(1))),
+            ("expiring", expires_at),
+            ("kept", None),
+        ] {
+            store
+                .store(text.to_string(), HashMap::new(), None, vec![1.0, 0.0], at)
+                .await
+                .unwrap();
+        }
+
+        let texts = |store: &MemoryStore| {
+            let mut texts: Vec<_> = store
+                .search(&vec![1.0, 0.0], &opts(10, None))
+                .into_iter()
+                .map(|r| (r.text, r.expires_at))
+                .collect();
+            texts.sort();
+            texts
+        };
+        let live = vec![
+            ("expiring".to_string(), expires_at),
+            ("kept".to_string(), None),
+        ];
+        assert_eq!(store.len(), 3);
+        assert_eq!(texts(&store), live);
+
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+        assert_eq!(store.purge_expired().await.unwrap(), 0);
+        assert_eq!(store.len(), 2);
+
+        let reloaded = MemoryStore::load(pool, &MemoryConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(texts(&reloaded), live);
     }
 }
@@ -0,0 +1,372 @@
+//! Prometheus metrics.
+//!
+//! A small, dependency-free registry rendered in the Prometheus text
+//! exposition format (version 0.0.4) by `GET /metrics`.
+//!
+//! Instrumented code records into the process-wide registry returned by
+//! [`metrics`]:
+//! - HTTP routes – [`track_http`] middleware
+//! - embedding providers – [`crate::embedding::instrumented::InstrumentedProvider`]
+//!   and [`crate::embedding::single_flight::SingleFlightProvider`]
+//! - Qdrant calls – [`crate::vector_store::QdrantStore`]
+//! - `/memory` store evictions – [`crate::memory::MemoryStore`]
+//!
+//! Values that are cheap to read on demand (memory store size, SQLite pool
+//! usage) are not stored here; the `/metrics` handler appends them with
+//! [`render_gauge`] at scrape time.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+// ---------------------------------------------------------------------------
+// Metric families
+// ---------------------------------------------------------------------------
+
+/// Label values of one series, in the order of the family's label names.
+type LabelValues = Vec<String>;
+
+struct Family<T> {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    series: Mutex<BTreeMap<LabelValues, T>>,
+}
+
+impl<T: Default> Family<T> {
+    fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn with<R>(&self, values: &[&str], f: impl FnOnce(&mut T) -> R) -> R {
+        debug_assert_eq!(values.len(), self.labels.len(), "{}", self.name);
+        let key = values.iter().map(|v| v.to_string()).collect();
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        f(series.entry(key).or_default())
+    }
+}
+
+/// A monotonically increasing count.
+pub struct CounterVec(Family<u64>);
+
+impl CounterVec {
+    pub fn inc(&self, labels: &[&str]) {
+        self.inc_by(labels, 1);
+    }
+
+    pub fn inc_by(&self, labels: &[&str], n: u64) {
+        self.0.with(labels, |count| *count += n);
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, &self.0, "counter");
+        let series = self.0.series.lock().unwrap_or_else(|e| e.into_inner());
+        for (values, count) in series.iter() {
+            let _ = writeln!(
+                out,
+                "{}{} {count}",
+                self.0.name,
+                label_set(self.0.labels, values, None)
+            );
+        }
+    }
+}
+
+/// A value that can go up and down.
+pub struct GaugeVec(Family<f64>);
+
+impl GaugeVec {
+    pub fn set(&self, labels: &[&str], value: f64) {
+        self.0.with(labels, |gauge| *gauge = value);
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, &self.0, "gauge");
+        let series = self.0.series.lock().unwrap_or_else(|e| e.into_inner());
+        for (values, value) in series.iter() {
+            let _ = writeln!(
+                out,
+                "{}{} {value}",
+                self.0.name,
+                label_set(self.0.labels, values, None)
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Non-cumulative count per bucket of [`LATENCY_BUCKETS`].
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+/// A latency distribution, in seconds.
+pub struct HistogramVec(Family<Histogram>);
+
+impl HistogramVec {
+    pub fn observe(&self, labels: &[&str], elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        self.0.with(labels, |h| {
+            if let Some(i) = LATENCY_BUCKETS.iter().position(|&le| seconds <= le) {
+                h.buckets[i] += 1;
+            }
+            h.count += 1;
+            h.sum += seconds;
+        });
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, &self.0, "histogram");
+        let name = self.0.name;
+        let series = self.0.series.lock().unwrap_or_else(|e| e.into_inner());
+        for (values, h) in series.iter() {
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(h.buckets) {
+                cumulative += count;
+                let labels = label_set(self.0.labels, values, Some(&le.to_string()));
+                let _ = writeln!(out, "{name}_bucket{labels} {cumulative}");
+            }
+            let labels = label_set(self.0.labels, values, Some("+Inf"));
+            let _ = writeln!(out, "{name}_bucket{labels} {}", h.count);
+            let labels = label_set(self.0.labels, values, None);
+            let _ = writeln!(out, "{name}_sum{labels} {}", h.sum);
+            let _ = writeln!(out, "{name}_count{labels} {}", h.count);
+        }
+    }
+}
+
+fn header<T>(out: &mut String, family: &Family<T>, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+    let _ = writeln!(out, "# TYPE {} {kind}", family.name);
+}
+
+/// Format `{name="value",…}`, with an optional trailing `le` label; empty
+/// when there are no labels.
+fn label_set(names: &[&str], values: &[String], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{name}=\"{}\"", escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{le}\""));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Append a gauge family whose values are read at scrape time.
+pub fn render_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    labels: &[&str],
+    series: &[(&[&str], f64)],
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    for (values, value) in series {
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        let _ = writeln!(out, "{name}{} {value}", label_set(labels, &values, None));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Every metric recorded by the server.
+pub struct Metrics {
+    pub http_requests: CounterVec,
+    pub http_duration: HistogramVec,
+    pub embedding_duration: HistogramVec,
+    pub embedding_errors: CounterVec,
+    pub embedding_dimensions: GaugeVec,
+    pub embedding_coalesced: CounterVec,
+    pub qdrant_duration: HistogramVec,
+    pub qdrant_errors: CounterVec,
+    pub memory_evictions: CounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            http_requests: CounterVec(Family::new(
+                "http_requests_total",
+                "HTTP requests handled, by route and status code.",
+                &["method", "route", "status"],
+            )),
+            http_duration: HistogramVec(Family::new(
+                "http_request_duration_seconds",
+                "HTTP request latency, by route.",
+                &["method", "route"],
+            )),
+            embedding_duration: HistogramVec(Family::new(
+                "embedding_request_duration_seconds",
+                "Latency of embedding calls per provider, including retries; cache hits excluded.",
+                &["provider", "operation"],
+            )),
+            embedding_errors: CounterVec(Family::new(
+                "embedding_errors_total",
+                "Failed embedding calls per provider, by error kind.",
+                &["provider", "error"],
+            )),
+            embedding_dimensions: GaugeVec(Family::new(
+                "embedding_dimensions",
+                "Size of the vectors most recently returned by each provider.",
+                &["provider"],
+            )),
+            embedding_coalesced: CounterVec(Family::new(
+                "embedding_coalesced_total",
+                "Embedding calls served by an identical request already in flight.",
+                &["provider"],
+            )),
+            qdrant_duration: HistogramVec(Family::new(
+                "qdrant_request_duration_seconds",
+                "Latency of Qdrant operations.",
+                &["operation"],
+            )),
+            qdrant_errors: CounterVec(Family::new(
+                "qdrant_errors_total",
+                "Failed Qdrant operations.",
+                &["operation"],
+            )),
+            memory_evictions: CounterVec(Family::new(
+                "memory_evictions_total",
+                "Entries evicted from the in-memory vector store to stay within capacity, by policy.",
+                &["policy"],
+            )),
+        }
+    }
+
+    /// Render every recorded metric in the Prometheus text format.
+    pub fn render(&self, out: &mut String) {
+        self.http_requests.render(out);
+        self.http_duration.render(out);
+        self.embedding_duration.render(out);
+        self.embedding_errors.render(out);
+        self.embedding_dimensions.render(out);
+        self.embedding_coalesced.render(out);
+        self.qdrant_duration.render(out);
+        self.qdrant_errors.render(out);
+        self.memory_evictions.render(out);
+    }
+}
+
+/// The process-wide metrics registry.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+// ---------------------------------------------------------------------------
+// HTTP middleware
+// ---------------------------------------------------------------------------
+
+/// Record the count and latency of every request to a matched route.
+///
+/// Install with `Router::route_layer` so the route template (e.g.
+/// `/memory/{id}`) is available as the `route` label instead of the raw path.
+pub async fn track_http(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |p| p.as_str().to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    let m = metrics();
+    m.http_duration
+        .observe(&[&method, &route], started.elapsed());
+    m.http_requests
+        .inc(&[&method, &route, response.status().as_str()]);
+    response
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_and_gauges_render_with_escaped_labels() {
+        let counter = CounterVec(Family::new("c_total", "A counter.", &["kind"]));
+        counter.inc(&["a\"b"]);
+        counter.inc(&["a\"b"]);
+        let gauge = GaugeVec(Family::new("g", "A gauge.", &[]));
+        gauge.set(&[], 1.5);
+
+        let mut out = String::new();
+        counter.render(&mut out);
+        gauge.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP c_total A counter.\n# TYPE c_total counter\nc_total{kind=\"a\\\"b\"} 2\n\
+             # HELP g A gauge.\n# TYPE g gauge\ng 1.5\n"
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = HistogramVec(Family::new("h_seconds", "A histogram.", &["op"]));
+        histogram.observe(&["x"], Duration::from_millis(3));
+        histogram.observe(&["x"], Duration::from_millis(30));
+        histogram.observe(&["x"], Duration::from_secs(60));
+
+        let mut out = String::new();
+        histogram.render(&mut out);
+        assert!(
+            out.contains("h_seconds_bucket{op=\"x\",le=\"0.005\"} 1\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains("h_seconds_bucket{op=\"x\",le=\"0.05\"} 2\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains("h_seconds_bucket{op=\"x\",le=\"30\"} 2\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains("h_seconds_bucket{op=\"x\",le=\"+Inf\"} 3\n"),
+            "{out}"
+        );
+        assert!(out.contains("h_seconds_count{op=\"x\"} 3\n"), "{out}");
+    }
+}
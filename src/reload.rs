@@ -0,0 +1,299 @@
+//! Runtime reload of the embedding provider registry.
+//!
+//! Handlers read the registry through a [`SharedRegistry`], taking an `Arc`
+//! snapshot per request.  A reload builds a complete new registry from the
+//! config file and swaps the pointer, so requests already in flight finish on
+//! the registry they started with and later requests see the new one.
+//!
+//! A reload is triggered by:
+//! - `SIGHUP`
+//! - a change to the config file, detected by polling its modification time
+//!   every `[server] config_poll_interval_ms` (0 disables polling)
+//! - `POST /admin/reload` (see `routes::admin_reload`)
+//!
+//! The new config goes through the same checks as at startup: it must parse
+//! and validate, every provider must build, the default provider must exist
+//! and, when Qdrant is configured, the default provider must produce vectors
+//! of the collection's size.  A rejected config is logged and reported to the
+//! caller; the running registry keeps serving.
+//!
+//! Only the `[embedding]` section is reloaded (except `[embedding.cache]`,
+//! whose cache is shared across reloads).  Other sections take effect on
+//! restart.  Circuit breakers and rate-limit budgets start fresh.
+
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::Config,
+    embedding::{cache::EmbeddingCache, ProviderRegistry},
+};
+
+// ---------------------------------------------------------------------------
+// SharedRegistry
+// ---------------------------------------------------------------------------
+
+/// The active [`ProviderRegistry`], replaceable at runtime.
+///
+/// Readers clone the inner `Arc` and release the lock immediately, so a swap
+/// never waits for in-flight embedding calls.
+pub struct SharedRegistry {
+    current: RwLock<Arc<ProviderRegistry>>,
+}
+
+impl SharedRegistry {
+    pub fn new(registry: ProviderRegistry) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(registry)),
+        }
+    }
+
+    /// A snapshot of the active registry.
+    pub fn load(&self) -> Arc<ProviderRegistry> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the active registry.
+    fn store(&self, registry: ProviderRegistry) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(registry);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Reloader
+// ---------------------------------------------------------------------------
+
+/// Summary of a successful reload, returned by `POST /admin/reload`.
+#[derive(Debug, Serialize)]
+pub struct ReloadSummary {
+    pub default_provider: String,
+    pub providers: Vec<String>,
+}
+
+/// Rebuilds the registry from the config file and swaps it in.
+pub struct Reloader {
+    config_path: String,
+    registry: Arc<SharedRegistry>,
+    /// The embedding cache built at startup; shared by every registry.
+    cache: Option<Arc<EmbeddingCache>>,
+    /// Vector size of the Qdrant collection, when Qdrant is configured.
+    vector_dimensions: Option<usize>,
+    /// Serialises reloads so two triggers cannot interleave.
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl Reloader {
+    pub fn new(
+        config_path: &str,
+        registry: Arc<SharedRegistry>,
+        cache: Option<Arc<EmbeddingCache>>,
+        vector_dimensions: Option<usize>,
+    ) -> Self {
+        Self {
+            config_path: config_path.to_string(),
+            registry,
+            cache,
+            vector_dimensions,
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Load and validate the config file and, if it is accepted, replace the
+    /// active registry.  `trigger` is only used for logging.
+    pub async fn reload(&self, trigger: &str) -> Result<ReloadSummary, String> {
+        let _guard = self.lock.lock().await;
+        match self.build().await {
+            Ok(registry) => {
+                let mut providers: Vec<String> = registry
+                    .provider_names()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+                providers.sort();
+                let summary = ReloadSummary {
+                    default_provider: registry.default_provider().to_string(),
+                    providers,
+                };
+                self.registry.store(registry);
+                info!(
+                    trigger,
+                    default = %summary.default_provider,
+                    providers = ?summary.providers,
+                    "Embedding provider registry reloaded"
+                );
+                Ok(summary)
+            }
+            Err(reason) => {
+                error!(trigger, %reason, "Configuration reload rejected; keeping the running registry");
+                Err(reason)
+            }
+        }
+    }
+
+    async fn build(&self) -> Result<ProviderRegistry, String> {
+        let config = Config::load(&self.config_path)
+            .map_err(|e| format!("failed to load '{}': {e}", self.config_path))?;
+        let mut registry = ProviderRegistry::from_config(&config.embedding, self.cache.clone())
+            .map_err(|e| e.to_string())?;
+        if let Some(dimensions) = self.vector_dimensions {
+            registry.check_vector_dimensions(dimensions).await?;
+        }
+        Ok(registry)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Triggers
+// ---------------------------------------------------------------------------
+
+/// Reload whenever the process receives `SIGHUP`.
+#[cfg(unix)]
+pub fn spawn_sighup_listener(reloader: Arc<Reloader>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!(error = %e, "Cannot listen for SIGHUP; reload on signal is disabled");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            let _ = reloader.reload("SIGHUP").await;
+        }
+    });
+}
+
+/// Modification time and size of a file; `None` when it cannot be read.
+fn file_stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Reload whenever the config file's modification time or size changes.
+///
+/// A rejected config is not retried until the file changes again.
+pub fn spawn_config_watcher(reloader: Arc<Reloader>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut last = file_stamp(&reloader.config_path);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let stamp = file_stamp(&reloader.config_path);
+            if stamp.is_none() || stamp == last {
+                continue;
+            }
+            last = stamp;
+            let _ = reloader.reload("config file change").await;
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashing_config(default: &str, dimensions: usize) -> String {
+        format!(
+            r#"
+            [server]
+            host = "127.0.0.1"
+            port = 8080
+
+            [embedding]
+            default_provider = "{default}"
+
+            [embedding.providers.hashing]
+            type = "hashing"
+            dimensions = {dimensions}
+            "#
+        )
+    }
+
+    fn setup(
+        contents: &str,
+        vector_dimensions: Option<usize>,
+    ) -> (std::path::PathBuf, Arc<SharedRegistry>, Reloader) {
+        let path = std::env::temp_dir().join(format!("reload-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        let config = Config::load(path.to_str().unwrap()).unwrap();
+        let registry = Arc::new(SharedRegistry::new(
+            ProviderRegistry::from_config(&config.embedding, None).unwrap(),
+        ));
+        let reloader = Reloader::new(
+            path.to_str().unwrap(),
+            registry.clone(),
+            None,
+            vector_dimensions,
+        );
+        (path, registry, reloader)
+    }
+
+    #[tokio::test]
+    async fn valid_config_replaces_registry_and_old_snapshots_stay_usable() {
+        let (path, shared, reloader) = setup(&hashing_config("hashing", 8), None);
+        let before = shared.load();
+
+        std::fs::write(
+            &path,
+            hashing_config("hashing", 8)
+                + r#"
+            [embedding.providers.second]
+            type = "hashing"
+            dimensions = 8
+            "#,
+        )
+        .unwrap();
+        let summary = reloader.reload("test").await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(summary.providers, vec!["hashing", "second"]);
+        assert!(shared.load().get(Some("second")).is_ok());
+        // A request holding the old snapshot still completes against it.
+        assert!(before.get(Some("second")).is_err());
+        assert_eq!(
+            before.get(None).unwrap().embed("hi").await.unwrap().len(),
+            8
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_config_keeps_running_registry() {
+        let (path, shared, reloader) = setup(&hashing_config("hashing", 8), Some(8));
+
+        // Unknown default provider.
+        std::fs::write(&path, hashing_config("missing", 8)).unwrap();
+        assert!(reloader.reload("test").await.is_err());
+
+        // Default provider no longer matches the Qdrant collection size.
+        std::fs::write(&path, hashing_config("hashing", 16)).unwrap();
+        let err = reloader.reload("test").await.unwrap_err();
+        assert!(err.contains("16"), "{err}");
+
+        // Unparseable file.
+        std::fs::write(&path, "[embedding").unwrap();
+        assert!(reloader.reload("test").await.is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let registry = shared.load();
+        assert_eq!(registry.default_provider(), "hashing");
+        assert_eq!(
+            registry.get(None).unwrap().embed("hi").await.unwrap().len(),
+            8
+        );
+    }
+}
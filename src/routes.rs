@@ -1,27 +1,51 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::{
-    embedding::{DynEmbeddingProvider, ProviderRegistry},
-    error::{EmbeddingError, SessionError, VectorStoreError},
-    memory::{MemoryStore, SearchResult as MemorySearchResult},
+    chunking::{Chunker, CHUNK_COUNT_KEY, CHUNK_INDEX_KEY, PARENT_ID_KEY, RESERVED_CHUNK_KEYS},
+    embedding::{
+        cache::CacheStats, embed_in_batches, resilience::BreakerStatus, DynEmbeddingProvider,
+        InputKind, ProviderRegistry,
+    },
+    error::{AdminError, EmbeddingError, SessionError, UsageError, VectorStoreError},
+    expiry::resolve_expiry,
+    filter::Filter,
+    memory::{MemoryEntry, MemoryStore, SearchOptions, SearchResult as MemorySearchResult},
+    metrics::{metrics, render_gauge},
+    reload::{Reloader, SharedRegistry},
     session_store::{Session, SessionStore},
-    vector_store::{QdrantStore, SearchResult as QdrantSearchResult, RESERVED_SESSION_ID_KEY_ERROR, RESERVED_TEXT_KEY_ERROR},
+    usage::{self, GroupBy, GroupUsage, UsageStore, UsageTotals},
+    vector_store::{
+        QdrantStore, SearchQuery, SearchResult as QdrantSearchResult, EXPIRES_AT_KEY,
+        RESERVED_CHUNK_KEY_ERROR, RESERVED_EXPIRES_AT_KEY_ERROR, RESERVED_SESSION_ID_KEY_ERROR,
+        RESERVED_TEXT_KEY_ERROR,
+    },
 };
 
 /// Shared application state passed to every handler.
 pub struct AppState {
-    pub registry: ProviderRegistry,
+    /// The active embedding providers; replaced on configuration reload.
+    /// Handlers take one snapshot per request with `registry.load()`.
+    pub registry: Arc<SharedRegistry>,
+    /// Rebuilds `registry` from the config file for `POST /admin/reload`.
+    pub reloader: Arc<Reloader>,
+    /// API key required in the `X-Api-Key` header for `/admin` endpoints.
+    /// When `None` the admin endpoints are disabled.
+    pub admin_api_key: Option<String>,
     /// Qdrant vector store – present only when `[qdrant]` is configured.
     pub vector_store: Option<Arc<QdrantStore>>,
     /// In-memory vector store for fast lookups without Qdrant.
@@ -31,25 +55,47 @@ pub struct AppState {
     /// Optional API key required in the `X-Api-Key` header for session endpoints.
     /// When `None` the endpoints are unauthenticated (suitable for private deployments).
     pub session_api_key: Option<String>,
+    /// Splits long texts before embedding – present only when `[chunking]`
+    /// is configured.
+    pub chunker: Option<Chunker>,
+    /// Embedding usage totals – present only when `[database]` is configured.
+    pub usage_store: Option<Arc<UsageStore>>,
 }
 
 impl AppState {
+    /// Return the active vector store.
+    fn store(&self) -> Result<&Arc<QdrantStore>, VectorStoreError> {
+        self.vector_store
+            .as_ref()
+            .ok_or(VectorStoreError::NotConfigured)
+    }
+
     /// Return the active vector store and the resolved embedding provider.
     ///
     /// `provider_override` comes from the per-request `?provider=` query
     /// parameter; when absent the registry default is used.
     fn resolve_store_and_provider<'a>(
         &'a self,
+        registry: &'a ProviderRegistry,
         provider_override: Option<&'a str>,
     ) -> Result<(&'a Arc<QdrantStore>, &'a str, &'a DynEmbeddingProvider), VectorStoreError> {
-        let store = self
-            .vector_store
-            .as_ref()
-            .ok_or(VectorStoreError::NotConfigured)?;
-        let provider_key = provider_override.unwrap_or(self.registry.default_provider());
-        let provider = self.registry.get(Some(provider_key))?;
+        let store = self.store()?;
+        let provider_key = provider_override.unwrap_or(registry.default_provider());
+        let provider = registry.get(Some(provider_key))?;
+        if let Some(reason) = registry.vector_incompatibility(provider_key) {
+            return Err(VectorStoreError::IncompatibleProvider(reason.to_string()));
+        }
         Ok((store, provider_key, provider))
     }
+
+    /// Split `text` with the configured chunker; a single chunk when chunking
+    /// is disabled or the text is short enough.
+    fn split_text(&self, text: &str) -> Vec<String> {
+        match &self.chunker {
+            Some(chunker) => chunker.split(text),
+            None => vec![text.to_string()],
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -63,12 +109,21 @@ pub struct HealthResponse {
     pub default_provider: String,
     pub vector_store: &'static str,
     pub session_store: &'static str,
+    /// Embedding cache hit/miss counters; omitted when caching is disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_cache: Option<CacheStats>,
+    /// Circuit-breaker state per provider (`closed`, `open` or `half_open`).
+    pub circuit_breakers: BTreeMap<String, BreakerStatus>,
+    /// Providers whose output size does not match the Qdrant collection, with
+    /// the reason; omitted when every probed provider is compatible.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub incompatible_providers: BTreeMap<String, String>,
 }
 
 /// Liveness probe – always returns 200 with available provider information.
 pub async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let mut names: Vec<String> = state
-        .registry
+    let registry = state.registry.load();
+    let mut names: Vec<String> = registry
         .provider_names()
         .into_iter()
         .map(str::to_string)
@@ -92,13 +147,70 @@ pub async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
         Json(HealthResponse {
             status: "ok",
             providers: names,
-            default_provider: state.registry.default_provider().to_string(),
+            default_provider: registry.default_provider().to_string(),
             vector_store,
             session_store,
+            embedding_cache: registry.cache_stats(),
+            circuit_breakers: registry.breaker_states(),
+            incompatible_providers: registry.vector_incompatibilities().clone(),
         }),
     )
 }
 
+// ---------------------------------------------------------------------------
+// GET /metrics
+// ---------------------------------------------------------------------------
+
+/// Prometheus metrics in the text exposition format.
+///
+/// Recorded metrics are followed by gauges read at scrape time: the number and
+/// size of in-memory store entries and, when the session store is configured, the
+/// SQLite pool's active and idle connections.
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut out = String::new();
+    metrics().render(&mut out);
+
+    render_gauge(
+        &mut out,
+        "memory_store_entries",
+        "Entries in the in-memory vector store.",
+        &[],
+        &[(&[], state.memory.len() as f64)],
+    );
+    render_gauge(
+        &mut out,
+        "memory_store_bytes",
+        "Approximate size of the in-memory vector store entries, as counted against max_bytes.",
+        &[],
+        &[(&[], state.memory.size_bytes() as f64)],
+    );
+    if let Some(store) = &state.session_store {
+        let pool = store.pool();
+        let idle = pool.num_idle() as f64;
+        let active = f64::from(pool.size()) - idle;
+        render_gauge(
+            &mut out,
+            "sqlite_pool_connections",
+            "Open SQLite connections in the session store pool, by state.",
+            &["state"],
+            &[(&["active"], active), (&["idle"], idle)],
+        );
+        render_gauge(
+            &mut out,
+            "sqlite_pool_max_connections",
+            "Maximum size of the session store pool.",
+            &[],
+            &[(&[], f64::from(pool.options().get_max_connections()))],
+        );
+    }
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        out,
+    )
+}
+
 // ---------------------------------------------------------------------------
 // Shared query parameter
 // ---------------------------------------------------------------------------
@@ -117,12 +229,18 @@ pub struct EmbedQuery {
 pub struct EmbedRequest {
     /// The text to embed.
     pub text: String,
+    /// Optional `"query"` or `"document"` hint for asymmetric models.
+    #[serde(default)]
+    pub input_type: InputKind,
 }
 
 #[derive(Serialize)]
 pub struct EmbedResponse {
-    /// Name of the provider that generated this embedding.
+    /// Name of the provider that was requested (or the default).
     pub provider: String,
+    /// Name of the provider that actually generated this embedding; differs
+    /// from `provider` when a fallback chain member answered.
+    pub served_by: String,
     /// Number of dimensions in the resulting vector.
     pub dimensions: usize,
     /// The embedding vector.
@@ -144,28 +262,108 @@ pub async fn embed(
         ));
     }
 
-    let provider_key = query.provider.as_deref().unwrap_or(state.registry.default_provider());
-    let provider = state.registry.get(Some(provider_key))?;
+    let registry = state.registry.load();
+    let provider_key = query
+        .provider
+        .as_deref()
+        .unwrap_or(registry.default_provider());
+    let provider = registry.get(Some(provider_key))?;
 
-    let embedding = provider.embed(&body.text).await?;
+    let (embedding, served_by) = provider
+        .embed_with_source(&body.text, body.input_type)
+        .await?;
     let dimensions = embedding.len();
 
     Ok((
         StatusCode::OK,
         Json(EmbedResponse {
             provider: provider_key.to_string(),
+            served_by: served_by.unwrap_or_else(|| provider_key.to_string()),
             dimensions,
             embedding,
         }),
     ))
 }
 
+// ---------------------------------------------------------------------------
+// POST /api/embed/batch
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct EmbedBatchRequest {
+    /// The texts to embed, in the order their embeddings should be returned.
+    pub texts: Vec<String>,
+    /// Optional `"query"` or `"document"` hint for asymmetric models.
+    #[serde(default)]
+    pub input_type: InputKind,
+}
+
+#[derive(Serialize)]
+pub struct EmbedBatchResponse {
+    /// Name of the provider that generated these embeddings.
+    pub provider: String,
+    /// The provider(s) that actually generated the vectors.
+    pub served_by: String,
+    /// Number of dimensions in each resulting vector.
+    pub dimensions: usize,
+    /// One embedding per input text, in input order.
+    pub embeddings: Vec<Vec<f32>>,
+}
+
+/// Generate embeddings for a list of texts using the selected provider.
+///
+/// The input is split into upstream requests no larger than the provider's
+/// maximum batch size; results are returned in input order.
+pub async fn embed_batch(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EmbedQuery>,
+    Json(body): Json<EmbedBatchRequest>,
+) -> Result<impl IntoResponse, EmbeddingError> {
+    if body.texts.is_empty() {
+        return Err(EmbeddingError::BadRequest(EMPTY_TEXTS_ERROR.to_string()));
+    }
+    if body.texts.len() > MAX_BATCH_TEXTS {
+        return Err(EmbeddingError::BadRequest(format!(
+            "Field 'texts' must not contain more than {MAX_BATCH_TEXTS} entries"
+        )));
+    }
+    if let Some(index) = body.texts.iter().position(String::is_empty) {
+        return Err(EmbeddingError::BadRequest(format!(
+            "Field 'texts[{index}]' must not be empty"
+        )));
+    }
+
+    let registry = state.registry.load();
+    let provider_key = query
+        .provider
+        .as_deref()
+        .unwrap_or(registry.default_provider());
+    let provider = registry.get(Some(provider_key))?;
+
+    let (embeddings, served_by) =
+        embed_in_batches(provider.as_ref(), &body.texts, body.input_type).await?;
+    let dimensions = embeddings.first().map_or(0, Vec::len);
+
+    Ok((
+        StatusCode::OK,
+        Json(EmbedBatchResponse {
+            provider: provider_key.to_string(),
+            served_by: served_by.unwrap_or_else(|| provider_key.to_string()),
+            dimensions,
+            embeddings,
+        }),
+    ))
+}
+
 // ---------------------------------------------------------------------------
 // Constants
 // ---------------------------------------------------------------------------
 
 const DEFAULT_SEARCH_LIMIT: u32 = 5;
+/// Upper bound on the number of texts accepted by `/api/embed/batch`.
+const MAX_BATCH_TEXTS: usize = 2048;
 const EMPTY_TEXT_ERROR: &str = "Field 'text' must not be empty";
+const EMPTY_TEXTS_ERROR: &str = "Field 'texts' must not be empty";
 const EMPTY_SEARCH_QUERY_ERROR: &str = "Query parameter 'q' must not be empty";
 
 // ---------------------------------------------------------------------------
@@ -182,6 +380,12 @@ fn require_non_empty_text(text: &str) -> Result<(), VectorStoreError> {
     }
 }
 
+fn uses_reserved_chunk_key<V>(metadata: &HashMap<String, V>) -> bool {
+    RESERVED_CHUNK_KEYS
+        .iter()
+        .any(|key| metadata.contains_key(*key))
+}
+
 // ---------------------------------------------------------------------------
 // POST /api/memory  – store an embedding in Qdrant
 // ---------------------------------------------------------------------------
@@ -197,22 +401,35 @@ pub struct StoreMemoryQdrantRequest {
     /// When provided and a session store is configured, the session must exist.
     pub session_id: Option<String>,
     /// Arbitrary metadata stored alongside the embedding in Qdrant.
-    /// The keys `"text"` and `"session_id"` are reserved and must not be used.
+    /// The keys `"text"`, `"session_id"`, `"expires_at"`, `"parent_id"`,
+    /// `"chunk_index"` and `"chunk_count"` are reserved and must not be used.
     #[serde(default)]
     pub metadata: HashMap<String, Value>,
+    /// Seconds until the memory expires; mutually exclusive with `expires_at`.
+    pub ttl_seconds: Option<u64>,
+    /// RFC 3339 time at which the memory expires.
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize)]
 pub struct StoreMemoryQdrantResponse {
-    /// The ID of the stored point (UUID string).
+    /// The ID of the stored point (UUID string).  For chunked text this is
+    /// the `parent_id` shared by all chunk points.
     pub id: String,
+    /// Number of points the text was split into (1 when not chunked).
+    pub chunks: usize,
     /// Dimensionality of the stored vector.
     pub dimensions: usize,
-    /// The embedding provider that was used.
+    /// The embedding provider that was requested (or the default).
     pub provider: String,
+    /// The provider that actually generated the stored vector.
+    pub served_by: String,
     /// The session ID associated with this memory entry, if any.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
+    /// When the memory expires, if it was stored with a TTL or expiry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 /// Embed `text` and store it in the Qdrant vector store.
@@ -221,6 +438,9 @@ pub struct StoreMemoryQdrantResponse {
 /// embedding provider generates the vector.
 ///
 /// Optionally supply a `session_id` to link this entry to an existing session.
+///
+/// When chunking is configured, long text is split and stored as one point
+/// per chunk; all chunks share the returned ID as their `parent_id`.
 pub async fn store_memory_qdrant(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -238,6 +458,18 @@ pub async fn store_memory_qdrant(
             RESERVED_SESSION_ID_KEY_ERROR.to_string(),
         ));
     }
+    if body.metadata.contains_key(EXPIRES_AT_KEY) {
+        return Err(VectorStoreError::BadRequest(
+            RESERVED_EXPIRES_AT_KEY_ERROR.to_string(),
+        ));
+    }
+    if uses_reserved_chunk_key(&body.metadata) {
+        return Err(VectorStoreError::BadRequest(
+            RESERVED_CHUNK_KEY_ERROR.to_string(),
+        ));
+    }
+    let expires_at =
+        resolve_expiry(body.ttl_seconds, body.expires_at).map_err(VectorStoreError::BadRequest)?;
 
     // When a session_id is supplied, require the same API key auth used by the
     // session endpoints to prevent unauthenticated callers from associating
@@ -260,22 +492,45 @@ pub async fn store_memory_qdrant(
             .await
             .map_err(|e| VectorStoreError::InternalDependencyError(format!("Session store error: {e}")))?
             .ok_or_else(|| VectorStoreError::BadRequest(format!("Session '{sid}' not found")))?;
+        usage::set_session(sid);
     }
 
+    let registry = state.registry.load();
     let (store, provider_key, provider) =
-        state.resolve_store_and_provider(query.provider.as_deref())?;
-
-    let embedding = provider.embed(&body.text).await?;
-    let dimensions = embedding.len();
+        state.resolve_store_and_provider(&registry, query.provider.as_deref())?;
 
     let mut metadata = body.metadata;
     if let Some(ref sid) = body.session_id {
         metadata.insert("session_id".to_string(), Value::String(sid.clone()));
     }
+    if let Some(at) = expires_at {
+        metadata.insert(EXPIRES_AT_KEY.to_string(), Value::from(at.timestamp()));
+    }
 
-    let id = store
-        .upsert(body.id, embedding, body.text, metadata)
-        .await?;
+    let chunks = state.split_text(&body.text);
+    let chunk_count = chunks.len();
+    let (id, dimensions, served_by) = if chunk_count > 1 {
+        let (embeddings, served_by) =
+            embed_in_batches(provider.as_ref(), &chunks, InputKind::Document).await?;
+        let dimensions = embeddings.first().map_or(0, Vec::len);
+        let id = store
+            .upsert_chunks(
+                body.id,
+                chunks.into_iter().zip(embeddings).collect(),
+                metadata,
+            )
+            .await?;
+        (id, dimensions, served_by)
+    } else {
+        let (embedding, served_by) = provider
+            .embed_with_source(&body.text, InputKind::Document)
+            .await?;
+        let dimensions = embedding.len();
+        let id = store
+            .upsert(body.id, embedding, body.text, metadata)
+            .await?;
+        (id, dimensions, served_by)
+    };
 
     // Best-effort: bump updated_at on the session so it reflects last activity.
     if let (Some(ref sid), Some(ref session_store)) = (&body.session_id, &state.session_store) {
@@ -286,9 +541,12 @@ pub async fn store_memory_qdrant(
         StatusCode::OK,
         Json(StoreMemoryQdrantResponse {
             id,
+            chunks: chunk_count,
             dimensions,
             provider: provider_key.to_string(),
+            served_by: served_by.unwrap_or_else(|| provider_key.to_string()),
             session_id: body.session_id,
+            expires_at,
         }),
     ))
 }
@@ -297,22 +555,47 @@ pub async fn store_memory_qdrant(
 // POST /api/search  – query Qdrant by vector similarity
 // ---------------------------------------------------------------------------
 
+/// Which stored vectors `/api/search` ranks by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Dense embedding similarity.
+    #[default]
+    Dense,
+    /// Sparse lexical vectors (requires `[qdrant.sparse]`).
+    Sparse,
+    /// Both, fused with reciprocal rank fusion (requires `[qdrant.sparse]`).
+    Hybrid,
+}
+
 #[derive(Deserialize)]
 pub struct SearchMemoryQdrantRequest {
     /// The query text to embed for the similarity search.
     pub text: String,
     /// Maximum number of results to return (default: 5).
     pub limit: Option<u32>,
-    /// Only return results with a cosine similarity score ≥ this value.
+    /// Only return results with a score ≥ this value.  In `hybrid` mode it
+    /// applies to the dense similarity before fusion.
     pub score_threshold: Option<f32>,
+    /// Return at most one hit per chunked memory, reported under its
+    /// `parent_id` (default: false).
+    #[serde(default)]
+    pub collapse: bool,
+    /// `dense` (default), `sparse` or `hybrid`.
+    #[serde(default)]
+    pub mode: SearchMode,
 }
 
 #[derive(Serialize)]
 pub struct SearchMemoryQdrantResponse {
-    /// Ordered list of nearest-neighbour results (most similar first).
+    /// Ordered list of results (best match first).
     pub results: Vec<QdrantSearchResult>,
-    /// The embedding provider that was used for the query vector.
+    /// The embedding provider that was requested for the query vector.
     pub provider: String,
+    /// The provider that actually generated the query vector; absent in
+    /// `sparse` mode, which does not embed the query.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub served_by: Option<String>,
 }
 
 /// Embed `text` and return the nearest memories from the Qdrant vector store.
@@ -320,6 +603,10 @@ pub struct SearchMemoryQdrantResponse {
 /// Use the optional `?provider=<name>` query parameter to choose which
 /// embedding provider generates the query vector (should match the provider
 /// used during storage for best results).
+///
+/// `mode` selects dense, sparse (lexical) or hybrid ranking; the latter two
+/// require `[qdrant.sparse]`.  `sparse` mode does not embed the query, so it
+/// neither resolves the provider nor checks its compatibility.
 pub async fn search_memory_qdrant(
     State(state): State<Arc<AppState>>,
     Query(query): Query<EmbedQuery>,
@@ -327,19 +614,56 @@ pub async fn search_memory_qdrant(
 ) -> Result<impl IntoResponse, VectorStoreError> {
     require_non_empty_text(&body.text)?;
 
-    let (store, provider_key, provider) =
-        state.resolve_store_and_provider(query.provider.as_deref())?;
-
-    let embedding = provider.embed(&body.text).await?;
+    let registry = state.registry.load();
+    let (store, provider_key, search_query, served_by) = match body.mode {
+        SearchMode::Sparse => {
+            let provider_key = query
+                .provider
+                .as_deref()
+                .unwrap_or(registry.default_provider());
+            (
+                state.store()?,
+                provider_key,
+                SearchQuery::Sparse(&body.text),
+                None,
+            )
+        }
+        mode => {
+            let (store, provider_key, provider) =
+                state.resolve_store_and_provider(&registry, query.provider.as_deref())?;
+            let (vector, served_by) = provider
+                .embed_with_source(&body.text, InputKind::Query)
+                .await?;
+            let served_by = served_by.unwrap_or_else(|| provider_key.to_string());
+            let search_query = if mode == SearchMode::Hybrid {
+                SearchQuery::Hybrid {
+                    vector,
+                    text: &body.text,
+                }
+            } else {
+                SearchQuery::Dense(vector)
+            };
+            (store, provider_key, search_query, Some(served_by))
+        }
+    };
     let limit = body.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
 
-    let results = store.search(embedding, limit, body.score_threshold).await?;
+    let results = if body.collapse {
+        store
+            .search_collapsed(search_query, limit, body.score_threshold)
+            .await?
+    } else {
+        store
+            .search(search_query, limit, body.score_threshold)
+            .await?
+    };
 
     Ok((
         StatusCode::OK,
         Json(SearchMemoryQdrantResponse {
             results,
             provider: provider_key.to_string(),
+            served_by,
         }),
     ))
 }
@@ -363,17 +687,30 @@ pub struct StoreMemoryRequest {
     pub metadata: HashMap<String, String>,
     /// Optional session tag for grouping related memories.
     pub session: Option<String>,
+    /// Seconds until the memory expires; mutually exclusive with `expires_at`.
+    pub ttl_seconds: Option<u64>,
+    /// RFC 3339 time at which the memory expires.
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize)]
 pub struct StoreMemoryResponse {
     pub id: String,
+    /// Number of entries the text was split into (1 when not chunked).
+    pub chunks: usize,
+    /// When the memory expires, if it was stored with a TTL or expiry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 /// Store a new memory entry.
 ///
 /// The text is embedded via the selected provider and stored alongside the
 /// supplied metadata.  Returns the generated memory ID.
+///
+/// When chunking is configured, long text is stored as one entry per chunk
+/// and the returned ID is the `parent_id` shared by all of them; deleting
+/// that ID removes every chunk.
 pub async fn store_memory(
     State(state): State<Arc<AppState>>,
     Query(query): Query<StoreMemoryQuery>,
@@ -385,23 +722,72 @@ pub async fn store_memory(
         ));
     }
 
+    let registry = state.registry.load();
     let provider_key = query
         .provider
         .as_deref()
-        .unwrap_or(state.registry.default_provider());
-    let provider = state.registry.get(Some(provider_key))?;
-
-    let embedding = provider.embed(&body.text).await?;
+        .unwrap_or(registry.default_provider());
+    if uses_reserved_chunk_key(&body.metadata) {
+        return Err(EmbeddingError::BadRequest(
+            RESERVED_CHUNK_KEY_ERROR.to_string(),
+        ));
+    }
+    let expires_at =
+        resolve_expiry(body.ttl_seconds, body.expires_at).map_err(EmbeddingError::BadRequest)?;
+    let provider = registry.get(Some(provider_key))?;
+
+    let chunks = state.split_text(&body.text);
+    let chunk_count = chunks.len();
+    if chunk_count == 1 {
+        let embedding = provider.embed_as(&body.text, InputKind::Document).await?;
+        let id = state
+            .memory
+            .store(
+                body.text,
+                body.metadata,
+                body.session,
+                embedding,
+                expires_at,
+            )
+            .await?;
+        return Ok((
+            StatusCode::CREATED,
+            Json(StoreMemoryResponse {
+                id,
+                chunks: 1,
+                expires_at,
+            }),
+        ));
+    }
 
-    let id = state
-        .memory
-        .store(body.text, body.metadata, body.session, embedding);
+    let (embeddings, _) = embed_in_batches(provider.as_ref(), &chunks, InputKind::Document).await?;
+    let parent_id = Uuid::new_v4().to_string();
+    let entries = chunks
+        .into_iter()
+        .zip(embeddings)
+        .enumerate()
+        .map(|(index, (text, embedding))| {
+            let mut metadata = body.metadata.clone();
+            metadata.insert(PARENT_ID_KEY.to_string(), parent_id.clone());
+            metadata.insert(CHUNK_INDEX_KEY.to_string(), index.to_string());
+            metadata.insert(CHUNK_COUNT_KEY.to_string(), chunk_count.to_string());
+            MemoryEntry::new(text, metadata, body.session.clone(), embedding, expires_at)
+        })
+        .collect();
+    state.memory.insert(entries).await?;
 
-    Ok((StatusCode::CREATED, Json(StoreMemoryResponse { id })))
+    Ok((
+        StatusCode::CREATED,
+        Json(StoreMemoryResponse {
+            id: parent_id,
+            chunks: chunk_count,
+            expires_at,
+        }),
+    ))
 }
 
 // ---------------------------------------------------------------------------
-// GET /memory/search
+// GET|POST /memory/search
 // ---------------------------------------------------------------------------
 
 #[derive(Deserialize)]
@@ -414,6 +800,31 @@ pub struct SearchMemoryQuery {
     pub session: Option<String>,
     /// Optionally override the configured default provider.
     pub provider: Option<String>,
+    /// Return at most one hit per chunked memory, reported under its
+    /// `parent_id` (default: false).
+    #[serde(default)]
+    pub collapse: bool,
+    /// Compare against every stored entry instead of using the approximate
+    /// index (default: false).
+    #[serde(default)]
+    pub exact: bool,
+    /// Metadata filter expression as JSON; see [`crate::filter`].
+    pub filter: Option<String>,
+}
+
+/// Body of `POST /memory/search`: the query parameters of the `GET` form,
+/// with the filter as a JSON object rather than an encoded string.
+#[derive(Deserialize)]
+pub struct SearchMemoryRequest {
+    pub q: String,
+    pub limit: Option<usize>,
+    pub session: Option<String>,
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub collapse: bool,
+    #[serde(default)]
+    pub exact: bool,
+    pub filter: Option<Filter>,
 }
 
 #[derive(Serialize)]
@@ -430,24 +841,63 @@ pub async fn search_memory(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchMemoryQuery>,
 ) -> Result<impl IntoResponse, EmbeddingError> {
-    if query.q.is_empty() {
+    let filter = query
+        .filter
+        .as_deref()
+        .map(Filter::parse)
+        .transpose()
+        .map_err(EmbeddingError::BadRequest)?;
+    let request = SearchMemoryRequest {
+        q: query.q,
+        limit: query.limit,
+        session: query.session,
+        provider: query.provider,
+        collapse: query.collapse,
+        exact: query.exact,
+        filter,
+    };
+    run_memory_search(&state, request).await
+}
+
+/// `POST` form of [`search_memory`], for filters too large or awkward to
+/// URL-encode.
+pub async fn search_memory_post(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<SearchMemoryRequest>,
+) -> Result<impl IntoResponse, EmbeddingError> {
+    run_memory_search(&state, body).await
+}
+
+async fn run_memory_search(
+    state: &AppState,
+    request: SearchMemoryRequest,
+) -> Result<impl IntoResponse, EmbeddingError> {
+    if request.q.is_empty() {
         return Err(EmbeddingError::BadRequest(
             EMPTY_SEARCH_QUERY_ERROR.to_string(),
         ));
     }
 
-    let provider_key = query
+    let registry = state.registry.load();
+    let provider_key = request
         .provider
         .as_deref()
-        .unwrap_or(state.registry.default_provider());
-    let provider = state.registry.get(Some(provider_key))?;
-
-    let query_embedding = provider.embed(&query.q).await?;
-
-    let limit = query.limit.unwrap_or(10);
-    let results = state
-        .memory
-        .search(&query_embedding, limit, query.session.as_deref());
+        .unwrap_or(registry.default_provider());
+    let provider = registry.get(Some(provider_key))?;
+
+    let query_embedding = provider.embed_as(&request.q, InputKind::Query).await?;
+
+    let limit = request.limit.unwrap_or(10);
+    let results = state.memory.search(
+        &query_embedding,
+        &SearchOptions {
+            limit,
+            session: request.session.as_deref(),
+            filter: request.filter.as_ref(),
+            collapse_chunks: request.collapse,
+            exact: request.exact,
+        },
+    );
 
     Ok((StatusCode::OK, Json(SearchMemoryResponse { results })))
 }
@@ -461,7 +911,7 @@ pub async fn delete_memory(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, EmbeddingError> {
-    if state.memory.delete(&id) {
+    if state.memory.delete(&id).await? {
         info!(memory_id = %id, "Memory entry deleted");
         Ok(StatusCode::NO_CONTENT)
     } else {
@@ -490,7 +940,10 @@ fn validate_session_auth(headers: &HeaderMap, state: &AppState) -> Result<(), Se
             .get("x-api-key")
             .and_then(|v| v.to_str().ok());
         match provided {
-            Some(key) if constant_time_eq(key, expected) => Ok(()),
+            Some(key) if constant_time_eq(key, expected) => {
+                usage::set_api_key(key);
+                Ok(())
+            }
             Some(_) => Err(SessionError::Unauthorized("Invalid API key".to_string())),
             None => Err(SessionError::Unauthorized(
                 "Missing X-Api-Key header".to_string(),
@@ -583,3 +1036,125 @@ pub async fn get_session(
 
     Ok((StatusCode::OK, Json(session)))
 }
+
+// ---------------------------------------------------------------------------
+// GET /api/usage  – embedding token usage and cost
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    /// RFC 3339 start of the range (default: the beginning of records).
+    pub from: Option<String>,
+    /// RFC 3339 end of the range, exclusive (default: now).
+    pub to: Option<String>,
+    /// `provider` (default), `session` or `key`.
+    #[serde(default)]
+    pub group_by: GroupBy,
+}
+
+#[derive(Serialize)]
+pub struct UsageResponse {
+    pub from: String,
+    pub to: String,
+    pub group_by: GroupBy,
+    /// Totals per group, highest cost first.
+    pub groups: Vec<GroupUsage>,
+    /// Sum over all groups.
+    pub total: UsageTotals,
+}
+
+fn parse_usage_time(name: &str, value: &str) -> Result<DateTime<Utc>, UsageError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| UsageError::BadRequest(format!("'{name}' is not an RFC 3339 timestamp: {e}")))
+}
+
+/// Report embedding tokens and cost between `from` and `to`, grouped by
+/// provider, session or API key.
+///
+/// Usage is recorded by the hour, so every hour that starts in the range
+/// (with `from` rounded down to the hour) is counted in full.
+///
+/// The report spans every caller, so it requires `ADMIN_API_KEY` like the
+/// `/admin` endpoints.
+pub async fn get_usage(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<UsageQuery>,
+) -> Result<impl IntoResponse, UsageError> {
+    validate_admin_auth(&headers, &state).map_err(|e| match e {
+        AdminError::Unauthorized(message) => UsageError::Unauthorized(message),
+        _ => UsageError::Disabled,
+    })?;
+    let store = state
+        .usage_store
+        .as_ref()
+        .ok_or(UsageError::NotConfigured)?;
+
+    let from = match &query.from {
+        Some(from) => parse_usage_time("from", from)?,
+        None => DateTime::UNIX_EPOCH,
+    };
+    let to = match &query.to {
+        Some(to) => parse_usage_time("to", to)?,
+        None => Utc::now(),
+    };
+    if from > to {
+        return Err(UsageError::BadRequest(
+            "'from' is later than 'to'".to_string(),
+        ));
+    }
+
+    let groups = store.report(from, to, query.group_by).await?;
+    let mut total = UsageTotals::default();
+    for group in &groups {
+        total += group.totals;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(UsageResponse {
+            from: from.to_rfc3339(),
+            to: to.to_rfc3339(),
+            group_by: query.group_by,
+            groups,
+            total,
+        }),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/reload  – reload embedding providers from the config file
+// ---------------------------------------------------------------------------
+
+/// Validate the `X-Api-Key` header against `ADMIN_API_KEY`.
+///
+/// Unlike the session endpoints, admin endpoints are closed when no key is
+/// configured.
+fn validate_admin_auth(headers: &HeaderMap, state: &AppState) -> Result<(), AdminError> {
+    let expected = state.admin_api_key.as_ref().ok_or(AdminError::Disabled)?;
+    match headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        Some(key) if constant_time_eq(key, expected) => Ok(()),
+        Some(_) => Err(AdminError::Unauthorized("Invalid API key".to_string())),
+        None => Err(AdminError::Unauthorized(
+            "Missing X-Api-Key header".to_string(),
+        )),
+    }
+}
+
+/// Re-read the config file and swap in a new embedding provider registry.
+///
+/// Returns the new default provider and provider list, or 422 with the reason
+/// when the config is rejected; the running providers are unaffected then.
+pub async fn admin_reload(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AdminError> {
+    validate_admin_auth(&headers, &state)?;
+    let summary = state
+        .reloader
+        .reload("POST /admin/reload")
+        .await
+        .map_err(AdminError::ReloadRejected)?;
+    Ok((StatusCode::OK, Json(summary)))
+}
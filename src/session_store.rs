@@ -71,6 +71,12 @@ impl SessionStore {
         Ok(Self { pool })
     }
 
+    /// The underlying connection pool, shared with other SQLite-backed
+    /// components such as the persistent embedding cache.
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
     // -----------------------------------------------------------------------
     // Write
     // -----------------------------------------------------------------------
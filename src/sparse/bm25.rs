@@ -0,0 +1,333 @@
+//! Built-in BM25 sparse encoder.
+//!
+//! Text is lower-cased and split into runs of letters, digits and `_`, so
+//! `ERR_CONN_RESET` stays one term while `HTTP-503` gives `http` and `503`.
+//! Each term is hashed with 32-bit FNV-1a to its index.
+//!
+//! BM25 is split between the two sides of the dot product Qdrant computes:
+//! - documents carry the saturated term frequency
+//!   `tf·(k1+1) / (tf + k1·(1 − b + b·len/avg_len))`
+//! - queries carry each term's inverse document frequency
+//!   `ln(1 + (N − df + 0.5) / (df + 0.5))`
+//!
+//! so a query scores every stored text by its BM25 relevance.
+//!
+//! Corpus statistics (document count, total length and per-term document
+//! frequency) are updated for every text once it has been written to Qdrant
+//! and, when `[database]` is configured, persisted in SQLite.  They are
+//! approximate: deleting or replacing a memory does not remove its counts,
+//! and document weights use the average length at the time the document was
+//! stored.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::{config::SparseConfig, error::EmbeddingError};
+
+use super::{SparseEncoder, SparseVector};
+
+#[derive(Default)]
+struct CorpusStats {
+    doc_count: u64,
+    total_length: u64,
+    doc_freq: HashMap<u32, u64>,
+}
+
+impl CorpusStats {
+    fn avg_length(&self) -> f32 {
+        if self.doc_count == 0 {
+            return 1.0;
+        }
+        (self.total_length as f32 / self.doc_count as f32).max(1.0)
+    }
+
+    fn idf(&self, term: u32) -> f32 {
+        let n = self.doc_count as f32;
+        let df = self.doc_freq.get(&term).copied().unwrap_or(0) as f32;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+}
+
+pub struct Bm25Encoder {
+    k1: f32,
+    b: f32,
+    stats: Mutex<CorpusStats>,
+    pool: Option<SqlitePool>,
+}
+
+impl Bm25Encoder {
+    /// Create an encoder, loading corpus statistics from `pool` when given.
+    pub async fn load(
+        cfg: &SparseConfig,
+        pool: Option<SqlitePool>,
+    ) -> Result<Self, EmbeddingError> {
+        let mut stats = CorpusStats::default();
+        if let Some(pool) = &pool {
+            let load_error = |e: sqlx::Error| {
+                EmbeddingError::ConfigError(format!("Failed to load BM25 statistics: {e}"))
+            };
+
+            let corpus: Option<(i64, i64)> =
+                sqlx::query_as("SELECT doc_count, total_length FROM bm25_corpus WHERE id = 1")
+                    .fetch_optional(pool)
+                    .await
+                    .map_err(load_error)?;
+            if let Some((doc_count, total_length)) = corpus {
+                stats.doc_count = doc_count as u64;
+                stats.total_length = total_length as u64;
+            }
+
+            let terms: Vec<(i64, i64)> = sqlx::query_as("SELECT term, doc_freq FROM bm25_terms")
+                .fetch_all(pool)
+                .await
+                .map_err(load_error)?;
+            stats.doc_freq = terms
+                .into_iter()
+                .map(|(term, df)| (term as u32, df as u64))
+                .collect();
+        }
+
+        Ok(Self {
+            k1: cfg.k1,
+            b: cfg.b,
+            stats: Mutex::new(stats),
+            pool,
+        })
+    }
+
+    /// BM25 term-frequency weights of one document.
+    fn document_vector(&self, terms: &[u32], avg_length: f32) -> SparseVector {
+        let mut tf: BTreeMap<u32, f32> = BTreeMap::new();
+        for &term in terms {
+            *tf.entry(term).or_default() += 1.0;
+        }
+        let length_norm = 1.0 - self.b + self.b * terms.len() as f32 / avg_length;
+        let (indices, values) = tf
+            .into_iter()
+            .map(|(term, tf)| (term, tf * (self.k1 + 1.0) / (tf + self.k1 * length_norm)))
+            .unzip();
+        SparseVector { indices, values }
+    }
+
+    /// Write the counts of newly stored documents to SQLite.
+    ///
+    /// Failures are logged and otherwise ignored; the in-memory statistics
+    /// stay correct for this process.
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        doc_count: u64,
+        total_length: u64,
+        terms: &BTreeMap<u32, u64>,
+    ) {
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            sqlx::query(
+                "INSERT INTO bm25_corpus (id, doc_count, total_length) VALUES (1, ?, ?) \
+                 ON CONFLICT(id) DO UPDATE SET doc_count = doc_count + excluded.doc_count, \
+                 total_length = total_length + excluded.total_length",
+            )
+            .bind(doc_count as i64)
+            .bind(total_length as i64)
+            .execute(&mut *tx)
+            .await?;
+            for (term, count) in terms {
+                sqlx::query(
+                    "INSERT INTO bm25_terms (term, doc_freq) VALUES (?, ?) \
+                     ON CONFLICT(term) DO UPDATE SET doc_freq = doc_freq + excluded.doc_freq",
+                )
+                .bind(i64::from(*term))
+                .bind(*count as i64)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(error = %e, "Failed to persist BM25 statistics");
+        }
+    }
+}
+
+#[async_trait]
+impl SparseEncoder for Bm25Encoder {
+    async fn encode_documents(
+        &self,
+        texts: &[String],
+    ) -> Result<Vec<SparseVector>, EmbeddingError> {
+        let documents: Vec<Vec<u32>> = texts.iter().map(|t| term_ids(t)).collect();
+
+        // Weigh lengths against the average the corpus will have once these
+        // documents are recorded.
+        let new_length: u64 = documents.iter().map(|terms| terms.len() as u64).sum();
+        let avg_length = {
+            let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+            CorpusStats {
+                doc_count: stats.doc_count + documents.len() as u64,
+                total_length: stats.total_length + new_length,
+                doc_freq: HashMap::new(),
+            }
+            .avg_length()
+        };
+        Ok(documents
+            .iter()
+            .map(|terms| self.document_vector(terms, avg_length))
+            .collect())
+    }
+
+    async fn record_documents(&self, texts: &[String]) {
+        let documents: Vec<Vec<u32>> = texts.iter().map(|t| term_ids(t)).collect();
+
+        // Counts added by this call, for persistence.
+        let mut new_terms: BTreeMap<u32, u64> = BTreeMap::new();
+        let mut new_length = 0;
+        {
+            let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+            for terms in &documents {
+                new_length += terms.len() as u64;
+                for term in terms.iter().copied().collect::<BTreeSet<_>>() {
+                    *stats.doc_freq.entry(term).or_default() += 1;
+                    *new_terms.entry(term).or_default() += 1;
+                }
+            }
+            stats.doc_count += documents.len() as u64;
+            stats.total_length += new_length;
+        }
+
+        if let Some(pool) = &self.pool {
+            self.persist(pool, documents.len() as u64, new_length, &new_terms)
+                .await;
+        }
+    }
+
+    async fn encode_query(&self, text: &str) -> Result<SparseVector, EmbeddingError> {
+        let terms: BTreeSet<u32> = term_ids(text).into_iter().collect();
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(SparseVector {
+            values: terms.iter().map(|&term| stats.idf(term)).collect(),
+            indices: terms.into_iter().collect(),
+        })
+    }
+}
+
+/// Lower-cased runs of letters, digits and `_`.
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn term_ids(text: &str) -> Vec<u32> {
+    tokenize(text)
+        .map(|token| fnv1a32(token.as_bytes()))
+        .collect()
+}
+
+/// 32-bit FNV-1a; specified, so term indices stay stable across builds.
+fn fnv1a32(bytes: &[u8]) -> u32 {
+    const OFFSET_BASIS: u32 = 0x811c_9dc5;
+    const PRIME: u32 = 0x0100_0193;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &b| {
+        (hash ^ u32::from(b)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::SessionStore;
+
+    fn make_config() -> SparseConfig {
+        toml::from_str(r#"type = "bm25""#).unwrap()
+    }
+
+    fn dot(a: &SparseVector, b: &SparseVector) -> f32 {
+        a.indices
+            .iter()
+            .zip(&a.values)
+            .filter_map(|(i, v)| {
+                b.indices
+                    .iter()
+                    .position(|j| j == i)
+                    .map(|p| v * b.values[p])
+            })
+            .sum()
+    }
+
+    #[test]
+    fn tokenizer_keeps_identifiers_whole() {
+        let tokens: Vec<String> = tokenize("Fix ERR_CONN_RESET in HTTP-503 handler").collect();
+        assert_eq!(
+            tokens,
+            vec!["fix", "err_conn_reset", "in", "http", "503", "handler"]
+        );
+        // Pin the hash so an accidental change does not orphan stored vectors.
+        assert_eq!(fnv1a32(b"fix"), 0xb2e4_8278);
+    }
+
+    #[tokio::test]
+    async fn exact_rare_terms_outscore_common_ones() {
+        let encoder = Bm25Encoder::load(&make_config(), None).await.unwrap();
+        let texts: Vec<String> = [
+            "the deploy failed with ERR_CONN_RESET",
+            "the deploy failed with a timeout",
+            "the deploy succeeded",
+        ]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+        let documents = encoder.encode_documents(&texts).await.unwrap();
+        encoder.record_documents(&texts).await;
+
+        let query = encoder.encode_query("deploy ERR_CONN_RESET").await.unwrap();
+        let scores: Vec<f32> = documents.iter().map(|d| dot(&query, d)).collect();
+        assert!(scores[0] > scores[1] && scores[0] > scores[2], "{scores:?}");
+
+        // A term in every document weighs less than one in a single document.
+        let common = encoder.encode_query("the").await.unwrap();
+        let rare = encoder.encode_query("timeout").await.unwrap();
+        assert!(common.values[0] < rare.values[0]);
+    }
+
+    #[tokio::test]
+    async fn statistics_are_persisted() {
+        let store = SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        let pool = store.pool().clone();
+
+        let first = Bm25Encoder::load(&make_config(), Some(pool.clone()))
+            .await
+            .unwrap();
+        first
+            .record_documents(&["alpha beta".to_string(), "alpha gamma".to_string()])
+            .await;
+        let expected = first.encode_query("alpha gamma").await.unwrap();
+
+        let second = Bm25Encoder::load(&make_config(), Some(pool)).await.unwrap();
+        assert_eq!(second.encode_query("alpha gamma").await.unwrap(), expected);
+        let stats = second.stats.lock().unwrap();
+        assert_eq!((stats.doc_count, stats.total_length), (2, 4));
+    }
+
+    #[tokio::test]
+    async fn encoding_alone_leaves_statistics_unchanged() {
+        let encoder = Bm25Encoder::load(&make_config(), None).await.unwrap();
+        let texts = vec!["alpha beta".to_string()];
+        let before = encoder.encode_documents(&texts).await.unwrap();
+        assert_eq!(encoder.stats.lock().unwrap().doc_count, 0);
+
+        // Recording the same texts afterwards does not change their weights.
+        encoder.record_documents(&texts).await;
+        assert_eq!(encoder.stats.lock().unwrap().doc_count, 1);
+        assert_eq!(encoder.encode_documents(&texts).await.unwrap(), before);
+    }
+}
@@ -0,0 +1,75 @@
+//! Sparse lexical vectors for hybrid retrieval.
+//!
+//! Dense embeddings capture meaning but blur exact tokens such as identifiers,
+//! error codes and names.  A [`SparseEncoder`] maps text to a
+//! [`SparseVector`] – a weight per vocabulary term – that Qdrant stores in a
+//! named sparse vector next to the dense one.  Searching it finds exact term
+//! matches; `/api/search` can fuse both result lists (see
+//! [`crate::vector_store::SearchQuery::Hybrid`]).
+//!
+//! Encoders:
+//! - [`bm25::Bm25Encoder`] – built in; BM25 weights with corpus statistics
+//!   kept by this server
+//! - [`splade::SpladeEncoder`] – a SPLADE model served by
+//!   text-embeddings-inference
+
+pub mod bm25;
+pub mod splade;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{config::SparseConfig, error::EmbeddingError};
+
+/// Non-zero weights of a sparse vector, in Qdrant's wire format.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// Produces sparse vectors for stored documents and for queries.
+#[async_trait]
+pub trait SparseEncoder: Send + Sync {
+    /// Encode texts being stored, in order.  Corpus statistics are not
+    /// changed until [`record_documents`](Self::record_documents) is called.
+    async fn encode_documents(&self, texts: &[String])
+        -> Result<Vec<SparseVector>, EmbeddingError>;
+
+    /// Count texts encoded by [`encode_documents`](Self::encode_documents)
+    /// as stored documents, once they have been written.  A no-op for
+    /// encoders without corpus statistics.
+    async fn record_documents(&self, _texts: &[String]) {}
+
+    /// Encode a search query.
+    async fn encode_query(&self, text: &str) -> Result<SparseVector, EmbeddingError>;
+}
+
+/// Thread-safe, reference-counted sparse encoder.
+pub type DynSparseEncoder = Arc<dyn SparseEncoder>;
+
+/// Build the encoder described by `cfg`.
+///
+/// `pool` persists the BM25 corpus statistics; without it they are kept in
+/// memory and start empty on every restart.
+pub async fn build_encoder(
+    cfg: &SparseConfig,
+    pool: Option<SqlitePool>,
+) -> Result<DynSparseEncoder, EmbeddingError> {
+    match cfg.encoder_type.as_str() {
+        "bm25" => Ok(Arc::new(bm25::Bm25Encoder::load(cfg, pool).await?)),
+        "splade" => Ok(Arc::new(splade::SpladeEncoder::new(cfg)?)),
+        unknown => Err(EmbeddingError::ConfigError(format!(
+            "Unknown sparse encoder type: '{unknown}'"
+        ))),
+    }
+}
@@ -0,0 +1,184 @@
+//! SPLADE sparse encoder served by text-embeddings-inference.
+//!
+//! API reference: <https://huggingface.github.io/text-embeddings-inference/>
+//!
+//! Request  → POST {base_url}/embed_sparse
+//! Body     → `{"inputs": ["…", …]}`
+//! Response → `[[{"index": 1996, "value": 0.42}, …], …]`
+//!
+//! The server must host a SPLADE model (e.g. `naver/efficient-splade-VI-BT-large-doc`).
+//! Documents and queries are encoded the same way, and no statistics are kept
+//! locally.  `api_key`, when set, is sent as a bearer token.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::SparseConfig,
+    embedding::{error_from_response, parse_response},
+    error::EmbeddingError,
+    http_client::build_client,
+};
+
+use super::{SparseEncoder, SparseVector};
+
+pub struct SpladeEncoder {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl SpladeEncoder {
+    pub fn new(cfg: &SparseConfig) -> Result<Self, EmbeddingError> {
+        Ok(Self {
+            client: build_client(&cfg.http).map_err(EmbeddingError::ConfigError)?,
+            base_url: cfg.base_url.trim_end_matches('/').to_string(),
+            api_key: cfg
+                .api_key
+                .as_ref()
+                .map(|k| k.expose().to_string())
+                .filter(|k| !k.is_empty()),
+        })
+    }
+
+    async fn request_vectors(&self, inputs: &[&str]) -> Result<Vec<SparseVector>, EmbeddingError> {
+        let url = format!("{}/embed_sparse", self.base_url);
+        let mut request = self.client.post(&url).json(&SpladeRequest { inputs });
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(EmbeddingError::AuthenticationError);
+        }
+        if !status.is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let parsed: Vec<Vec<SpladeWeight>> = parse_response(response, "sparse embedding").await?;
+        if parsed.len() != inputs.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "Expected {} sparse vectors, server returned {}",
+                inputs.len(),
+                parsed.len()
+            )));
+        }
+
+        Ok(parsed
+            .into_iter()
+            .map(|weights| SparseVector {
+                indices: weights.iter().map(|w| w.index).collect(),
+                values: weights.iter().map(|w| w.value).collect(),
+            })
+            .collect())
+    }
+}
+
+#[derive(Serialize)]
+struct SpladeRequest<'a> {
+    inputs: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct SpladeWeight {
+    index: u32,
+    value: f32,
+}
+
+#[async_trait]
+impl SparseEncoder for SpladeEncoder {
+    async fn encode_documents(
+        &self,
+        texts: &[String],
+    ) -> Result<Vec<SparseVector>, EmbeddingError> {
+        let inputs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.request_vectors(&inputs).await
+    }
+
+    async fn encode_query(&self, text: &str) -> Result<SparseVector, EmbeddingError> {
+        self.request_vectors(&[text])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                EmbeddingError::InvalidResponse("Empty sparse embeddings array".to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    fn make_encoder(base_url: &str) -> SpladeEncoder {
+        let mut cfg: SparseConfig = toml::from_str(r#"type = "splade""#).unwrap();
+        cfg.base_url = base_url.to_string();
+        cfg.api_key = Some("proxy-key".into());
+        SpladeEncoder::new(&cfg).unwrap()
+    }
+
+    #[tokio::test]
+    async fn documents_are_encoded_in_one_request() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/embed_sparse"))
+            .and(header("authorization", "Bearer proxy-key"))
+            .and(body_json(
+                serde_json::json!({ "inputs": ["first", "second"] }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                [{"index": 3, "value": 0.5}, {"index": 17, "value": 1.25}],
+                [{"index": 8, "value": 2.0}]
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let encoder = make_encoder(&server.uri());
+        let vectors = encoder
+            .encode_documents(&["first".to_string(), "second".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            vectors,
+            vec![
+                SparseVector {
+                    indices: vec![3, 17],
+                    values: vec![0.5, 1.25]
+                },
+                SparseVector {
+                    indices: vec![8],
+                    values: vec![2.0]
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_provider_errors() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/embed_sparse"))
+            .respond_with(ResponseTemplate::new(424).set_body_json(serde_json::json!({
+                "error": "Model is not a SPLADE model",
+                "error_type": "Backend"
+            })))
+            .mount(&server)
+            .await;
+
+        let result = make_encoder(&server.uri()).encode_query("hello").await;
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::ProviderError { status: 424, .. })
+        ));
+    }
+}
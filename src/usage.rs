@@ -0,0 +1,448 @@
+//! Embedding token usage and cost accounting.
+//!
+//! Every upstream embedding call is counted by
+//! [`InstrumentedProvider`](crate::embedding::instrumented::InstrumentedProvider):
+//! - providers whose responses carry a token count (`openai`, `voyage`,
+//!   `cohere`, `ollama`) pass it to [`report_tokens`]
+//! - for the others the input is estimated at ≈ 4 characters per token and
+//!   recorded as estimated
+//!
+//! and priced with the provider's `cost_per_million_tokens`.
+//!
+//! The [`track_usage`] middleware attributes the calls made while serving a
+//! request to its caller once the handler has vouched for it: the API key
+//! passed to [`set_api_key`] after it validated (stored only as a truncated
+//! SHA-256 hash) and the session passed to [`set_session`] after it was
+//! found.  Totals are added to hourly buckets in the SQLite `embedding_usage`
+//! table and reported by `GET /api/usage`.
+//!
+//! Cache hits and coalesced duplicate calls make no upstream request and are
+//! not counted, nor are calls made outside an HTTP request, such as the
+//! dimension probe at startup.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    future::Future,
+    ops::AddAssign,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::error::UsageError;
+
+/// Hex digits of the API key hash kept as `api_key_id`.
+const KEY_ID_LEN: usize = 12;
+
+/// Width of one aggregation bucket, in seconds.
+const BUCKET_SECS: i64 = 3600;
+
+tokio::task_local! {
+    /// Tokens reported by providers during the upstream call in progress.
+    static REPORTED: Cell<Option<u64>>;
+    /// Usage of the HTTP request being served.
+    static REQUEST: RequestUsage;
+}
+
+// ---------------------------------------------------------------------------
+// Recording
+// ---------------------------------------------------------------------------
+
+/// Calls, tokens and cost, for one call or summed over many.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    /// All tokens, including the estimated ones.
+    pub tokens: u64,
+    /// Tokens estimated from the input because the provider reported none.
+    pub estimated_tokens: u64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    /// One call of `tokens` tokens, of which `estimated_tokens` were estimated.
+    pub fn call(tokens: u64, estimated_tokens: u64, cost_per_million_tokens: Option<f64>) -> Self {
+        Self {
+            calls: 1,
+            tokens,
+            estimated_tokens,
+            cost: tokens as f64 * cost_per_million_tokens.unwrap_or(0.0) / 1_000_000.0,
+        }
+    }
+}
+
+impl AddAssign for UsageTotals {
+    fn add_assign(&mut self, other: Self) {
+        self.calls += other.calls;
+        self.tokens += other.tokens;
+        self.estimated_tokens += other.estimated_tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// Report the token count from an upstream response.
+///
+/// Call once per upstream request; counts reported during retries add up.
+pub fn report_tokens(tokens: u64) {
+    let _ = REPORTED.try_with(|reported| reported.set(Some(reported.get().unwrap_or(0) + tokens)));
+}
+
+/// Run `call`, returning its output and the tokens reported during it.
+pub async fn capture_reported<F: Future>(call: F) -> (F::Output, Option<u64>) {
+    REPORTED
+        .scope(Cell::new(None), async {
+            let output = call.await;
+            (output, REPORTED.with(Cell::get))
+        })
+        .await
+}
+
+/// Add the usage of one call to `provider` to the request being served.
+pub fn record(provider: &str, usage: UsageTotals) {
+    let _ = REQUEST.try_with(|request| {
+        *request
+            .providers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(provider.to_string())
+            .or_default() += usage;
+    });
+}
+
+/// Attribute the request being served to `api_key`, once it has been
+/// validated.
+pub fn set_api_key(api_key: &str) {
+    let _ = REQUEST.try_with(|request| {
+        *request.api_key_id.lock().unwrap_or_else(|e| e.into_inner()) = key_id(api_key);
+    });
+}
+
+/// Attribute the request being served to `session_id`, once the session has
+/// been found.
+pub fn set_session(session_id: &str) {
+    let _ = REQUEST.try_with(|request| {
+        *request.session_id.lock().unwrap_or_else(|e| e.into_inner()) = session_id.to_string();
+    });
+}
+
+/// The caller of one request and the usage recorded while serving it.
+#[derive(Default)]
+struct RequestUsage {
+    api_key_id: Mutex<String>,
+    session_id: Mutex<String>,
+    providers: Mutex<HashMap<String, UsageTotals>>,
+}
+
+impl RequestUsage {
+    fn take(&self) -> RecordedUsage {
+        RecordedUsage {
+            api_key_id: std::mem::take(
+                &mut *self.api_key_id.lock().unwrap_or_else(|e| e.into_inner()),
+            ),
+            session_id: std::mem::take(
+                &mut *self.session_id.lock().unwrap_or_else(|e| e.into_inner()),
+            ),
+            providers: std::mem::take(
+                &mut *self.providers.lock().unwrap_or_else(|e| e.into_inner()),
+            ),
+        }
+    }
+}
+
+/// Usage of one request, ready to be stored.
+#[derive(Debug, Default, PartialEq)]
+pub struct RecordedUsage {
+    /// Truncated hash of the caller's API key; empty when none was validated.
+    pub api_key_id: String,
+    /// Empty when the request named no session.
+    pub session_id: String,
+    pub providers: HashMap<String, UsageTotals>,
+}
+
+/// Run `fut` as a request of the given caller, returning its output and the
+/// usage recorded while it ran.
+async fn tracked<F: Future>(usage: RequestUsage, fut: F) -> (F::Output, RecordedUsage) {
+    REQUEST
+        .scope(usage, async {
+            let output = fut.await;
+            (output, REQUEST.with(RequestUsage::take))
+        })
+        .await
+}
+
+/// The stored identity of an API key: a prefix of its SHA-256 hash.
+fn key_id(api_key: &str) -> String {
+    let mut id = hex::encode(Sha256::digest(api_key.as_bytes()));
+    id.truncate(KEY_ID_LEN);
+    id
+}
+
+/// Attribute the embedding usage of every request to its caller and store it.
+///
+/// Install with `middleware::from_fn_with_state`; requests pass through
+/// untracked when no [`UsageStore`] is configured.
+pub async fn track_usage(
+    State(store): State<Option<Arc<UsageStore>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(store) = store else {
+        return next.run(request).await;
+    };
+
+    let (response, recorded) = tracked(RequestUsage::default(), next.run(request)).await;
+
+    // Write in the background so the response is not held up by SQLite.
+    if !recorded.providers.is_empty() {
+        tokio::spawn(async move {
+            if let Err(e) = store.add(Utc::now(), &recorded).await {
+                warn!(error = %e, "Failed to record embedding usage");
+            }
+        });
+    }
+    response
+}
+
+// ---------------------------------------------------------------------------
+// UsageStore
+// ---------------------------------------------------------------------------
+
+/// What `GET /api/usage` totals are grouped by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    Provider,
+    Session,
+    Key,
+}
+
+impl GroupBy {
+    fn column(self) -> &'static str {
+        match self {
+            GroupBy::Provider => "provider",
+            GroupBy::Session => "session_id",
+            GroupBy::Key => "api_key_id",
+        }
+    }
+}
+
+/// Usage totals of one provider, session or API key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupUsage {
+    /// Provider name, session ID or API key hash; `null` for calls made
+    /// without a session or API key.
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Hourly usage totals in the SQLite `embedding_usage` table.
+pub struct UsageStore {
+    pool: SqlitePool,
+}
+
+impl UsageStore {
+    /// Use the session store's pool, whose migrations create the table.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Add the usage of one request, made at `at`, to its hourly bucket.
+    pub async fn add(&self, at: DateTime<Utc>, usage: &RecordedUsage) -> Result<(), UsageError> {
+        let bucket_start = at.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS;
+        let mut tx = self.pool.begin().await?;
+        for (provider, totals) in &usage.providers {
+            sqlx::query(
+                "INSERT INTO embedding_usage \
+                 (bucket_start, provider, api_key_id, session_id, calls, tokens, estimated_tokens, cost) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT(bucket_start, provider, api_key_id, session_id) DO UPDATE SET \
+                 calls = calls + excluded.calls, tokens = tokens + excluded.tokens, \
+                 estimated_tokens = estimated_tokens + excluded.estimated_tokens, \
+                 cost = cost + excluded.cost",
+            )
+            .bind(bucket_start)
+            .bind(provider)
+            .bind(&usage.api_key_id)
+            .bind(&usage.session_id)
+            .bind(totals.calls as i64)
+            .bind(totals.tokens as i64)
+            .bind(totals.estimated_tokens as i64)
+            .bind(totals.cost)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Totals of the hours starting in `[from, to)`, with `from` rounded down
+    /// to the hour, grouped by `group_by` and ordered by cost, highest first.
+    pub async fn report(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        group_by: GroupBy,
+    ) -> Result<Vec<GroupUsage>, UsageError> {
+        let column = group_by.column();
+        let rows: Vec<(String, i64, i64, i64, f64)> = sqlx::query_as(&format!(
+            "SELECT {column}, SUM(calls), SUM(tokens), SUM(estimated_tokens), SUM(cost) \
+             FROM embedding_usage WHERE bucket_start >= ? AND bucket_start < ? \
+             GROUP BY {column} ORDER BY SUM(cost) DESC, SUM(tokens) DESC, {column}"
+        ))
+        .bind(from.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS)
+        .bind(to.timestamp())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(group, calls, tokens, estimated_tokens, cost)| GroupUsage {
+                    group: Some(group).filter(|g| !g.is_empty()),
+                    totals: UsageTotals {
+                        calls: calls as u64,
+                        tokens: tokens as u64,
+                        estimated_tokens: estimated_tokens as u64,
+                        cost,
+                    },
+                },
+            )
+            .collect())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::session_store::SessionStore;
+
+    async fn make_store() -> UsageStore {
+        let sessions = SessionStore::new("sqlite::memory:")
+            .await
+            .expect("in-memory database should initialise");
+        UsageStore::new(sessions.pool().clone())
+    }
+
+    #[tokio::test]
+    async fn calls_are_attributed_to_the_caller() {
+        let ((), recorded) = tracked(RequestUsage::default(), async {
+            let ((), reported) = capture_reported(async {
+                report_tokens(7);
+                report_tokens(3);
+            })
+            .await;
+            record(
+                "openai",
+                UsageTotals::call(reported.unwrap(), 0, Some(0.02)),
+            );
+            record("openai", UsageTotals::call(5, 5, Some(0.02)));
+            set_api_key("secret-key");
+            set_session("body-session");
+        })
+        .await;
+
+        assert_eq!(recorded.api_key_id, key_id("secret-key"));
+        assert_eq!(recorded.api_key_id.len(), KEY_ID_LEN);
+        assert_eq!(recorded.session_id, "body-session");
+        let openai = recorded.providers["openai"];
+        assert_eq!(
+            (openai.calls, openai.tokens, openai.estimated_tokens),
+            (2, 15, 5)
+        );
+        assert!((openai.cost - 15.0 * 0.02 / 1e6).abs() < 1e-12);
+
+        // Outside a tracked request, recording is a no-op.
+        record("openai", UsageTotals::call(1, 0, None));
+        let ((), reported) = capture_reported(async {}).await;
+        assert_eq!(reported, None);
+    }
+
+    #[tokio::test]
+    async fn calls_of_unvalidated_callers_are_not_attributed() {
+        let ((), recorded) = tracked(RequestUsage::default(), async {
+            record("openai", UsageTotals::call(5, 0, None));
+        })
+        .await;
+
+        assert_eq!(recorded.api_key_id, "");
+        assert_eq!(recorded.session_id, "");
+        assert_eq!(recorded.providers["openai"].tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn report_groups_hourly_totals_within_the_range() {
+        let store = make_store().await;
+        let at = |h, m| Utc.with_ymd_and_hms(2026, 1, 1, h, m, 0).unwrap();
+        let usage = |provider: &str, key: &str, session: &str, tokens| RecordedUsage {
+            api_key_id: key.to_string(),
+            session_id: session.to_string(),
+            providers: HashMap::from([(
+                provider.to_string(),
+                UsageTotals::call(tokens, 0, Some(1.0)),
+            )]),
+        };
+
+        store
+            .add(at(9, 10), &usage("openai", "k1", "s1", 100))
+            .await
+            .unwrap();
+        store
+            .add(at(9, 50), &usage("openai", "k1", "s1", 200))
+            .await
+            .unwrap();
+        store
+            .add(at(10, 5), &usage("ollama", "k2", "", 50))
+            .await
+            .unwrap();
+        store
+            .add(at(12, 0), &usage("openai", "k2", "s2", 400))
+            .await
+            .unwrap();
+
+        let by_provider = store
+            .report(at(9, 30), at(11, 0), GroupBy::Provider)
+            .await
+            .unwrap();
+        let summary: Vec<_> = by_provider
+            .iter()
+            .map(|g| (g.group.as_deref(), g.totals.calls, g.totals.tokens))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(Some("openai"), 2, 300), (Some("ollama"), 1, 50)]
+        );
+
+        let by_session = store
+            .report(at(0, 0), at(23, 0), GroupBy::Session)
+            .await
+            .unwrap();
+        let sessions: Vec<_> = by_session.iter().map(|g| g.group.as_deref()).collect();
+        assert_eq!(sessions, vec![Some("s2"), Some("s1"), None]);
+        assert!((by_session[0].totals.cost - 400.0 / 1e6).abs() < 1e-12);
+
+        let by_key = store
+            .report(at(0, 0), at(23, 0), GroupBy::Key)
+            .await
+            .unwrap();
+        assert_eq!(by_key[0].group.as_deref(), Some("k2"));
+        assert_eq!(by_key[0].totals.tokens, 450);
+    }
+}
@@ -2,8 +2,15 @@
 //!
 //! This module provides a lightweight REST client for Qdrant that supports:
 //! - Automatic collection creation on startup (with exponential-backoff retry)
+//! - Validation of an existing collection's vector size and distance metric
 //! - Upserting embeddings with arbitrary JSON metadata
 //! - Querying by vector similarity (configurable distance metric)
+//! - Optionally, a named sparse vector per point for lexical search, and
+//!   hybrid search fusing dense and sparse results with reciprocal rank
+//!   fusion (see [`SearchQuery`])
+//! - Expiring points: a Unix timestamp in the `expires_at` payload field
+//!   hides the point from searches once passed, and
+//!   [`QdrantStore::purge_expired`] deletes it
 //!
 //! API reference: <https://qdrant.tech/documentation/interfaces/#api-reference>
 //!
@@ -11,10 +18,16 @@
 //! - `GET  /collections/{name}`          – check whether a collection exists
 //! - `PUT  /collections/{name}`          – create a collection
 //! - `PUT  /collections/{name}/points`   – upsert one or more points
+//! - `POST /collections/{name}/points/delete` – delete points by filter
 //! - `POST /collections/{name}/points/search` – nearest-neighbour search
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    time::{Duration, Instant},
+};
 
+use chrono::Utc;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -22,7 +35,14 @@ use tokio::time::sleep;
 use tracing::{info, warn};
 use uuid::Uuid;
 
-use crate::{config::QdrantConfig, error::VectorStoreError};
+use crate::{
+    chunking::{CHUNK_COUNT_KEY, CHUNK_INDEX_KEY, PARENT_ID_KEY},
+    config::QdrantConfig,
+    error::{EmbeddingError, VectorStoreError},
+    http_client::build_client,
+    metrics::metrics,
+    sparse::{DynSparseEncoder, SparseVector},
+};
 
 // ---------------------------------------------------------------------------
 // Public constants
@@ -36,10 +56,45 @@ pub const RESERVED_TEXT_KEY_ERROR: &str =
 pub const RESERVED_SESSION_ID_KEY_ERROR: &str =
     "'session_id' is a reserved metadata key. Supply it via the top-level 'session_id' field instead.";
 
+/// Error message returned when a caller uses a metadata key reserved for chunking.
+pub const RESERVED_CHUNK_KEY_ERROR: &str =
+    "'parent_id', 'chunk_index' and 'chunk_count' are reserved metadata keys used for chunked memories.";
+
+/// Error message returned when a caller uses the reserved `"expires_at"` metadata key.
+pub const RESERVED_EXPIRES_AT_KEY_ERROR: &str =
+    "'expires_at' is a reserved metadata key. Supply it via the top-level 'expires_at' or 'ttl_seconds' field instead.";
+
+/// Payload key holding a point's expiry as a Unix timestamp in seconds.
+pub const EXPIRES_AT_KEY: &str = "expires_at";
+
+/// Error message returned for sparse or hybrid searches without `[qdrant.sparse]`.
+pub const SPARSE_NOT_CONFIGURED_ERROR: &str =
+    "Sparse and hybrid search require [qdrant.sparse] to be configured.";
+
+/// How many hits per requested result are fetched when collapsing chunks, so
+/// that several chunks of one parent do not crowd out other memories.
+const COLLAPSE_OVERFETCH: u32 = 4;
+
+/// The `k` constant of reciprocal rank fusion.  A hit ranked `r` (from 1) in
+/// one result list contributes `1 / (k + r)` to its fused score; 60 is the
+/// value from the original paper and the common default.
+const RRF_K: f32 = 60.0;
+
 // ---------------------------------------------------------------------------
 // Public types
 // ---------------------------------------------------------------------------
 
+/// What a search ranks by.
+pub enum SearchQuery<'a> {
+    /// Nearest neighbours of a dense embedding.
+    Dense(Vec<f32>),
+    /// Best lexical matches of a text, by its sparse vector.
+    Sparse(&'a str),
+    /// Both of the above, fused with reciprocal rank fusion.  Scores of the
+    /// results are fused scores, not similarities.
+    Hybrid { vector: Vec<f32>, text: &'a str },
+}
+
 /// A stored memory point returned by search queries.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
@@ -66,22 +121,38 @@ pub struct QdrantStore {
     api_key: Option<String>,
     dimensions: u32,
     distance: String,
+    sparse: Option<SparseIndex>,
+}
+
+/// The collection's named sparse vector and the encoder that fills it.
+struct SparseIndex {
+    vector_name: String,
+    encoder: DynSparseEncoder,
 }
 
 impl QdrantStore {
-    pub fn new(cfg: &QdrantConfig) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to build Qdrant HTTP client");
-        Self {
+    pub fn new(cfg: &QdrantConfig) -> Result<Self, VectorStoreError> {
+        let client = build_client(&cfg.http).map_err(VectorStoreError::Config)?;
+        Ok(Self {
             client,
             base_url: cfg.url.trim_end_matches('/').to_string(),
             collection: cfg.collection.clone(),
-            api_key: cfg.api_key.clone(),
+            api_key: cfg.api_key.as_ref().map(|k| k.expose().to_string()),
             dimensions: cfg.dimensions,
             distance: cfg.distance.clone(),
-        }
+            sparse: None,
+        })
+    }
+
+    /// Store a sparse vector named `vector_name`, produced by `encoder`, with
+    /// every point, enabling [`SearchQuery::Sparse`] and
+    /// [`SearchQuery::Hybrid`].
+    pub fn with_sparse_encoder(mut self, vector_name: &str, encoder: DynSparseEncoder) -> Self {
+        self.sparse = Some(SparseIndex {
+            vector_name: vector_name.to_string(),
+            encoder,
+        });
+        self
     }
 
     // -----------------------------------------------------------------------
@@ -107,7 +178,7 @@ impl QdrantStore {
     ///
     /// Makes up to 5 total attempts (1 initial + up to 4 retries) with
     /// exponential backoff (1 s, 2 s, 4 s, 8 s) on transient failures:
-    /// network errors, HTTP 429 (rate-limited), and HTTP 503 (service
+    /// network errors, timeouts, HTTP 429 (rate-limited), and HTTP 503 (service
     /// temporarily unavailable).
     pub async fn ensure_collection(&self) -> Result<(), VectorStoreError> {
         let mut attempts = 0u32;
@@ -115,12 +186,13 @@ impl QdrantStore {
 
         loop {
             attempts += 1;
-            match self.try_ensure_collection().await {
+            match observed("ensure_collection", self.try_ensure_collection()).await {
                 Ok(()) => return Ok(()),
                 Err(e) => {
                     let is_transient = matches!(
                         &e,
                         VectorStoreError::Http(_)
+                            | VectorStoreError::Timeout(_)
                             | VectorStoreError::Api {
                                 status: 429 | 503,
                                 ..
@@ -147,32 +219,32 @@ impl QdrantStore {
     async fn try_ensure_collection(&self) -> Result<(), VectorStoreError> {
         let path = format!("/collections/{}", self.collection);
 
-        let resp = self
-            .request(reqwest::Method::GET, &path)
-            .send()
-            .await
-            .map_err(VectorStoreError::Http)?;
+        let resp = self.request(reqwest::Method::GET, &path).send().await?;
 
         match resp.status().as_u16() {
             200 => {
+                let info: Value = parse_body(resp, "collection info").await?;
+                self.check_collection_params(&info)?;
                 info!(collection = %self.collection, "Qdrant collection already exists");
                 Ok(())
             }
             404 => {
                 // Collection is missing – create it.
-                let body = json!({
+                let mut body = json!({
                     "vectors": {
                         "size": self.dimensions,
                         "distance": self.distance
                     }
                 });
+                if let Some(sparse) = &self.sparse {
+                    body["sparse_vectors"] = json!({ &sparse.vector_name: {} });
+                }
 
                 let create_resp = self
                     .request(reqwest::Method::PUT, &path)
                     .json(&body)
                     .send()
-                    .await
-                    .map_err(VectorStoreError::Http)?;
+                    .await?;
 
                 match create_resp.status().as_u16() {
                     200..=299 => {
@@ -199,6 +271,63 @@ impl QdrantStore {
         }
     }
 
+    /// Compare an existing collection's vector parameters (from
+    /// `GET /collections/{name}`) with the configured `dimensions` and
+    /// `distance`.
+    ///
+    /// A mismatch would otherwise only surface as an opaque Qdrant 400 on the
+    /// first upsert, so it is reported as
+    /// [`VectorStoreError::CollectionMismatch`].  Collections whose parameters
+    /// cannot be read (e.g. named vectors) are accepted with a warning.
+    ///
+    /// When sparse vectors are configured the collection must already have
+    /// the named sparse vector; Qdrant cannot add one to an existing
+    /// collection.
+    fn check_collection_params(&self, info: &Value) -> Result<(), VectorStoreError> {
+        let params = &info["result"]["config"]["params"];
+        if let Some(sparse) = &self.sparse {
+            if !params["sparse_vectors"][&sparse.vector_name].is_object() {
+                return Err(VectorStoreError::CollectionMismatch(format!(
+                    "collection '{}' has no sparse vector '{}', and one cannot be added to an \
+                     existing collection. Use a new collection or remove [qdrant.sparse]",
+                    self.collection, sparse.vector_name
+                )));
+            }
+        }
+
+        let vectors = &params["vectors"];
+        let (Some(size), Some(distance)) = (vectors["size"].as_u64(), vectors["distance"].as_str())
+        else {
+            warn!(
+                collection = %self.collection,
+                "Could not read vector parameters of existing Qdrant collection; skipping validation"
+            );
+            return Ok(());
+        };
+
+        let mut problems = Vec::new();
+        if size != u64::from(self.dimensions) {
+            problems.push(format!(
+                "it stores {size}-dimensional vectors but [qdrant] dimensions is {}",
+                self.dimensions
+            ));
+        }
+        if !distance.eq_ignore_ascii_case(&self.distance) {
+            problems.push(format!(
+                "it uses '{distance}' distance but [qdrant] distance is '{}'",
+                self.distance
+            ));
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(VectorStoreError::CollectionMismatch(format!(
+            "collection '{}': {}. Fix the config or use a different collection",
+            self.collection,
+            problems.join("; ")
+        )))
+    }
+
     // -----------------------------------------------------------------------
     // Write
     // -----------------------------------------------------------------------
@@ -211,6 +340,9 @@ impl QdrantStore {
     /// - `text`: the original text; stored in the payload under `"text"`.
     /// - `metadata`: arbitrary additional payload fields.
     ///
+    /// When `id` is supplied, chunks previously stored under it are deleted
+    /// once the new point is written.
+    ///
     /// Returns the point ID that was stored (as a string).
     pub async fn upsert(
         &self,
@@ -227,44 +359,261 @@ impl QdrantStore {
                 RESERVED_TEXT_KEY_ERROR.to_string(),
             ));
         }
-        payload.insert("text".to_string(), Value::String(text));
+        let sparse = self
+            .encode_documents(std::slice::from_ref(&text))
+            .await?
+            .and_then(|vectors| vectors.into_iter().next());
+        payload.insert("text".to_string(), Value::String(text.clone()));
+
+        self.put_points(vec![json!({
+            "id": &point_id,
+            "vector": self.point_vectors(vector, sparse),
+            "payload": payload
+        })])
+        .await?;
+        self.record_documents(std::slice::from_ref(&text)).await;
+        if id.is_some() {
+            self.delete_stale(&point_id, std::slice::from_ref(&point_id))
+                .await?;
+        }
 
-        let body = json!({
-            "points": [
-                {
-                    "id": &point_id,
-                    "vector": vector,
+        Ok(point_id)
+    }
+
+    /// Upsert the chunks of one long text as separate points.
+    ///
+    /// Every point gets its own UUID and carries `parent_id`, `chunk_index`
+    /// and `chunk_count` in its payload alongside `metadata`.  Once the new
+    /// chunks are written, the points previously stored under the same
+    /// parent — its old chunks or a single unchunked point — are deleted, so
+    /// re-upserting a parent never leaves stale points behind.
+    ///
+    /// Returns the parent ID (as a string).
+    pub async fn upsert_chunks(
+        &self,
+        parent_id: Option<Uuid>,
+        chunks: Vec<(String, Vec<f32>)>,
+        metadata: HashMap<String, Value>,
+    ) -> Result<String, VectorStoreError> {
+        if metadata.contains_key("text") {
+            return Err(VectorStoreError::BadRequest(
+                RESERVED_TEXT_KEY_ERROR.to_string(),
+            ));
+        }
+        let supplied = parent_id.is_some();
+        let parent_id = parent_id.unwrap_or_else(Uuid::new_v4).to_string();
+        let texts: Vec<String> = chunks.iter().map(|(text, _)| text.clone()).collect();
+        let mut sparse = self.encode_documents(&texts).await?.map(Vec::into_iter);
+
+        let chunk_count = chunks.len();
+        let chunk_ids: Vec<String> = (0..chunk_count)
+            .map(|_| Uuid::new_v4().to_string())
+            .collect();
+        let points = chunks
+            .into_iter()
+            .zip(&chunk_ids)
+            .enumerate()
+            .map(|(index, ((text, vector), chunk_id))| {
+                let sparse = sparse.as_mut().and_then(Iterator::next);
+                let mut payload = metadata.clone();
+                payload.insert("text".to_string(), Value::String(text));
+                payload.insert(PARENT_ID_KEY.to_string(), json!(parent_id));
+                payload.insert(CHUNK_INDEX_KEY.to_string(), json!(index));
+                payload.insert(CHUNK_COUNT_KEY.to_string(), json!(chunk_count));
+                json!({
+                    "id": chunk_id,
+                    "vector": self.point_vectors(vector, sparse),
                     "payload": payload
-                }
-            ]
-        });
+                })
+            })
+            .collect();
+        self.put_points(points).await?;
+        self.record_documents(&texts).await;
+        if supplied {
+            self.delete_stale(&parent_id, &chunk_ids).await?;
+        }
 
-        let path = format!("/collections/{}/points", self.collection);
-        let resp = self
-            .request(reqwest::Method::PUT, &path)
-            .json(&body)
-            .send()
-            .await
-            .map_err(VectorStoreError::Http)?;
+        Ok(parent_id)
+    }
 
-        if !resp.status().is_success() {
-            return Err(api_error(resp).await);
+    /// Delete the points previously stored under `id` — the point with that
+    /// id and every chunk whose payload `parent_id` equals it — except the
+    /// freshly written points in `keep`.
+    async fn delete_stale(&self, id: &str, keep: &[String]) -> Result<(), VectorStoreError> {
+        self.delete_points(json!({
+            "should": [
+                { "has_id": [id] },
+                { "key": PARENT_ID_KEY, "match": { "value": id } }
+            ],
+            "must_not": [{ "has_id": keep }]
+        }))
+        .await
+    }
+
+    /// Delete every point whose `expires_at` has passed.
+    pub async fn purge_expired(&self) -> Result<(), VectorStoreError> {
+        self.delete_points(json!({
+            "must": [{ "key": EXPIRES_AT_KEY, "range": { "lte": Utc::now().timestamp() } }]
+        }))
+        .await
+    }
+
+    /// Delete every point matching the Qdrant `filter`.
+    async fn delete_points(&self, filter: Value) -> Result<(), VectorStoreError> {
+        let body = json!({ "filter": filter });
+        let path = format!("/collections/{}/points/delete?wait=true", self.collection);
+        observed("delete", async {
+            let resp = self
+                .request(reqwest::Method::POST, &path)
+                .json(&body)
+                .send()
+                .await?;
+
+            if !resp.status().is_success() {
+                return Err(api_error(resp).await);
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Sparse vectors of texts being stored; `None` when sparse vectors are
+    /// not configured.
+    async fn encode_documents(
+        &self,
+        texts: &[String],
+    ) -> Result<Option<Vec<SparseVector>>, VectorStoreError> {
+        let Some(sparse) = &self.sparse else {
+            return Ok(None);
+        };
+        let vectors = sparse.encoder.encode_documents(texts).await?;
+        if vectors.len() != texts.len() {
+            return Err(VectorStoreError::Embedding(
+                EmbeddingError::InvalidResponse(format!(
+                    "Expected {} sparse vectors, encoder returned {}",
+                    texts.len(),
+                    vectors.len()
+                )),
+            ));
         }
+        Ok(Some(vectors))
+    }
 
-        Ok(point_id)
+    /// Count stored texts in the sparse encoder's corpus statistics.
+    async fn record_documents(&self, texts: &[String]) {
+        if let Some(sparse) = &self.sparse {
+            sparse.encoder.record_documents(texts).await;
+        }
+    }
+
+    /// The `vector` field of a point: the bare dense vector or, with sparse
+    /// vectors configured, the dense vector under the default name `""` next
+    /// to the named sparse vector.
+    fn point_vectors(&self, dense: Vec<f32>, sparse: Option<SparseVector>) -> Value {
+        match (&self.sparse, sparse) {
+            (Some(index), Some(sparse)) => json!({ "": dense, &index.vector_name: sparse }),
+            _ => json!(dense),
+        }
+    }
+
+    async fn put_points(&self, points: Vec<Value>) -> Result<(), VectorStoreError> {
+        let path = format!("/collections/{}/points", self.collection);
+        observed("upsert", async {
+            let resp = self
+                .request(reqwest::Method::PUT, &path)
+                .json(&json!({ "points": points }))
+                .send()
+                .await?;
+
+            if !resp.status().is_success() {
+                return Err(api_error(resp).await);
+            }
+            Ok(())
+        })
+        .await
     }
 
     // -----------------------------------------------------------------------
     // Query
     // -----------------------------------------------------------------------
 
-    /// Search for the `limit` nearest neighbours of `vector`.
+    /// Return the `limit` best matches for `query`.
+    ///
+    /// - `score_threshold`: when provided, only results scoring ≥ this value
+    ///   are returned.  It applies to the dense similarity for
+    ///   [`SearchQuery::Dense`] and, before fusion, [`SearchQuery::Hybrid`],
+    ///   and to the sparse score for [`SearchQuery::Sparse`].
     ///
-    /// - `score_threshold`: when provided, only results with a cosine
-    ///   similarity ≥ this value are returned.
+    /// Hybrid search fetches `limit` hits from each vector and fuses them, so
+    /// a point found by both ranks above points found by only one.
     pub async fn search(
         &self,
-        vector: Vec<f32>,
+        query: SearchQuery<'_>,
+        limit: u32,
+        score_threshold: Option<f32>,
+    ) -> Result<Vec<SearchResult>, VectorStoreError> {
+        match query {
+            SearchQuery::Dense(vector) => {
+                self.search_points(json!(vector), limit, score_threshold)
+                    .await
+            }
+            SearchQuery::Sparse(text) => self.search_sparse(text, limit, score_threshold).await,
+            SearchQuery::Hybrid { vector, text } => {
+                let (dense, sparse) = tokio::try_join!(
+                    self.search_points(json!(vector), limit, score_threshold),
+                    self.search_sparse(text, limit, None),
+                )?;
+                Ok(reciprocal_rank_fusion(vec![dense, sparse], limit as usize))
+            }
+        }
+    }
+
+    /// Like [`search`](Self::search), but returns at most one hit per chunked
+    /// memory: the best-scoring chunk, reported under its `parent_id`.
+    ///
+    /// Collapsing happens client-side over an over-fetched candidate set, so
+    /// fewer than `limit` results may be returned when one parent has many
+    /// matching chunks.
+    pub async fn search_collapsed(
+        &self,
+        query: SearchQuery<'_>,
+        limit: u32,
+        score_threshold: Option<f32>,
+    ) -> Result<Vec<SearchResult>, VectorStoreError> {
+        let candidates = self
+            .search(
+                query,
+                limit.saturating_mul(COLLAPSE_OVERFETCH),
+                score_threshold,
+            )
+            .await?;
+        Ok(collapse_chunks(candidates, limit as usize))
+    }
+
+    async fn search_sparse(
+        &self,
+        text: &str,
+        limit: u32,
+        score_threshold: Option<f32>,
+    ) -> Result<Vec<SearchResult>, VectorStoreError> {
+        let sparse = self
+            .sparse
+            .as_ref()
+            .ok_or_else(|| VectorStoreError::BadRequest(SPARSE_NOT_CONFIGURED_ERROR.to_string()))?;
+        let vector = sparse.encoder.encode_query(text).await?;
+        // A query without terms (e.g. only punctuation) matches nothing.
+        if vector.is_empty() {
+            return Ok(Vec::new());
+        }
+        let named = json!({ "name": sparse.vector_name, "vector": vector });
+        self.search_points(named, limit, score_threshold).await
+    }
+
+    /// Run one `points/search` request; `vector` is a dense vector or a named
+    /// vector object.
+    async fn search_points(
+        &self,
+        vector: Value,
         limit: u32,
         score_threshold: Option<f32>,
     ) -> Result<Vec<SearchResult>, VectorStoreError> {
@@ -274,10 +623,16 @@ impl QdrantStore {
         // because (a) `text` is part of SearchResult and must be returned to
         // callers, and (b) metadata keys are arbitrary and unknown at query
         // time, so we cannot enumerate them for an include filter.
+        //
+        // Expired points are excluded here, as the sweeper only deletes them
+        // periodically; points without `expires_at` never match the range.
         let mut body = json!({
             "vector": vector,
             "limit": limit,
-            "with_payload": true
+            "with_payload": true,
+            "filter": {
+                "must_not": [{ "key": EXPIRES_AT_KEY, "range": { "lte": Utc::now().timestamp() } }]
+            }
         });
 
         if let Some(threshold) = score_threshold {
@@ -285,35 +640,87 @@ impl QdrantStore {
         }
 
         let path = format!("/collections/{}/points/search", self.collection);
-        let resp = self
-            .request(reqwest::Method::POST, &path)
-            .json(&body)
-            .send()
-            .await
-            .map_err(VectorStoreError::Http)?;
-
-        if !resp.status().is_success() {
-            return Err(api_error(resp).await);
-        }
+        observed("search", async {
+            let resp = self
+                .request(reqwest::Method::POST, &path)
+                .json(&body)
+                .send()
+                .await?;
+
+            if !resp.status().is_success() {
+                return Err(api_error(resp).await);
+            }
 
-        let parsed: QdrantSearchResponse = resp.json().await.map_err(|e| {
-            VectorStoreError::InvalidResponse(format!("Failed to parse search response: {e}"))
-        })?;
+            let parsed: QdrantSearchResponse = parse_body(resp, "search response").await?;
 
-        let results = parsed
-            .result
-            .into_iter()
-            .map(SearchResult::try_from)
-            .collect::<Result<Vec<_>, _>>()?;
+            parsed
+                .result
+                .into_iter()
+                .map(SearchResult::try_from)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await
+    }
+}
 
-        Ok(results)
+/// Fuse ranked result lists with reciprocal rank fusion and keep the best
+/// `limit`.  A point in several lists is reported once, with the payload of
+/// its first occurrence; ties keep the order of the first list.
+fn reciprocal_rank_fusion(lists: Vec<Vec<SearchResult>>, limit: usize) -> Vec<SearchResult> {
+    let mut fused: Vec<SearchResult> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for list in lists {
+        for (rank, hit) in list.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+            match positions.get(&hit.id) {
+                Some(&i) => fused[i].score += score,
+                None => {
+                    positions.insert(hit.id.clone(), fused.len());
+                    fused.push(SearchResult { score, ..hit });
+                }
+            }
+        }
     }
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused.truncate(limit);
+    fused
+}
+
+/// Keep the first (best-scoring) hit per parent, in input order.
+fn collapse_chunks(results: Vec<SearchResult>, limit: usize) -> Vec<SearchResult> {
+    let mut seen = std::collections::HashSet::new();
+    results
+        .into_iter()
+        .filter_map(|mut hit| {
+            if let Some(Value::String(parent)) = hit.metadata.get(PARENT_ID_KEY) {
+                hit.id = parent.clone();
+            }
+            seen.insert(hit.id.clone()).then_some(hit)
+        })
+        .take(limit)
+        .collect()
 }
 
 // ---------------------------------------------------------------------------
 // Private helpers
 // ---------------------------------------------------------------------------
 
+/// Run one Qdrant call, recording its latency and any failure under
+/// `operation` in the Prometheus metrics.
+async fn observed<T>(
+    operation: &str,
+    call: impl Future<Output = Result<T, VectorStoreError>>,
+) -> Result<T, VectorStoreError> {
+    let started = Instant::now();
+    let result = call.await;
+    let m = metrics();
+    m.qdrant_duration.observe(&[operation], started.elapsed());
+    if result.is_err() {
+        m.qdrant_errors.inc(&[operation]);
+    }
+    result
+}
+
 /// Convert a non-2xx [`reqwest::Response`] into a [`VectorStoreError::Api`].
 ///
 /// Reads the response body for the error message, falling back to a
@@ -327,6 +734,24 @@ async fn api_error(resp: reqwest::Response) -> VectorStoreError {
     VectorStoreError::Api { status, message }
 }
 
+/// Read and deserialize the body of a successful [`reqwest::Response`].
+///
+/// `what` names the body in parse errors.  A body that stalls past the
+/// request timeout becomes [`VectorStoreError::Timeout`] rather than a parse
+/// error.
+async fn parse_body<T: serde::de::DeserializeOwned>(
+    resp: reqwest::Response,
+    what: &str,
+) -> Result<T, VectorStoreError> {
+    resp.json().await.map_err(|e| {
+        if e.is_timeout() {
+            VectorStoreError::Timeout(e.to_string())
+        } else {
+            VectorStoreError::InvalidResponse(format!("Failed to parse {what}: {e}"))
+        }
+    })
+}
+
 // ---------------------------------------------------------------------------
 // Private Qdrant response types
 // ---------------------------------------------------------------------------
@@ -382,8 +807,10 @@ impl TryFrom<QdrantHit> for SearchResult {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use wiremock::{
-        matchers::{method, path},
+        matchers::{body_partial_json, method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -397,7 +824,9 @@ mod tests {
             api_key: None,
             dimensions: 3,
             distance: "Cosine".to_string(),
+            ..Default::default()
         })
+        .unwrap()
     }
 
     // -----------------------------------------------------------------------
@@ -478,6 +907,57 @@ mod tests {
         assert!(matches!(result, Err(VectorStoreError::Api { status: 500, .. })));
     }
 
+    #[tokio::test]
+    async fn ensure_collection_accepts_matching_params() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/collections/test_col"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": {
+                    "status": "green",
+                    "config": { "params": { "vectors": { "size": 3, "distance": "Cosine" } } }
+                },
+                "status": "ok"
+            })))
+            .mount(&server)
+            .await;
+
+        make_store(&server.uri())
+            .ensure_collection()
+            .await
+            .expect("matching collection should be accepted");
+    }
+
+    #[tokio::test]
+    async fn ensure_collection_rejects_mismatched_params() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/collections/test_col"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": {
+                    "status": "green",
+                    "config": { "params": { "vectors": { "size": 1536, "distance": "Dot" } } }
+                },
+                "status": "ok"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let err = make_store(&server.uri())
+            .ensure_collection()
+            .await
+            .expect_err("mismatched collection must be rejected");
+        let message = err.to_string();
+        assert!(matches!(err, VectorStoreError::CollectionMismatch(_)));
+        assert!(
+            message.contains("1536") && message.contains("Dot"),
+            "{message}"
+        );
+    }
+
     // -----------------------------------------------------------------------
     // upsert
     // -----------------------------------------------------------------------
@@ -522,6 +1002,11 @@ mod tests {
             })))
             .mount(&server)
             .await;
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/delete"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" })))
+            .mount(&server)
+            .await;
 
         let store = make_store(&server.uri());
         let returned_id = store
@@ -566,10 +1051,293 @@ mod tests {
         assert!(matches!(result, Err(VectorStoreError::Api { status: 400, .. })));
     }
 
+    /// Matches a delete request whose filter removes the point `id` and its
+    /// chunks while keeping the points written by the same request.
+    fn deletes_stale_points_of(id: String) -> impl Fn(&wiremock::Request) -> bool {
+        move |req: &wiremock::Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap_or_default();
+            let filter = &body["filter"];
+            filter["should"]
+                == json!([
+                    { "has_id": [id] },
+                    { "key": PARENT_ID_KEY, "match": { "value": id } }
+                ])
+                && filter["must_not"][0]["has_id"]
+                    .as_array()
+                    .is_some_and(|keep| !keep.is_empty())
+        }
+    }
+
+    /// Records the order of the write and delete requests sent to the store.
+    struct Recorder {
+        log: Arc<Mutex<Vec<&'static str>>>,
+        entry: &'static str,
+    }
+
+    impl wiremock::Respond for Recorder {
+        fn respond(&self, _req: &wiremock::Request) -> ResponseTemplate {
+            self.log.lock().unwrap().push(self.entry);
+            ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" }))
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_with_supplied_id_replaces_old_chunks_after_writing() {
+        let server = MockServer::start().await;
+        let id = Uuid::parse_str("00000000-0000-0000-0000-000000000003").unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .respond_with(Recorder {
+                log: log.clone(),
+                entry: "put",
+            })
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/delete"))
+            .and(deletes_stale_points_of(id.to_string()))
+            .and(body_partial_json(json!({
+                "filter": { "must_not": [{ "has_id": [id.to_string()] }] }
+            })))
+            .respond_with(Recorder {
+                log: log.clone(),
+                entry: "delete",
+            })
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        make_store(&server.uri())
+            .upsert(
+                Some(id),
+                vec![0.1, 0.2, 0.3],
+                "short".to_string(),
+                HashMap::new(),
+            )
+            .await
+            .expect("upsert should succeed");
+        assert_eq!(*log.lock().unwrap(), ["put", "delete"]);
+    }
+
+    #[tokio::test]
+    async fn upsert_keeps_old_points_when_write_fails() {
+        let server = MockServer::start().await;
+        let id = Uuid::parse_str("00000000-0000-0000-0000-000000000004").unwrap();
+
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/delete"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" })))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let result = make_store(&server.uri())
+            .upsert(
+                Some(id),
+                vec![0.1, 0.2, 0.3],
+                "short".to_string(),
+                HashMap::new(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(VectorStoreError::Api { status: 500, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn upsert_chunks_clears_old_chunks_and_tags_payloads() {
+        let server = MockServer::start().await;
+        let parent = Uuid::parse_str("00000000-0000-0000-0000-000000000002").unwrap();
+
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/delete"))
+            .and(deletes_stale_points_of(parent.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .and(body_partial_json(json!({
+                "points": [
+                    { "payload": { "text": "one", "parent_id": parent.to_string(), "chunk_index": 0, "chunk_count": 2, "source": "t" } },
+                    { "payload": { "text": "two", "parent_id": parent.to_string(), "chunk_index": 1, "chunk_count": 2, "source": "t" } }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let metadata = HashMap::from([("source".to_string(), json!("t"))]);
+        let id = make_store(&server.uri())
+            .upsert_chunks(
+                Some(parent),
+                vec![
+                    ("one".to_string(), vec![0.1, 0.2, 0.3]),
+                    ("two".to_string(), vec![0.3, 0.2, 0.1]),
+                ],
+                metadata,
+            )
+            .await
+            .expect("upsert_chunks should succeed");
+        assert_eq!(id, parent.to_string());
+    }
+
+    #[tokio::test]
+    async fn upsert_chunks_replaces_unchunked_point_after_writing() {
+        let server = MockServer::start().await;
+        let parent = Uuid::parse_str("00000000-0000-0000-0000-000000000005").unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .respond_with(Recorder {
+                log: log.clone(),
+                entry: "put",
+            })
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/delete"))
+            .and(deletes_stale_points_of(parent.to_string()))
+            .respond_with(Recorder {
+                log: log.clone(),
+                entry: "delete",
+            })
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        make_store(&server.uri())
+            .upsert_chunks(
+                Some(parent),
+                vec![
+                    ("one".to_string(), vec![0.1, 0.2, 0.3]),
+                    ("two".to_string(), vec![0.3, 0.2, 0.1]),
+                ],
+                HashMap::new(),
+            )
+            .await
+            .expect("upsert_chunks should succeed");
+        assert_eq!(*log.lock().unwrap(), ["put", "delete"]);
+
+        // The delete keeps exactly the chunk points that were just written.
+        let requests = server.received_requests().await.unwrap();
+        let put: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let delete: Value = serde_json::from_slice(&requests[1].body).unwrap();
+        let written: Vec<&Value> = put["points"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|point| &point["id"])
+            .collect();
+        let kept: Vec<&Value> = delete["filter"]["must_not"][0]["has_id"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .collect();
+        assert_eq!(kept, written);
+    }
+
+    /// Matches a request whose JSON body has a range condition on
+    /// `expires_at` at `pointer` with an `lte` bound of about now.
+    fn expiry_range_at(pointer: &'static str) -> impl Fn(&wiremock::Request) -> bool {
+        move |req: &wiremock::Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap_or_default();
+            let condition = &body.pointer(pointer).cloned().unwrap_or_default();
+            condition["key"] == EXPIRES_AT_KEY
+                && condition["range"]["lte"]
+                    .as_i64()
+                    .is_some_and(|lte| (lte - Utc::now().timestamp()).abs() <= 5)
+        }
+    }
+
+    #[tokio::test]
+    async fn purge_expired_deletes_points_past_their_expiry() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/delete"))
+            .and(expiry_range_at("/filter/must/0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        make_store(&server.uri())
+            .purge_expired()
+            .await
+            .expect("purge should succeed");
+    }
+
     // -----------------------------------------------------------------------
     // search
     // -----------------------------------------------------------------------
 
+    #[tokio::test]
+    async fn search_excludes_expired_points() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/search"))
+            .and(expiry_range_at("/filter/must_not/0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": [],
+                "status": "ok",
+                "time": 0.001
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let results = make_store(&server.uri())
+            .search(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 5, None)
+            .await
+            .expect("search should succeed");
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_collapsed_keeps_best_chunk_per_parent() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/search"))
+            .and(body_partial_json(json!({ "limit": 8 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": [
+                    { "id": "c1", "score": 0.9, "payload": { "text": "chunk 1", "parent_id": "p", "chunk_index": 1 } },
+                    { "id": "c0", "score": 0.8, "payload": { "text": "chunk 0", "parent_id": "p", "chunk_index": 0 } },
+                    { "id": "solo", "score": 0.7, "payload": { "text": "standalone" } }
+                ],
+                "status": "ok",
+                "time": 0.001
+            })))
+            .mount(&server)
+            .await;
+
+        let results = make_store(&server.uri())
+            .search_collapsed(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 2, None)
+            .await
+            .expect("search should succeed");
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, "p");
+        assert_eq!(results[0].text, "chunk 1");
+        assert_eq!(results[1].id, "solo");
+    }
+
     #[tokio::test]
     async fn search_returns_results() {
         let server = MockServer::start().await;
@@ -596,7 +1364,7 @@ mod tests {
             .await;
 
         let results = make_store(&server.uri())
-            .search(vec![0.1, 0.2, 0.3], 5, None)
+            .search(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 5, None)
             .await
             .expect("search should succeed");
 
@@ -627,7 +1395,7 @@ mod tests {
             .await;
 
         let results = make_store(&server.uri())
-            .search(vec![0.1, 0.2, 0.3], 5, Some(0.8))
+            .search(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 5, Some(0.8))
             .await
             .expect("search should succeed with empty results");
 
@@ -645,9 +1413,262 @@ mod tests {
             .await;
 
         let result = make_store(&server.uri())
-            .search(vec![0.1, 0.2, 0.3], 5, None)
+            .search(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 5, None)
             .await;
 
         assert!(matches!(result, Err(VectorStoreError::Api { status: 503, .. })));
     }
+
+    // -----------------------------------------------------------------------
+    // sparse vectors and hybrid search
+    // -----------------------------------------------------------------------
+
+    /// Encodes every text as the same one-term sparse vector.
+    struct FixedEncoder;
+
+    #[async_trait::async_trait]
+    impl crate::sparse::SparseEncoder for FixedEncoder {
+        async fn encode_documents(
+            &self,
+            texts: &[String],
+        ) -> Result<Vec<SparseVector>, EmbeddingError> {
+            Ok(texts.iter().map(|_| fixed_sparse()).collect())
+        }
+
+        async fn encode_query(&self, _text: &str) -> Result<SparseVector, EmbeddingError> {
+            Ok(fixed_sparse())
+        }
+    }
+
+    fn fixed_sparse() -> SparseVector {
+        SparseVector {
+            indices: vec![7],
+            values: vec![1.5],
+        }
+    }
+
+    fn make_sparse_store(base_url: &str) -> QdrantStore {
+        make_store(base_url).with_sparse_encoder("text-sparse", std::sync::Arc::new(FixedEncoder))
+    }
+
+    fn hit(id: &str, score: f32) -> Value {
+        json!({ "id": id, "score": score, "payload": { "text": id } })
+    }
+
+    #[tokio::test]
+    async fn ensure_collection_creates_and_requires_sparse_vector() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/collections/test_col"))
+            .respond_with(ResponseTemplate::new(404))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col"))
+            .and(body_partial_json(json!({
+                "vectors": { "size": 3, "distance": "Cosine" },
+                "sparse_vectors": { "text-sparse": {} }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = make_sparse_store(&server.uri());
+        store
+            .ensure_collection()
+            .await
+            .expect("collection should be created");
+
+        // An existing collection without the sparse vector is rejected.
+        Mock::given(method("GET"))
+            .and(path("/collections/test_col"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": { "config": { "params": {
+                    "vectors": { "size": 3, "distance": "Cosine" }
+                } } }
+            })))
+            .mount(&server)
+            .await;
+        let err = store.ensure_collection().await.unwrap_err();
+        assert!(
+            matches!(&err, VectorStoreError::CollectionMismatch(msg) if msg.contains("text-sparse")),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn upsert_stores_dense_and_sparse_vectors() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .and(body_partial_json(json!({
+                "points": [{
+                    "vector": {
+                        "": [0.5, 0.25, 1.0],
+                        "text-sparse": { "indices": [7], "values": [1.5] }
+                    },
+                    "payload": { "text": "ERR_CONN_RESET on deploy" }
+                }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": {} })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        make_sparse_store(&server.uri())
+            .upsert(
+                None,
+                vec![0.5, 0.25, 1.0],
+                "ERR_CONN_RESET on deploy".to_string(),
+                HashMap::new(),
+            )
+            .await
+            .expect("upsert should succeed");
+    }
+
+    #[tokio::test]
+    async fn bm25_statistics_are_recorded_only_after_a_successful_write() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/collections/test_col/points"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": {} })))
+            .mount(&server)
+            .await;
+
+        use crate::sparse::{bm25::Bm25Encoder, SparseEncoder};
+
+        let cfg = toml::from_str(r#"type = "bm25""#).unwrap();
+        let encoder = Arc::new(Bm25Encoder::load(&cfg, None).await.unwrap());
+        let store = make_store(&server.uri()).with_sparse_encoder("text-sparse", encoder.clone());
+        let unseen = encoder.encode_query("deploy").await.unwrap();
+
+        let upsert = || {
+            store.upsert(
+                None,
+                vec![0.1, 0.2, 0.3],
+                "deploy".to_string(),
+                HashMap::new(),
+            )
+        };
+        assert!(upsert().await.is_err());
+        assert_eq!(encoder.encode_query("deploy").await.unwrap(), unseen);
+
+        upsert().await.expect("upsert should succeed");
+        assert_ne!(encoder.encode_query("deploy").await.unwrap(), unseen);
+    }
+
+    #[tokio::test]
+    async fn hybrid_search_fuses_ranks() {
+        let server = MockServer::start().await;
+
+        // Sparse leg: matched by the named vector, preferred over the dense mock.
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/search"))
+            .and(body_partial_json(json!({
+                "vector": { "name": "text-sparse", "vector": { "indices": [7] } }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": [hit("exact", 9.0), hit("both", 4.0)]
+            })))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": [hit("semantic", 0.9), hit("both", 0.8), hit("other", 0.7)]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let results = make_sparse_store(&server.uri())
+            .search(
+                SearchQuery::Hybrid {
+                    vector: vec![0.1, 0.2, 0.3],
+                    text: "ERR_CONN_RESET",
+                },
+                3,
+                None,
+            )
+            .await
+            .expect("hybrid search should succeed");
+
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        // "both" is ranked by both legs; "semantic" wins the tie with "exact"
+        // because dense results come first.
+        assert_eq!(ids, vec!["both", "semantic", "exact"]);
+        assert!((results[0].score - (1.0 / 62.0 + 1.0 / 62.0)).abs() < 1e-6);
+        assert!((results[1].score - 1.0 / 61.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn sparse_search_requires_sparse_configuration() {
+        let result = make_store("http://127.0.0.1:1")
+            .search(SearchQuery::Sparse("ERR_CONN_RESET"), 5, None)
+            .await;
+        assert!(matches!(result, Err(VectorStoreError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn slow_qdrant_fails_with_timeout() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/collections/test_col/points/search"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_delay(Duration::from_millis(500))
+                    .set_body_json(json!({ "result": [] })),
+            )
+            .mount(&server)
+            .await;
+
+        let mut cfg = QdrantConfig {
+            url: server.uri(),
+            collection: "test_col".to_string(),
+            ..Default::default()
+        };
+        cfg.http.request_timeout_ms = Some(50);
+        let result = QdrantStore::new(&cfg)
+            .unwrap()
+            .search(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 5, None)
+            .await;
+
+        assert!(
+            matches!(result, Err(VectorStoreError::Timeout(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn stalled_qdrant_body_fails_with_timeout() {
+        let mut cfg = QdrantConfig {
+            url: crate::http_client::stalled_body_server().await,
+            collection: "test_col".to_string(),
+            ..Default::default()
+        };
+        cfg.http.request_timeout_ms = Some(100);
+        let result = QdrantStore::new(&cfg)
+            .unwrap()
+            .search(SearchQuery::Dense(vec![0.1, 0.2, 0.3]), 5, None)
+            .await;
+
+        assert!(
+            matches!(result, Err(VectorStoreError::Timeout(_))),
+            "{result:?}"
+        );
+    }
 }